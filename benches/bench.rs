@@ -4,7 +4,12 @@ extern crate criterion;
 use criterion::{black_box, Benchmark, Criterion};
 use lipsum::lipsum;
 
-use iscc::{content_id_image, content_id_text, data_id, instance_id};
+#[cfg(feature = "image")]
+use iscc::content_id_image;
+use iscc::hashes::{
+    minimum_hash, sliding_window, sliding_window_slices, text_features, FeatureDedup, MinHasher,
+};
+use iscc::{content_id_text, data_id, instance_id};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let test_text = lipsum(1000);
@@ -15,6 +20,49 @@ fn criterion_benchmark(c: &mut Criterion) {
         })
         .sample_size(200),
     );
+    let document = lipsum(10000);
+    let slices_document = document.clone();
+    c.bench(
+        "sliding-window",
+        Benchmark::new("owned", move |b| {
+            b.iter(|| sliding_window(black_box(&document), black_box(13)))
+        })
+        .with_function("slices", move |b| {
+            b.iter(|| sliding_window_slices(black_box(&slices_document), black_box(13)))
+        }),
+    );
+    let log: String = (0..20_000)
+        .map(|i| format!("INFO request {} served in {} ms\n", i % 50, i % 7))
+        .collect();
+    let log_features: Vec<u32> = text_features(&log, 13).collect();
+    let exact_features = log_features.clone();
+    let bloom_features = log_features.clone();
+    c.bench(
+        "minhash-log",
+        Benchmark::new("minimum-hash", move |b| {
+            b.iter(|| minimum_hash(black_box(log_features.iter().copied())))
+        })
+        .with_function("push-unique-exact", move |b| {
+            b.iter(|| {
+                let mut hasher = MinHasher::new();
+                for &f in black_box(&exact_features) {
+                    hasher.push_unique(f);
+                }
+                hasher.finish()
+            })
+        })
+        .with_function("push-unique-bloom", move |b| {
+            b.iter(|| {
+                let mut hasher =
+                    MinHasher::with_dedup(FeatureDedup::Bloom { bits: 1 << 16 }).unwrap();
+                for &f in black_box(&bloom_features) {
+                    hasher.push_unique(f);
+                }
+                hasher.finish()
+            })
+        }),
+    );
+    #[cfg(feature = "image")]
     c.bench_function("content-id-image", |b| {
         b.iter(|| content_id_image(black_box("tests/test_data/lenna.jpg"), black_box(false)))
     });
@@ -0,0 +1,40 @@
+//! Throughput of the Data-ID and the Content-ID-Text on the inputs of the
+//! `bench` harness, run with `cargo bench --features bench --bench throughput`.
+#[macro_use]
+extern crate criterion;
+
+use criterion::{black_box, Criterion, ParameterizedBenchmark, Throughput};
+
+use iscc::bench::{data_code, data_input, text_code, text_input};
+
+fn throughput_benchmark(c: &mut Criterion) {
+    c.bench(
+        "throughput-data-id",
+        ParameterizedBenchmark::new(
+            "data-id",
+            |b, &size| {
+                let data = data_input(size);
+                b.iter(|| data_code(black_box(&data)))
+            },
+            vec![1 << 16, 1 << 20, 1 << 24],
+        )
+        .throughput(|&size| Throughput::Bytes(size as u32))
+        .sample_size(20),
+    );
+    c.bench(
+        "throughput-content-id-text",
+        ParameterizedBenchmark::new(
+            "content-id-text",
+            |b, &chars| {
+                let text = text_input(chars);
+                b.iter(|| text_code(black_box(&text)))
+            },
+            vec![1_000, 100_000, 1_000_000],
+        )
+        .throughput(|&chars| Throughput::Elements(chars as u32))
+        .sample_size(20),
+    );
+}
+
+criterion_group!(benches, throughput_benchmark);
+criterion_main!(benches);
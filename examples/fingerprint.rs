@@ -0,0 +1,30 @@
+//! Fingerprints two texts and a file and compares the text codes.
+//!
+//! Run with `cargo run --example fingerprint`.
+use std::error::Error;
+
+use iscc::prelude::*;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let original = "The quick brown fox jumps over the lazy dog.";
+    let edited = "The quick brown fox jumped over the lazy dog!";
+
+    let a: Code = content_id_text(original, false).parse()?;
+    let b: Code = content_id_text(edited, false).parse()?;
+    let distance = hamming_distance_u64(
+        a.body_u64().expect("64-bit code"),
+        b.body_u64().expect("64-bit code"),
+    );
+    println!("Content-ID-Text {} vs {}: {} bits differ", a, b, distance);
+
+    let mut hasher = DataHasher::new();
+    hasher.update(original.as_bytes());
+    let did = hasher.finish()?;
+    println!("Data-ID of the original text: {}", did);
+
+    let did = data_id("tests/test_data/mediafile.html")?;
+    let (iid, tophash) = instance_id("tests/test_data/mediafile.html")?;
+    println!("mediafile.html: {} {} ({})", did, iid, tophash);
+
+    Ok(())
+}
@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use iscc::code::Code;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(code) = std::str::from_utf8(data) {
+        if let Ok(parsed) = code.parse::<Code>() {
+            assert_eq!(parsed.to_string(), code);
+            assert_eq!(Code::from_bytes(&parsed.to_bytes()).unwrap(), parsed);
+        }
+    }
+    let _ = Code::from_bytes(data);
+});
@@ -0,0 +1,16 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use iscc::base58::{decode_checked, encode, try_decode};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(code) = std::str::from_utf8(data) {
+        if let Ok(digest) = try_decode(code) {
+            // Leading `C` pads only the full 13-character code.
+            if code.len() == 13 {
+                assert_eq!(encode(&digest), code);
+            }
+        }
+        let _ = decode_checked(code);
+    }
+});
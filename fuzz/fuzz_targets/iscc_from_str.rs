@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use iscc::code::Iscc;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(iscc) = std::str::from_utf8(data) {
+        if let Ok(parsed) = iscc.parse::<Iscc>() {
+            assert_eq!(parsed.to_string(), iscc);
+        }
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use iscc::code::{pack_codes, unpack_codes, CodeReader};
+
+fuzz_target!(|data: &[u8]| {
+    let unpacked = unpack_codes(data);
+    if let Ok(codes) = &unpacked {
+        assert_eq!(pack_codes(codes), data);
+    }
+    // The streaming reader agrees with `unpack_codes`.
+    let streamed = CodeReader::new(data).and_then(|reader| reader.collect::<Result<Vec<_>, _>>());
+    assert_eq!(streamed.is_ok(), unpacked.is_ok());
+});
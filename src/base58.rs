@@ -7,7 +7,23 @@ Wilcox-O'Hearn](https://en.wikipedia.org/wiki/Zooko_Wilcox-O%27Hearn) but
 with a 58-character symbol table.
 
 `SYMBOLS = "C23456789rB1ZEFGTtYiAaVvMmHUPWXKDNbcdefghLjkSnopRqsJuQwxyz"`
+
+The optional checked form of a code appends 3 characters encoding the first
+two bytes of the `xxhash32` of the component digest, see `encode_checked`.
+
+Base58-ISCC is case sensitive: 23 letters appear in both cases with different
+values, e.g. the headers `CT` and `Ct` differ only in the partial content
+flag. The encoded codes are canonical and decoding accepts exactly these
+codes, case variants are never folded. An invalid code that is entirely in
+upper or lower case, as written by systems that change the case of
+identifiers, is reported as `IsccError::NonCanonicalCase` if it is valid
+with the case of its letters swapped. A valid code in changed case decodes
+to a different digest, only checked codes detect this. Systems that do not
+preserve case should store codes in hex, see `code::canonicalize`.
  */
+use crate::error::IsccError;
+use crate::hashes::xxhash32;
+
 const SYMBOLS: [char; 58] = [
     'C', '2', '3', '4', '5', '6', '7', '8', '9', 'r', 'B', '1', 'Z', 'E', 'F', 'G', 'T', 't', 'Y',
     'i', 'A', 'a', 'V', 'v', 'M', 'm', 'H', 'U', 'P', 'W', 'X', 'K', 'D', 'N', 'b', 'c', 'd', 'e',
@@ -17,20 +33,21 @@ const SYMBOLS: [char; 58] = [
 
 /// The `encode` function accepts a 9-byte **ISCC Component Digest** and returns
 /// the Base58-ISCC encoded alphanumeric string of 13 characters which we call
-/// the **ISCC-Component Code**.
+/// the **ISCC-Component Code**. The header byte of a longer digest with a
+/// body of 16, 24 or 32 bytes, see `code::Code::to_bytes`, is followed by 11
+/// characters for every 8 bytes of the body.
 pub fn encode(digest: &[u8]) -> String {
-    if digest.len() == 9 {
-        let full_encode = format!(
-            // Pad with C, base58 "zeros"
-            "{:C>2}{:C>11}",
-            encode(&digest[..1]),
-            encode(&digest[1..])
-        );
+    if digest.len() > 8 && (digest.len() - 1).is_multiple_of(8) {
+        // Pad with C, base58 "zeros"
+        let mut full_encode = format!("{:C>2}", encode(&digest[..1]));
+        for word in digest[1..].chunks(8) {
+            full_encode.push_str(&format!("{:C>11}", encode(word)));
+        }
         return full_encode;
     }
     assert!(
         digest.len() == 1 || digest.len() == 8,
-        "Digest must be 1, 8 or 9 bytes long"
+        "Digest must be 1, 8 or 1 + 8n bytes long"
     );
     let mut array: [u8; 16] = [0; 16];
     array[16 - digest.len()..].copy_from_slice(&digest);
@@ -46,41 +63,212 @@ pub fn encode(digest: &[u8]) -> String {
 }
 
 /// The `decode` function accepts a 13-character **ISCC-Component Code** and
-/// returns the corresponding 9-byte **ISCC-Component Digest**.
+/// returns the corresponding 9-byte **ISCC-Component Digest**. The
+/// 2-character header and the 11-character body are decoded on their own to 1
+/// and 8 bytes.
+///
+/// Panics if `code` is invalid, use [`try_decode`] for untrusted input.
 pub fn decode(code: &str) -> Vec<u8> {
-    let n = code.len();
-    if n == 13 {
-        return decode(&code[..2])
-            .into_iter()
-            .chain(decode(&code[2..]).into_iter())
-            .collect();
+    try_decode(code).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Like [`decode`], but returns [`IsccError::InvalidArgument`] instead of
+/// panicking if `code` is not 2, 11 or 13 characters long, contains other
+/// characters than the Base58-ISCC symbols or is out of range.
+pub fn try_decode(code: &str) -> Result<Vec<u8>, IsccError> {
+    check_case(code, decode_parts)
+}
+
+fn decode_parts(code: &str) -> Result<Vec<u8>, IsccError> {
+    match code.len() {
+        13 if code.is_ascii() => {
+            let mut digest = decode_parts(&code[..2])?;
+            digest.extend(decode_parts(&code[2..])?);
+            Ok(digest)
+        }
+        2 => {
+            let num = decode_fixed(code)?;
+            if num > 0xFF {
+                return Err(IsccError::InvalidArgument(format!(
+                    "The first two characters encode the 1-byte component header and \
+                     have to be < 256. But '{}' is {}.",
+                    code, num,
+                )));
+            }
+            Ok(vec![num as u8])
+        }
+        11 => {
+            let num = decode_fixed(code)?;
+            if num > u128::from(u64::MAX) {
+                return Err(IsccError::InvalidArgument(format!(
+                    "'{}' is out of range for a component body",
+                    code
+                )));
+            }
+            Ok((num as u64).to_be_bytes().to_vec())
+        }
+        _ => Err(IsccError::InvalidArgument(format!(
+            "Code must be 2, 11 or 13 chars. Not {}",
+            code.chars().count()
+        ))),
     }
-    assert!(
-        n == 2 || n == 11,
-        "Code must be 2, 11 or 13 chars. Not {}",
-        n
-    );
-    let base: usize = 58;
-    let mut num: u64 = 0;
-
-    for (i, chr) in code.chars().enumerate() {
-        let power = n - (i + 1);
-        num += (SYMBOLS.iter().position(|c| c == &chr).unwrap() * (base.pow(power as u32))) as u64;
-    }
-    if n == 2 {
-        assert!(
-            num < 256,
-            "The first two characters encode the 1-byte component header and \
-             have to be < 256. But '{}' is {}.",
-            code,
-            num,
-        );
-        let bytes: [u8; 1] = (num as u8).to_be_bytes();
-        bytes.to_vec()
-    } else {
-        let bytes: [u8; 8] = num.to_be_bytes();
-        bytes.to_vec()
+}
+
+const CHECKSUM_LEN: usize = 3;
+
+/// Encodes a 9-byte **ISCC Component Digest** like [`encode`] and appends a
+/// checksum of 3 characters such that transcription errors are detected by
+/// [`decode_checked`]. The checksum is built from the first two bytes of the
+/// `xxhash32` of the digest.
+pub fn encode_checked(digest: &[u8]) -> String {
+    assert!(digest.len() == 9, "Digest must be 9 bytes long");
+    let mut code = encode(digest);
+    code.push_str(&encode_fixed(u64::from(checksum(digest)), CHECKSUM_LEN));
+    code
+}
+
+/// Decodes a 16-character checked code as created by [`encode_checked`] to the
+/// 9-byte **ISCC-Component Digest**. Returns
+/// [`IsccError::ChecksumMismatch`] if the checksum does not match the digest.
+/// Unlike [`decode`] it never panics on invalid input.
+pub fn decode_checked(code: &str) -> Result<Vec<u8>, IsccError> {
+    check_case(code, decode_checked_parts)
+}
+
+fn decode_checked_parts(code: &str) -> Result<Vec<u8>, IsccError> {
+    let n = code.chars().count();
+    if n != 13 + CHECKSUM_LEN {
+        return Err(IsccError::InvalidArgument(format!(
+            "A checked code must be {} chars, not {}. Plain 13 char codes have no checksum.",
+            13 + CHECKSUM_LEN,
+            n
+        )));
+    }
+    if !code.is_ascii() {
+        return Err(IsccError::InvalidArgument(format!(
+            "'{}' is not a Base58-ISCC code",
+            code
+        )));
+    }
+    let digest = try_decode_component(&code[..13])?;
+    let actual = decode_fixed(&code[13..])?;
+    if actual > 0xFFFF {
+        return Err(IsccError::InvalidArgument(format!(
+            "'{}' is out of range for a checksum",
+            &code[13..]
+        )));
+    }
+
+    let expected = checksum(&digest);
+    if expected != actual as u16 {
+        return Err(IsccError::ChecksumMismatch {
+            expected,
+            actual: actual as u16,
+        });
+    }
+    Ok(digest)
+}
+
+/// Like [`try_decode`], but only accepts 13-character codes.
+pub(crate) fn try_decode_component(code: &str) -> Result<Vec<u8>, IsccError> {
+    let n = code.chars().count();
+    if n != 13 || code.len() != 13 {
+        return Err(IsccError::InvalidArgument(format!(
+            "Code must be 13 chars. Not {}",
+            n
+        )));
+    }
+    try_decode_words(code)
+}
+
+/// Decodes the 2-character header followed by any number of 11-character
+/// words, each an 8-byte part of the body.
+pub(crate) fn try_decode_words(code: &str) -> Result<Vec<u8>, IsccError> {
+    check_case(code, decode_words)
+}
+
+fn decode_words(code: &str) -> Result<Vec<u8>, IsccError> {
+    if !code.is_ascii() || code.len() < 13 || !(code.len() - 2).is_multiple_of(11) {
+        return Err(IsccError::InvalidArgument(format!(
+            "'{}' is not a Base58-ISCC code",
+            code
+        )));
+    }
+    let out_of_range =
+        || IsccError::InvalidArgument(format!("'{}' is out of range for a component code", code));
+    let header = decode_fixed(&code[..2])?;
+    if header > 0xFF {
+        return Err(out_of_range());
+    }
+
+    let mut digest = vec![header as u8];
+    for i in (2..code.len()).step_by(11) {
+        let word = decode_fixed(&code[i..i + 11])?;
+        if word > u128::from(u64::MAX) {
+            return Err(out_of_range());
+        }
+        digest.extend(&(word as u64).to_be_bytes());
     }
+    Ok(digest)
+}
+
+/// Reports the error of an invalid `code` that has letters in only one case
+/// as [`IsccError::NonCanonicalCase`] if `decode` accepts the code with the
+/// case of its letters swapped. Other errors are returned unchanged.
+fn check_case(
+    code: &str,
+    decode: fn(&str) -> Result<Vec<u8>, IsccError>,
+) -> Result<Vec<u8>, IsccError> {
+    decode(code).map_err(|e| {
+        let upper = code.chars().any(|c| c.is_ascii_uppercase());
+        let lower = code.chars().any(|c| c.is_ascii_lowercase());
+        if upper == lower {
+            return e;
+        }
+        let swapped: String = code
+            .chars()
+            .map(|c| {
+                if upper {
+                    c.to_ascii_lowercase()
+                } else {
+                    c.to_ascii_uppercase()
+                }
+            })
+            .collect();
+        match decode(&swapped) {
+            Ok(_) => IsccError::NonCanonicalCase {
+                code: code.to_string(),
+                expected: swapped,
+            },
+            Err(_) => e,
+        }
+    })
+}
+
+fn checksum(digest: &[u8]) -> u16 {
+    (xxhash32(digest) >> 16) as u16
+}
+
+/// Encodes `num` to exactly `width` characters padded with `C`.
+fn encode_fixed(mut num: u64, width: usize) -> String {
+    let mut chars: Vec<char> = vec![SYMBOLS[0]; width];
+    for chr in chars.iter_mut().rev() {
+        *chr = SYMBOLS[(num % 58) as usize];
+        num /= 58;
+    }
+    chars.into_iter().collect()
+}
+
+/// Decodes up to 21 characters to a number.
+fn decode_fixed(code: &str) -> Result<u128, IsccError> {
+    code.chars().try_fold(0u128, |num, chr| {
+        let value = SYMBOLS.iter().position(|c| c == &chr).ok_or_else(|| {
+            IsccError::InvalidArgument(format!("'{}' is not a Base58-ISCC character", chr))
+        })?;
+        num.checked_mul(58)
+            .and_then(|num| num.checked_add(value as u128))
+            .ok_or_else(|| IsccError::InvalidArgument(format!("'{}' is out of range", code)))
+    })
 }
 
 #[cfg(test)]
@@ -114,4 +302,107 @@ mod tests {
         decode(code);
     }
 
+    #[test]
+    fn test_try_decode_invalid() {
+        assert_eq!(
+            try_decode("5GcQF7sC3iY2i").unwrap(),
+            decode("5GcQF7sC3iY2i")
+        );
+        assert_eq!(try_decode("5G").unwrap(), vec![0xF7]);
+        for code in &[
+            "",
+            "1H",
+            "0C",
+            // Not on a char boundary after the header
+            "CäxpnrJkvQDH",
+            "CR6xpnrJkvQä",
+            "CR6xpnrJkvQDH1",
+        ] {
+            assert!(try_decode(code).is_err(), "{}", code);
+        }
+    }
+    #[test]
+    fn test_regression_accumulator_overflow() {
+        // Found by fuzzing: 11 characters can exceed the 64-bit body. The
+        // codes are in mixed case, in one case they would be case variants.
+        for code in &["zzzzzzzzzzZ", "CCzzzzzzzzzzz", "CCzzzzzzzzzzzzzzzzzzzzz"] {
+            assert!(
+                matches!(try_decode(code), Err(IsccError::InvalidArgument(_))),
+                "{}",
+                code
+            );
+        }
+        assert!(matches!(
+            try_decode_words(&format!("CC{}", "z".repeat(11 * 4))),
+            Err(IsccError::InvalidArgument(_))
+        ));
+    }
+    #[test]
+    fn test_non_canonical_case() {
+        // The partial content flag is the case of the second character.
+        assert_eq!(decode("CT7A4zpmccuEv")[0], 0x10);
+        assert_eq!(decode("Ct7A4zpmccuEv")[0], 0x11);
+        // A valid code with letters in only one case
+        assert_eq!(hex::encode(decode("CCDZZ1THKDQ5A")), "00c092f9ecb87de120");
+        match try_decode("ccdzz1thkdq5a") {
+            Err(e @ IsccError::NonCanonicalCase { .. }) => assert_eq!(
+                e.to_string(),
+                "'ccdzz1thkdq5a' is not in canonical case: Base58-ISCC codes are case \
+                 sensitive, expected 'CCDZZ1THKDQ5A'"
+            ),
+            other => panic!("{:?}", other),
+        }
+        match decode_checked("ccdzz1thkdq5a9tc") {
+            Err(IsccError::NonCanonicalCase { expected, .. }) => {
+                assert_eq!(expected, "CCDZZ1THKDQ5A9TC")
+            }
+            other => panic!("{:?}", other),
+        }
+        // Invalid in either case, the original error is returned
+        for code in &["CCH2TW8YIFHSN", "cch2tw8yifhsn", "zzzzzzzzzzzzz"] {
+            match try_decode(code) {
+                Err(IsccError::InvalidArgument(_)) => {}
+                other => panic!("{}: {:?}", code, other),
+            }
+        }
+    }
+    #[test]
+    fn test_checked() {
+        let digest = decode("5GcQF7sC3iY2i");
+        let checked = encode_checked(&digest);
+        assert_eq!(checked.len(), 16);
+        assert!(checked.starts_with("5GcQF7sC3iY2i"));
+        assert_eq!(decode_checked(&checked).unwrap(), digest);
+
+        let digest = vec![0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(decode_checked(&encode_checked(&digest)).unwrap(), digest);
+        let digest = vec![0xFF; 9];
+        assert_eq!(decode_checked(&encode_checked(&digest)).unwrap(), digest);
+    }
+    #[test]
+    fn test_checked_substitutions() {
+        let checked = encode_checked(&decode("5GcQF7sC3iY2i"));
+        let chars: Vec<char> = checked.chars().collect();
+        for i in 0..chars.len() {
+            for &symbol in SYMBOLS.iter().filter(|&&s| s != chars[i]) {
+                let mut typo = chars.clone();
+                typo[i] = symbol;
+                let typo: String = typo.into_iter().collect();
+                assert!(decode_checked(&typo).is_err(), "{}", typo);
+            }
+        }
+    }
+    #[test]
+    fn test_checked_invalid() {
+        let err = decode_checked("5GcQF7sC3iY2i").unwrap_err();
+        assert!(err.to_string().contains("Plain 13 char codes"), "{}", err);
+        assert!(decode_checked("5GcQF7sC3iY2i0CC").is_err());
+        assert!(decode_checked("zzcQF7sC3iY2iCCC").is_err());
+        assert!(decode_checked("5GzzzzzzzzzzzCCC").is_err());
+        match decode_checked("5GcQF7sC3iY2iCCC") {
+            Err(IsccError::ChecksumMismatch { actual, .. }) => assert_eq!(actual, 0),
+            other => panic!("{:?}", other),
+        }
+    }
+
 }
@@ -0,0 +1,165 @@
+//! Throughput Benchmarks
+//!
+//! Harness functions that measure the throughput of the code generation on
+//! the machine they run on, e.g. to size the hardware of an ingestion. The
+//! inputs are generated from a fixed seed with [`gen_bytes`] and [`gen_text`],
+//! so every run and every machine processes the same data, and the codes are
+//! generated by the public functions [`data_id_with_options`] and
+//! [`content_id_text`]. The criterion benchmarks in `benches/throughput.rs`
+//! run the same inputs and functions.
+//!
+//! Only available with the `bench` feature.
+use std::time::{Duration, Instant};
+
+use crate::cid_text::content_id_text;
+use crate::did::data_id_with_options;
+use crate::options::IsccOptions;
+use crate::testing::{gen_bytes, gen_text};
+
+/// The seed of the generated inputs.
+const SEED: u64 = 0x6973_6363;
+
+/// The result of a benchmark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Throughput {
+    /// The number of bytes processed in all iterations.
+    pub bytes: u64,
+    /// The number of characters processed in all iterations, `0` for binary
+    /// inputs.
+    pub chars: u64,
+    /// The number of iterations.
+    pub iterations: usize,
+    /// The wall-clock duration of all iterations.
+    pub elapsed: Duration,
+    /// The code generated from the input.
+    pub code: String,
+}
+
+impl Throughput {
+    /// Bytes per second.
+    pub fn bytes_per_sec(&self) -> f64 {
+        self.bytes as f64 / self.seconds()
+    }
+
+    /// Megabytes (10^6 bytes) per second.
+    pub fn mb_per_sec(&self) -> f64 {
+        self.bytes_per_sec() / 1e6
+    }
+
+    /// Characters per second, `0` for binary inputs.
+    pub fn chars_per_sec(&self) -> f64 {
+        self.chars as f64 / self.seconds()
+    }
+
+    fn seconds(&self) -> f64 {
+        // Guards against a zero duration on coarse clocks.
+        self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+/// The data of `size` bytes that [`bench_data_code`] processes.
+pub fn data_input(size: usize) -> Vec<u8> {
+    gen_bytes(SEED, size)
+}
+
+/// The text of `chars` characters that [`bench_text_code`] processes.
+pub fn text_input(chars: usize) -> String {
+    gen_text(SEED, chars)
+}
+
+/// Generates the Data-ID of `data` like a single benchmark iteration.
+pub fn data_code(data: &[u8]) -> String {
+    data_id_with_options(data, &IsccOptions::default())
+        .expect("the data is not empty")
+        .code
+}
+
+/// Generates the Content-ID-Text of `text` like a single benchmark iteration.
+pub fn text_code(text: &str) -> String {
+    content_id_text(text, false)
+}
+
+/// Measures the Data-ID of `size` bytes of generated data, `iterations`
+/// times but at least once.
+///
+/// # Panics
+///
+/// Panics if `size` is `0`, empty data has no Data-ID.
+pub fn bench_data_code(size: usize, iterations: usize) -> Throughput {
+    assert!(size > 0, "empty data has no Data-ID");
+    let data = data_input(size);
+    let (code, iterations, elapsed) = measure(iterations, || data_code(&data));
+    Throughput {
+        bytes: size as u64 * iterations as u64,
+        chars: 0,
+        iterations,
+        elapsed,
+        code,
+    }
+}
+
+/// Measures the Content-ID-Text of a generated text of `chars` characters,
+/// `iterations` times but at least once.
+pub fn bench_text_code(chars: usize, iterations: usize) -> Throughput {
+    let text = text_input(chars);
+    let (code, iterations, elapsed) = measure(iterations, || text_code(&text));
+    Throughput {
+        bytes: text.len() as u64 * iterations as u64,
+        chars: chars as u64 * iterations as u64,
+        iterations,
+        elapsed,
+        code,
+    }
+}
+
+/// Runs `f` `iterations` times but at least once and returns the output of
+/// the last run, the number of runs and their duration.
+fn measure(iterations: usize, mut f: impl FnMut() -> String) -> (String, usize, Duration) {
+    let iterations = iterations.max(1);
+    let start = Instant::now();
+    let mut code = String::new();
+    for _ in 0..iterations {
+        code = f();
+    }
+    (code, iterations, start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::did::data_id;
+
+    #[test]
+    fn test_bench_data_code() {
+        let result = bench_data_code(100_000, 3);
+        assert_eq!(result.bytes, 300_000);
+        assert_eq!(result.chars, 0);
+        assert_eq!(result.iterations, 3);
+        assert!(result.mb_per_sec() > 0.0);
+
+        let path = std::env::temp_dir().join(format!("iscc-bench-{}", std::process::id()));
+        fs::write(&path, data_input(100_000)).unwrap();
+        let expected = data_id(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(result.code, expected);
+    }
+
+    #[test]
+    fn test_bench_text_code() {
+        let result = bench_text_code(5000, 0);
+        assert_eq!(result.iterations, 1);
+        assert_eq!(result.chars, 5000);
+        assert!(result.bytes >= 5000);
+        assert!(result.chars_per_sec() > 0.0);
+        assert_eq!(result.code, content_id_text(&text_input(5000), false));
+    }
+
+    #[test]
+    fn test_inputs_are_deterministic() {
+        assert_eq!(data_input(1000), data_input(1000));
+        assert_eq!(data_input(1000).len(), 1000);
+        assert_eq!(text_input(1000).chars().count(), 1000);
+    }
+}
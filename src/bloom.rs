@@ -0,0 +1,328 @@
+//! Feature Bloom Filters
+//!
+//! Bloom filters of the `xxhash32` window features of a text, to estimate the
+//! overlap of two documents without exchanging their text or their feature
+//! hashes. A filter does not store the features themselves, but it is not a
+//! cryptographic commitment either: whoever holds a filter can test whether it
+//! probably contains the features of a text they already know.
+use crate::error::IsccError;
+use crate::hashes::{text_features, xxhash64_with_seed};
+
+const FILTER_VERSION: u8 = 1;
+
+/// A Bloom filter of `u32` features with `bits` bits and `k` bit positions per
+/// feature. The positions are derived by double hashing of two seeded
+/// `xxhash64` of the little endian feature bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    bits: usize,
+    k: u32,
+    words: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// Creates an empty filter. Panics if `bits` or `k` is `0`, if `bits`
+    /// does not fit into 32 bits or if `k` is greater than 255, such that
+    /// every filter can be serialized.
+    pub fn new(bits: usize, k: u32) -> BloomFilter {
+        assert!(bits > 0, "A Bloom filter needs at least one bit.");
+        assert!(
+            bits <= u32::MAX as usize,
+            "A Bloom filter has at most 2^32 - 1 bits."
+        );
+        assert!(k > 0, "A Bloom filter needs at least one hash function.");
+        assert!(k <= 255, "A Bloom filter has at most 255 hash functions.");
+        BloomFilter {
+            bits,
+            k,
+            words: vec![0; bits.div_ceil(64)],
+        }
+    }
+
+    /// The number of bits of the filter.
+    pub fn bits(&self) -> usize {
+        self.bits
+    }
+
+    /// The number of bit positions per feature.
+    pub fn k(&self) -> u32 {
+        self.k
+    }
+
+    /// Adds a feature to the filter.
+    pub fn insert(&mut self, feature: u32) {
+        for bit in self.positions(feature) {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns `true` if the feature was probably inserted, `false` if it was
+    /// certainly not.
+    pub fn contains(&self, feature: u32) -> bool {
+        self.positions(feature)
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// The number of set bits.
+    pub fn count_ones(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Estimates the number of distinct inserted features from the number of
+    /// set bits `x` as `-(bits / k) * ln(1 - x / bits)`. A full filter
+    /// estimates infinity.
+    pub fn estimate_count(&self) -> f64 {
+        estimate_count(self.count_ones(), self.bits, self.k)
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the feature sets
+    /// of two filters. The union is estimated from the bitwise or of the
+    /// filters and the intersection as `|A| + |B| - |A ∪ B|`. Returns
+    /// [`IsccError::FilterMismatch`] if the filters differ in `bits` or `k`
+    /// and [`IsccError::InvalidArgument`] if every bit of their union is set.
+    pub fn estimate_overlap(&self, other: &BloomFilter) -> Result<f64, IsccError> {
+        if self.bits != other.bits || self.k != other.k {
+            return Err(IsccError::FilterMismatch(format!(
+                "{} bits with k = {} vs. {} bits with k = {}",
+                self.bits, self.k, other.bits, other.k
+            )));
+        }
+        let union_ones = self
+            .words
+            .iter()
+            .zip(other.words.iter())
+            .map(|(a, b)| (a | b).count_ones() as usize)
+            .sum();
+        let union = estimate_count(union_ones, self.bits, self.k);
+        if union == 0.0 {
+            return Ok(0.0);
+        }
+        if union.is_infinite() {
+            return Err(IsccError::InvalidArgument(
+                "the filters are saturated".to_string(),
+            ));
+        }
+        let intersection = self.estimate_count() + other.estimate_count() - union;
+        Ok((intersection / union).clamp(0.0, 1.0))
+    }
+
+    /// Serializes the filter to
+    ///
+    /// * the format version `1` (1 byte),
+    /// * `k` (1 byte),
+    /// * `bits` (`u32`, little endian),
+    /// * the bits as `u64` words (little endian), bit `i` is bit `i % 64` of
+    ///   word `i / 64`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![FILTER_VERSION, self.k as u8];
+        bytes.extend(&(self.bits as u32).to_le_bytes());
+        for word in &self.words {
+            bytes.extend(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Reads a filter written with [`BloomFilter::to_bytes`]. Returns
+    /// [`IsccError::InvalidFilter`] if the data is truncated, followed by
+    /// trailing bytes, of an unknown version or has invalid parameters.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BloomFilter, IsccError> {
+        let invalid = |reason: &str| IsccError::InvalidFilter(reason.to_string());
+        if bytes.len() < 6 {
+            return Err(invalid("truncated"));
+        }
+        if bytes[0] != FILTER_VERSION {
+            return Err(invalid(&format!("unsupported version {}", bytes[0])));
+        }
+        let k = u32::from(bytes[1]);
+        let bits = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]) as usize;
+        if k == 0 || bits == 0 {
+            return Err(invalid("bits and k must not be 0"));
+        }
+        let data = &bytes[6..];
+        let expected = bits.div_ceil(64) * 8;
+        if data.len() < expected {
+            return Err(invalid("truncated"));
+        }
+        if data.len() > expected {
+            return Err(invalid("trailing bytes"));
+        }
+        let words: Vec<u64> = data
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes([w[0], w[1], w[2], w[3], w[4], w[5], w[6], w[7]]))
+            .collect();
+        let unused = words.len() * 64 - bits;
+        if unused > 0 && words[words.len() - 1] >> (64 - unused) != 0 {
+            return Err(invalid("bits set beyond the filter size"));
+        }
+        Ok(BloomFilter { bits, k, words })
+    }
+
+    fn positions(&self, feature: u32) -> impl Iterator<Item = usize> {
+        let bytes = feature.to_le_bytes();
+        let h1 = xxhash64_with_seed(&bytes, 1);
+        let h2 = xxhash64_with_seed(&bytes, 2) | 1;
+        let bits = self.bits as u64;
+        (0..u64::from(self.k)).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bits) as usize)
+    }
+}
+
+fn estimate_count(ones: usize, bits: usize, k: u32) -> f64 {
+    let (x, m) = (ones as f64, bits as f64);
+    -(m / f64::from(k)) * (1.0 - x / m).ln()
+}
+
+/// Inserts the [`text_features`] of every window of `text` into a
+/// [`BloomFilter`] with `bits` bits and `k` bit positions per feature. Both
+/// sides of a comparison must use the same `width`, `bits` and `k`, and
+/// normalize the text the same way, e.g. with
+/// [`text_normalize`](crate::normalization::text_normalize).
+///
+/// The false positive rate for `n` distinct features is about
+/// `(1 - e^(-k * n / bits))^k`. Panics if `width` is smaller than 2, `bits`
+/// or `k` is `0` or `k` is greater than 255.
+pub fn text_feature_filter(text: &str, width: usize, bits: usize, k: u32) -> BloomFilter {
+    let mut filter = BloomFilter::new(bits, k);
+    for feature in text_features(text, width) {
+        filter.insert(feature);
+    }
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::hashes::xxhash32;
+
+    fn features(seed: u32, n: u32) -> Vec<u32> {
+        (0..n)
+            .map(|i| xxhash32(&[seed.to_le_bytes(), i.to_le_bytes()].concat()))
+            .collect()
+    }
+
+    /// A text of `n` distinct words starting at word `start`.
+    fn document(start: usize, n: usize) -> String {
+        (start..start + n)
+            .map(|i| format!("word{}", i))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn jaccard(a: &str, b: &str) -> f64 {
+        let a: HashSet<u32> = text_features(a, 13).collect();
+        let b: HashSet<u32> = text_features(b, 13).collect();
+        a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        for &(bits, k) in [(8192, 4), (16384, 7)].iter() {
+            let n = 1000;
+            let mut filter = BloomFilter::new(bits, k);
+            let inserted = features(1, n);
+            for &feature in &inserted {
+                filter.insert(feature);
+            }
+            assert!(inserted.iter().all(|&f| filter.contains(f)));
+
+            let probes = features(2, 20000);
+            let positives = probes.iter().filter(|&&f| filter.contains(f)).count();
+            let rate = positives as f64 / probes.len() as f64;
+            let expected =
+                (1.0 - (-f64::from(k) * f64::from(n) / bits as f64).exp()).powi(k as i32);
+            assert!(
+                (rate - expected).abs() < expected * 0.3 + 0.001,
+                "bits {} k {}: rate {} expected {}",
+                bits,
+                k,
+                rate,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimate_count() {
+        let mut filter = BloomFilter::new(16384, 5);
+        for feature in features(3, 1500) {
+            filter.insert(feature);
+        }
+        assert!((filter.estimate_count() - 1500.0).abs() < 75.0);
+        assert_eq!(BloomFilter::new(64, 3).estimate_count(), 0.0);
+    }
+
+    #[test]
+    fn test_estimate_overlap_tracks_jaccard() {
+        let a = document(0, 400);
+        for &shift in [0, 50, 150, 300, 400].iter() {
+            let b = document(shift, 400);
+            let fa = text_feature_filter(&a, 13, 1 << 16, 4);
+            let fb = text_feature_filter(&b, 13, 1 << 16, 4);
+            let estimate = fa.estimate_overlap(&fb).unwrap();
+            let exact = jaccard(&a, &b);
+            assert!(
+                (estimate - exact).abs() < 0.05,
+                "shift {}: estimate {} exact {}",
+                shift,
+                estimate,
+                exact
+            );
+        }
+    }
+
+    #[test]
+    fn test_estimate_overlap_mismatch() {
+        let a = text_feature_filter("some text", 4, 1024, 3);
+        let b = text_feature_filter("some text", 4, 2048, 3);
+        let c = text_feature_filter("some text", 4, 1024, 4);
+        assert!(matches!(
+            a.estimate_overlap(&b),
+            Err(IsccError::FilterMismatch(_))
+        ));
+        assert!(matches!(
+            a.estimate_overlap(&c),
+            Err(IsccError::FilterMismatch(_))
+        ));
+        assert_eq!(a.estimate_overlap(&a).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_filter_bytes_roundtrip() {
+        let filter = text_feature_filter(&document(0, 50), 13, 1000, 3);
+        let bytes = filter.to_bytes();
+        assert_eq!(&bytes[..6], &[1, 3, 0xe8, 0x03, 0, 0]);
+        assert_eq!(bytes.len(), 6 + 16 * 8);
+        assert_eq!(BloomFilter::from_bytes(&bytes).unwrap(), filter);
+
+        let mut version = bytes.clone();
+        version[0] = 2;
+        let mut stray = bytes.clone();
+        *stray.last_mut().unwrap() |= 0x80;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        for invalid in [
+            &bytes[..bytes.len() - 1],
+            &bytes[..4],
+            &version[..],
+            &stray[..],
+            &trailing[..],
+        ]
+        .iter()
+        {
+            assert!(matches!(
+                BloomFilter::from_bytes(invalid),
+                Err(IsccError::InvalidFilter(_))
+            ));
+        }
+
+        let widest = BloomFilter::new(64, 255);
+        assert_eq!(BloomFilter::from_bytes(&widest.to_bytes()).unwrap(), widest);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_filter_k_too_large() {
+        BloomFilter::new(64, 256);
+    }
+}
@@ -0,0 +1,295 @@
+//! Result Caching
+//!
+//! The Data-ID and Content-ID of a file are pure functions of its bytes and
+//! of its format, so they can be cached by the tophash of its Instance-ID and
+//! the format selected by its extension. [`iscc_from_file_cached`] computes
+//! the Instance-ID first, which is a single cheap pass over the file, and only
+//! generates the other codes if the cache holds no result for the key.
+//!
+//! [`IsccCache`] is object safe, so caches can be backed by any store, e.g. a
+//! database. [`MemoryCache`] is a bounded in-memory LRU cache.
+//!
+//! Only available with the `cache` feature.
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+use crate::did::data_id;
+use crate::error::IsccError;
+use crate::file::{content_code, ContentFormat};
+use crate::iid::instance_id;
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+/// The codes of a file, see [`iscc_from_file_cached`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileIscc {
+    /// The Content-ID, `None` if the format of the file is not supported.
+    pub content_code: Option<String>,
+    /// The Data-ID, `None` if the file is empty.
+    pub data_code: Option<String>,
+    pub instance_code: String,
+    /// The hex encoded tophash of the Instance-ID.
+    pub tophash: String,
+    /// The version of the algorithm that generated the codes.
+    pub version: AlgorithmVersion,
+}
+
+/// A cache of [`FileIscc`] results. The keys consist of the hex encoded
+/// tophash of the Instance-ID of the file and the name of its format, e.g.
+/// `text`, because the same bytes have a Content-ID as `.txt` file but none
+/// as `.bin` file.
+///
+/// The trait has no error type: a cache that fails to read should return
+/// `None` and a cache that fails to write should drop the result, the codes
+/// are then generated again.
+pub trait IsccCache {
+    /// The cached result for `key`.
+    fn get(&mut self, key: &str) -> Option<FileIscc>;
+
+    /// Stores the result of a file with the given `key`.
+    fn put(&mut self, key: &str, iscc: FileIscc);
+}
+
+/// An in-memory cache that holds up to `capacity` results and evicts the
+/// least recently used one when it is full.
+#[derive(Debug, Clone)]
+pub struct MemoryCache {
+    capacity: usize,
+    /// The results and the tick of their last use.
+    entries: HashMap<String, (u64, FileIscc)>,
+    /// The tophashes by the tick of their last use.
+    recency: BTreeMap<u64, String>,
+    tick: u64,
+}
+
+impl MemoryCache {
+    /// A cache of up to `capacity` results, at least one.
+    pub fn new(capacity: usize) -> MemoryCache {
+        MemoryCache {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    /// The number of cached results.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Marks `tophash` as used now.
+    fn touch(&mut self, tophash: &str) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(tophash) {
+            self.recency.remove(&entry.0);
+            entry.0 = self.tick;
+            self.recency.insert(self.tick, tophash.to_string());
+        }
+    }
+}
+
+impl IsccCache for MemoryCache {
+    fn get(&mut self, tophash: &str) -> Option<FileIscc> {
+        self.touch(tophash);
+        self.entries.get(tophash).map(|(_, iscc)| iscc.clone())
+    }
+
+    fn put(&mut self, tophash: &str, iscc: FileIscc) {
+        if let Some(entry) = self.entries.get_mut(tophash) {
+            entry.1 = iscc;
+            self.touch(tophash);
+            return;
+        }
+        if self.entries.len() >= self.capacity {
+            let oldest = *self.recency.keys().next().expect("the cache is not empty");
+            let evicted = self.recency.remove(&oldest).unwrap();
+            self.entries.remove(&evicted);
+        }
+        self.tick += 1;
+        self.entries.insert(tophash.to_string(), (self.tick, iscc));
+        self.recency.insert(self.tick, tophash.to_string());
+    }
+}
+
+/// Generates the codes of the file at `path` and caches them in `cache`.
+///
+/// The Instance-ID is always computed. If `cache` holds a result of the
+/// current [`ALGORITHM_VERSION`] for its tophash and format, that result is
+/// returned,
+/// otherwise the Data-ID and the Content-ID are generated and the result is
+/// stored. Results of other versions are replaced.
+///
+/// The Content-ID is selected by the file extension like in
+/// [`iscc_best_effort`](crate::file::iscc_best_effort).
+pub fn iscc_from_file_cached<C: IsccCache + ?Sized>(
+    path: &Path,
+    cache: &mut C,
+) -> Result<FileIscc, IsccError> {
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| IsccError::InvalidArgument(format!("{:?} is not valid UTF-8", path)))?;
+    let (instance_code, tophash) = instance_id(path_str)?;
+    let key = cache_key(&tophash, path);
+    if let Some(iscc) = cache.get(&key) {
+        if iscc.version == ALGORITHM_VERSION && iscc.instance_code == instance_code {
+            return Ok(iscc);
+        }
+    }
+
+    let data_code = if fs::metadata(path)?.len() == 0 {
+        None
+    } else {
+        Some(data_id(path_str)?)
+    };
+    let iscc = FileIscc {
+        content_code: content_code(path)?,
+        data_code,
+        instance_code,
+        tophash,
+        version: ALGORITHM_VERSION,
+    };
+    cache.put(&key, iscc.clone());
+    Ok(iscc)
+}
+
+/// The cache key of a file, see [`IsccCache`].
+fn cache_key(tophash: &str, path: &Path) -> String {
+    format!("{}:{}", tophash, ContentFormat::of(path).name())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::cid_text::content_id_text;
+
+    /// Counts the calls and never returns a result.
+    #[derive(Default)]
+    struct NullCache {
+        gets: usize,
+        puts: usize,
+    }
+
+    impl IsccCache for NullCache {
+        fn get(&mut self, _key: &str) -> Option<FileIscc> {
+            self.gets += 1;
+            None
+        }
+
+        fn put(&mut self, _key: &str, _iscc: FileIscc) {
+            self.puts += 1;
+        }
+    }
+
+    fn path(filename: &str) -> PathBuf {
+        Path::new("tests/test_data").join(filename)
+    }
+
+    #[test]
+    fn test_hit_equals_miss() {
+        let mut cache = MemoryCache::new(10);
+        let mut files = vec!["invoice.txt", "mediafile.html"];
+        if cfg!(feature = "image") {
+            files.push("lenna.jpg");
+        }
+        if cfg!(feature = "extract") {
+            files.push("sample.epub");
+        }
+        for file in files {
+            let miss = iscc_from_file_cached(&path(file), &mut cache).unwrap();
+            let hit = iscc_from_file_cached(&path(file), &mut cache).unwrap();
+            let recomputed = iscc_from_file_cached(&path(file), &mut NullCache::default()).unwrap();
+            assert_eq!(hit, miss);
+            assert_eq!(hit, recomputed);
+            assert_eq!(
+                hit.data_code.unwrap(),
+                data_id(path(file).to_str().unwrap()).unwrap()
+            );
+        }
+        let invoice = iscc_from_file_cached(&path("invoice.txt"), &mut cache).unwrap();
+        let text = fs::read_to_string(path("invoice.txt")).unwrap();
+        assert_eq!(invoice.content_code, Some(content_id_text(&text, false)));
+        let html = iscc_from_file_cached(&path("mediafile.html"), &mut cache).unwrap();
+        assert_eq!(html.content_code, None);
+    }
+
+    #[test]
+    fn test_hit_skips_generation() {
+        // A cached result is returned as is, so a planted one proves the hit.
+        let (_, tophash) = instance_id("tests/test_data/invoice.txt").unwrap();
+        let key = cache_key(&tophash, &path("invoice.txt"));
+        let mut planted =
+            iscc_from_file_cached(&path("invoice.txt"), &mut NullCache::default()).unwrap();
+        planted.data_code = Some("planted".to_string());
+        let mut cache = MemoryCache::new(1);
+        cache.put(&key, planted.clone());
+        let cache: &mut dyn IsccCache = &mut cache;
+        assert_eq!(
+            iscc_from_file_cached(&path("invoice.txt"), cache).unwrap(),
+            planted
+        );
+
+        // Results of other versions are regenerated.
+        planted.version = AlgorithmVersion(0);
+        cache.put(&key, planted);
+        let iscc = iscc_from_file_cached(&path("invoice.txt"), cache).unwrap();
+        assert_eq!(iscc.version, ALGORITHM_VERSION);
+        assert_ne!(iscc.data_code.as_ref().unwrap(), "planted");
+    }
+
+    #[test]
+    fn test_same_bytes_other_format() {
+        let dir = std::env::temp_dir().join(format!("iscc-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (bin, txt) = (dir.join("a.bin"), dir.join("a.txt"));
+        fs::write(&bin, "The same bytes in two files").unwrap();
+        fs::write(&txt, "The same bytes in two files").unwrap();
+
+        let mut cache = MemoryCache::new(10);
+        let first = iscc_from_file_cached(&bin, &mut cache).unwrap();
+        let second = iscc_from_file_cached(&txt, &mut cache).unwrap();
+        assert_eq!(first.content_code, None);
+        assert_eq!(
+            second.content_code,
+            Some(content_id_text("The same bytes in two files", false))
+        );
+        assert_eq!(first.tophash, second.tophash);
+        assert_eq!(iscc_from_file_cached(&bin, &mut cache).unwrap(), first);
+        assert_eq!(cache.len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_miss_stores_once() {
+        let mut cache = NullCache::default();
+        iscc_from_file_cached(&path("invoice.txt"), &mut cache).unwrap();
+        assert_eq!((cache.gets, cache.puts), (1, 1));
+    }
+
+    #[test]
+    fn test_memory_cache_evicts_least_recently_used() {
+        let iscc = iscc_from_file_cached(&path("invoice.txt"), &mut NullCache::default()).unwrap();
+        let mut cache = MemoryCache::new(2);
+        cache.put("a", iscc.clone());
+        cache.put("b", iscc.clone());
+        assert!(cache.get("a").is_some());
+        cache.put("c", iscc.clone());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+
+        // Updating an entry does not evict another one.
+        cache.put("c", iscc);
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get("a").is_some());
+        assert!(!cache.is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+//! Checkpoint Encoding
+//!
+//! Shared framing of the checkpoints of the incremental hashers. Every
+//! checkpoint starts with a 4-byte magic and a 1-byte format version followed
+//! by the hasher specific fields in little endian byte order and ends with the
+//! `xxhash64` of all preceding bytes (8 bytes, little endian).
+use std::convert::TryInto;
+
+use crate::error::IsccError;
+use crate::hashes::xxhash64;
+
+pub(crate) const VERSION: u8 = 1;
+
+/// Builds a checkpoint.
+pub(crate) struct Writer {
+    buffer: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new(magic: &[u8; 4]) -> Writer {
+        let mut buffer = magic.to_vec();
+        buffer.push(VERSION);
+        Writer { buffer }
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.buffer.extend(&value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.buffer.extend(&value.to_le_bytes());
+    }
+
+    /// Writes the length of `data` as `u32` followed by `data`.
+    pub(crate) fn bytes(&mut self, data: &[u8]) {
+        self.u32(data.len() as u32);
+        self.buffer.extend(data);
+    }
+
+    pub(crate) fn raw(&mut self, data: &[u8]) {
+        self.buffer.extend(data);
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        let checksum = xxhash64(&self.buffer);
+        self.u64(checksum);
+        self.buffer
+    }
+}
+
+/// Reads a checkpoint after verifying its checksum, magic and version.
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(checkpoint: &'a [u8], magic: &[u8; 4]) -> Result<Reader<'a>, IsccError> {
+        if checkpoint.len() < 4 + 1 + 8 {
+            return Err(invalid("truncated"));
+        }
+        let (data, checksum) = checkpoint.split_at(checkpoint.len() - 8);
+        if xxhash64(data).to_le_bytes() != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+        if &data[..4] != magic {
+            return Err(invalid("wrong hasher"));
+        }
+        if data[4] != VERSION {
+            return Err(invalid(&format!("unsupported version {}", data[4])));
+        }
+        Ok(Reader { data: &data[5..] })
+    }
+
+    pub(crate) fn raw(&mut self, n: usize) -> Result<&'a [u8], IsccError> {
+        if self.data.len() < n {
+            return Err(invalid("truncated"));
+        }
+        let (value, rest) = self.data.split_at(n);
+        self.data = rest;
+        Ok(value)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, IsccError> {
+        Ok(self.raw(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, IsccError> {
+        Ok(u32::from_le_bytes(self.raw(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, IsccError> {
+        Ok(u64::from_le_bytes(self.raw(8)?.try_into().unwrap()))
+    }
+
+    /// Reads data written with [`Writer::bytes`].
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], IsccError> {
+        let n = self.u32()? as usize;
+        self.raw(n)
+    }
+
+    /// Fails if there are unread bytes left.
+    pub(crate) fn finish(self) -> Result<(), IsccError> {
+        if !self.data.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn invalid(reason: &str) -> IsccError {
+    IsccError::InvalidCheckpoint(reason.to_string())
+}
@@ -1,60 +1,286 @@
 //! Content-ID Image
-use bit_vec::BitVec;
-use image::{FilterType, ImageResult};
+use std::fs;
+use std::io::Cursor;
 
-use crate::base58::encode;
+use image::{gif, AnimationDecoder, ColorType, DynamicImage, FilterType, ImageFormat, ImageResult};
+
+use crate::code::{CodeKind, FullDigest};
+use crate::error::IsccError;
+use crate::hashes::{pack_bits, similarity_hash};
+use crate::metrics::{Metrics, Stage};
+use crate::result::CodeResult;
 
 const HEAD_CID_I: u8 = 0x12;
 const HEAD_CID_I_PCF: u8 = 0x13;
 
+/// The perceptual hash used for the Content-ID-Image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ImageAlgo {
+    /// The hash defined by the ISCC specification, see [`image_hash`].
+    #[default]
+    Default,
+    /// A pHash that leaves out the DC coefficient, see [`image_hash_dct`].
+    /// **This is not part of the ISCC specification.** The header does not
+    /// distinguish these codes from standard Content-ID-Images, they must not
+    /// be compared with codes of another algorithm.
+    Dct,
+    /// A wavelet hash (wHash), see [`image_hash_wavelet`]. Like [`ImageAlgo::Dct`]
+    /// **this is not part of the ISCC specification.**
+    Wavelet,
+}
+
 /// Generates the id from normalized grayscale pixel data
 ///
 /// * `partial` - The last bit of the header byte of the Content-ID is the
 ///   "Partial Content Flag". It designates if the Content-ID applies to the
 ///   full content or just some part of it.
 pub fn content_id_image(img_path: &str, partial: bool) -> ImageResult<String> {
+    content_id_image_with_algo(img_path, partial, ImageAlgo::Default)
+}
+
+/// Generates the id like [`content_id_image`] and returns it together with
+/// the raw digest.
+pub fn content_id_image_result(img_path: &str, partial: bool) -> ImageResult<CodeResult> {
     let pixels = image_normalize(img_path)?;
-    let hash_digest = image_hash(&pixels);
+    Ok(image_code(&pixels, partial, ImageAlgo::Default))
+}
 
-    let mut content_id_digest = if partial {
-        vec![HEAD_CID_I_PCF]
-    } else {
-        vec![HEAD_CID_I]
+/// Generates the id like [`content_id_image_result`] and measures the
+/// generation, see [`Metrics`]. The image is read into memory and its format
+/// is guessed from its content.
+pub fn content_id_image_with_metrics(
+    img_path: &str,
+    partial: bool,
+) -> Result<(CodeResult, Metrics), IsccError> {
+    let mut metrics = Metrics::default();
+    let bytes = metrics.time(Stage::Reading, || fs::read(img_path))?;
+    metrics.input_bytes = bytes.len() as u64;
+    let img = metrics.time(Stage::Decoding, || image::load_from_memory(&bytes))?;
+    let pixels: Vec<Vec<u8>> = metrics.time(Stage::Normalization, || {
+        normalize_pixels(&img)
+            .chunks(32)
+            .map(|row| row.to_vec())
+            .collect()
+    });
+    let result = metrics.time(Stage::Dct, || {
+        image_code(&pixels, partial, ImageAlgo::Default)
+    });
+    Ok((result, metrics))
+}
+
+/// Generates the 256-bit digest of the image id, see [`FullDigest`] and
+/// [`image_hash_full`].
+pub fn content_id_image_full(img_path: &str, partial: bool) -> ImageResult<FullDigest> {
+    let pixels = image_normalize(img_path)?;
+    Ok(FullDigest {
+        kind: CodeKind::ContentImage { partial },
+        digest: image_hash_full(&pixels),
+    })
+}
+
+/// Generates the id like [`content_id_image`] with the given hash algorithm.
+pub fn content_id_image_with_algo(
+    img_path: &str,
+    partial: bool,
+    algo: ImageAlgo,
+) -> ImageResult<String> {
+    let pixels = image_normalize(img_path)?;
+    Ok(image_code(&pixels, partial, algo).code)
+}
+
+/// Bounds the frames of an animation that are hashed by
+/// [`content_id_image_animated`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameSampling {
+    /// Hash every `step`-th frame, starting with the first one.
+    pub step: usize,
+    /// The maximum number of frames to hash.
+    pub max_frames: usize,
+}
+
+impl Default for FrameSampling {
+    fn default() -> FrameSampling {
+        FrameSampling {
+            step: 1,
+            max_frames: 256,
+        }
+    }
+}
+
+/// Generates the id of a possibly animated image. The frames of an animated
+/// GIF are sampled according to `sampling`, each sampled frame is hashed with
+/// [`image_hash`] and the `similarity_hash` of the frame hashes is the body of
+/// the code. **Codes of animations are not part of the ISCC specification.**
+///
+/// Still images and GIFs with a single frame get the same code as with
+/// [`content_id_image`]. The frames of animated WebP and APNG images can not
+/// be decoded, they return [`IsccError::UnsupportedAnimation`] instead of the
+/// code of their first frame.
+pub fn content_id_image_animated(
+    img_path: &str,
+    partial: bool,
+    sampling: FrameSampling,
+) -> Result<String, IsccError> {
+    if sampling.step == 0 || sampling.max_frames == 0 {
+        return Err(IsccError::InvalidArgument(
+            "step and max_frames must be at least 1".to_string(),
+        ));
+    }
+    let bytes = fs::read(img_path)?;
+    if let Some(format) = unsupported_animation(&bytes) {
+        return Err(IsccError::UnsupportedAnimation(format.to_string()));
+    }
+    if image::guess_format(&bytes).ok() == Some(ImageFormat::GIF) {
+        let frames = gif::Decoder::new(Cursor::new(&bytes))?.into_frames();
+        let mut frame_hashes: Vec<u64> = Vec::new();
+        let mut n_frames = 0;
+        for (i, frame) in frames.enumerate() {
+            let frame = frame?;
+            n_frames = i + 1;
+            if frame_hashes.len() == sampling.max_frames {
+                break;
+            }
+            if i % sampling.step == 0 {
+                let img = DynamicImage::ImageRgba8(frame.into_buffer());
+                let pixels: Vec<Vec<u8>> = normalize_pixels(&img)
+                    .chunks(32)
+                    .map(|row| row.to_vec())
+                    .collect();
+                let mut array = [0; 8];
+                array.copy_from_slice(&image_hash(&pixels));
+                frame_hashes.push(u64::from_be_bytes(array));
+            }
+        }
+        if n_frames > 1 {
+            let header = if partial { HEAD_CID_I_PCF } else { HEAD_CID_I };
+            return Ok(CodeResult::new(header, similarity_hash(frame_hashes)).code);
+        }
+    }
+    Ok(content_id_image(img_path, partial)?)
+}
+
+/// Returns the format name of an animated WebP or APNG image, whose frames
+/// can not be decoded.
+fn unsupported_animation(bytes: &[u8]) -> Option<&'static str> {
+    // The VP8X chunk of an extended WebP flags animations.
+    if bytes.len() > 20 && &bytes[..4] == b"RIFF" && &bytes[8..16] == b"WEBPVP8X" {
+        return if bytes[20] & 0x02 != 0 {
+            Some("animated WebP")
+        } else {
+            None
+        };
+    }
+    // An APNG has an animation control chunk before its image data.
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut pos = 8;
+        while pos + 8 <= bytes.len() {
+            match &bytes[pos + 4..pos + 8] {
+                b"acTL" => return Some("APNG"),
+                b"IDAT" => return None,
+                _ => {}
+            }
+            let mut len = [0; 4];
+            len.copy_from_slice(&bytes[pos..pos + 4]);
+            pos = pos.saturating_add(12 + u32::from_be_bytes(len) as usize);
+        }
+    }
+    None
+}
+
+/// Generates the id from 32x32 normalized grayscale pixels in row-major order,
+/// e.g. the pixels of an [`ImagePreview`].
+pub fn content_id_image_pixels(pixels: &[u8], partial: bool) -> Result<String, IsccError> {
+    if pixels.len() != 32 * 32 {
+        return Err(IsccError::LengthMismatch {
+            expected: 32 * 32,
+            actual: pixels.len(),
+        });
+    }
+    let pixels: Vec<Vec<u8>> = pixels.chunks(32).map(|row| row.to_vec()).collect();
+    Ok(image_code(&pixels, partial, ImageAlgo::Default).code)
+}
+
+fn image_code(pixels: &[Vec<u8>], partial: bool, algo: ImageAlgo) -> CodeResult {
+    let hash_digest = match algo {
+        ImageAlgo::Default => image_hash(pixels),
+        ImageAlgo::Dct => image_hash_dct(pixels),
+        ImageAlgo::Wavelet => image_hash_wavelet(pixels),
     };
-    content_id_digest.extend(&hash_digest);
 
-    Ok(encode(&content_id_digest))
+    let header = if partial { HEAD_CID_I_PCF } else { HEAD_CID_I };
+    CodeResult::new(header, hash_digest)
 }
 
 pub fn image_normalize(img_path: &str) -> ImageResult<Vec<Vec<u8>>> {
     let img = image::open(img_path)?;
 
+    let two_dim_image = normalize_pixels(&img)
+        .chunks(32)
+        .map(|row| row.to_vec())
+        .collect();
+    Ok(two_dim_image)
+}
+
+/// Converts the image to 32x32 grayscale pixels in row-major order.
+fn normalize_pixels(img: &DynamicImage) -> Vec<u8> {
     let img = img.grayscale();
 
     // TODO: Not the same as in pillow, see https://stackoverflow.com/a/23209568
     let img = img.resize_exact(32, 32, FilterType::Triangle);
 
-    let two_dim_image = img
-        .raw_pixels()
-        .chunks(32)
-        .map(|row| row.to_vec())
-        .collect();
-    Ok(two_dim_image)
+    img.raw_pixels()
 }
 
-pub fn image_hash(pixels: &[Vec<u8>]) -> Vec<u8> {
-    let mut dct_row_lists: Vec<Vec<f64>> = Vec::new();
-    for row in pixels.iter() {
-        let mut row: Vec<f64> = row.iter().map(|&n| f64::from(n)).collect();
-        dct(&mut row);
-        dct_row_lists.push(row);
+/// The normalized 32x32 grayscale pixels the Content-ID-Image is computed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImagePreview {
+    /// 1024 grayscale pixels in row-major order
+    pub pixels: Vec<u8>,
+}
+
+/// Returns the normalized pixels of an image file as used for the
+/// Content-ID-Image.
+pub fn image_preview(img_path: &str) -> Result<ImagePreview, IsccError> {
+    let img = image::open(img_path)?;
+    Ok(ImagePreview {
+        pixels: normalize_pixels(&img),
+    })
+}
+
+/// Returns the normalized pixels of an encoded image as used for the
+/// Content-ID-Image.
+pub fn image_preview_from_bytes(bytes: &[u8]) -> Result<ImagePreview, IsccError> {
+    let img = image::load_from_memory(bytes)?;
+    Ok(ImagePreview {
+        pixels: normalize_pixels(&img),
+    })
+}
+
+impl ImagePreview {
+    /// Encodes the pixels as 32x32 grayscale PNG.
+    pub fn to_png_bytes(&self) -> Result<Vec<u8>, IsccError> {
+        let mut png = Vec::new();
+        image::png::PNGEncoder::new(&mut png).encode(&self.pixels, 32, 32, ColorType::Gray(8))?;
+        Ok(png)
     }
 
-    let mut dct_col_lists_t: Vec<Vec<f64>> = transpose(&dct_row_lists);
-    for mut col in dct_col_lists_t.iter_mut() {
-        dct(&mut col);
+    /// Renders the pixels as 32 lines of 32 characters, darker pixels are
+    /// drawn with denser characters.
+    pub fn to_ascii_art(&self) -> String {
+        const RAMP: &[u8] = b"@%#*+=-:. ";
+        let mut art = String::with_capacity(33 * 32);
+        for row in self.pixels.chunks(32) {
+            for &pixel in row {
+                art.push(RAMP[pixel as usize * (RAMP.len() - 1) / 255] as char);
+            }
+            art.push('\n');
+        }
+        art
     }
-    let dct_lists: Vec<Vec<f64>> = transpose(&dct_col_lists_t);
+}
+
+pub fn image_hash(pixels: &[Vec<u8>]) -> Vec<u8> {
+    let dct_lists = dct_2d(pixels);
 
     // Extract upper left 8x8 corner
     let flat_list: Vec<f64> = dct_lists
@@ -66,8 +292,104 @@ pub fn image_hash(pixels: &[Vec<u8>]) -> Vec<u8> {
 
     let med = median(&flat_list);
 
-    let bv: BitVec = flat_list.into_iter().map(|v| v > med).collect();
-    bv.to_bytes()
+    pack_bits(flat_list.into_iter().map(|v| v > med))
+}
+
+/// Extends [`image_hash`] to 256 bits. The upper left 16x16 DCT coefficients
+/// are split into four 8x8 quadrants in the order upper left, upper right,
+/// lower left, lower right. Each quadrant is hashed like `image_hash` against
+/// its own median, so the first 64 bits equal `image_hash`.
+pub fn image_hash_full(pixels: &[Vec<u8>]) -> [u8; 32] {
+    let dct_lists = dct_2d(pixels);
+
+    let mut digest = [0; 32];
+    for (quadrant, bytes) in digest.chunks_mut(8).enumerate() {
+        let (row, col) = (quadrant / 2 * 8, quadrant % 2 * 8);
+        let flat_list: Vec<f64> = dct_lists[row..row + 8]
+            .iter()
+            .flat_map(|l| l[col..col + 8].iter().copied())
+            .collect();
+
+        let med = median(&flat_list);
+
+        bytes.copy_from_slice(&pack_bits(flat_list.into_iter().map(|v| v > med)));
+    }
+    digest
+}
+
+/// A pHash variant that excludes the DC coefficient (the average brightness).
+/// Like [`image_hash`] the 64 bits are taken from the upper left 8x8 DCT
+/// coefficients, but the median is computed without the DC coefficient and
+/// its bit is always unset.
+pub fn image_hash_dct(pixels: &[Vec<u8>]) -> Vec<u8> {
+    let dct_lists = dct_2d(pixels);
+
+    // Extract upper left 8x8 corner
+    let flat_list: Vec<f64> = dct_lists
+        .into_iter()
+        .take(8)
+        .flat_map(|l| l.into_iter().take(8))
+        .collect();
+
+    let med = median(&flat_list[1..]);
+
+    pack_bits(
+        flat_list
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| i > 0 && v > med),
+    )
+}
+
+/// A wavelet hash over the normalized 32x32 pixels. The pixels are decomposed
+/// with the 2D Haar wavelet transform until the approximation (LL) band is
+/// 8x8, every bit is set if its LL coefficient is above the median of all LL
+/// coefficients.
+pub fn image_hash_wavelet(pixels: &[Vec<u8>]) -> Vec<u8> {
+    let mut ll: Vec<Vec<f64>> = pixels
+        .iter()
+        .map(|row| row.iter().map(|&n| f64::from(n) / 255.0).collect())
+        .collect();
+    while ll.len() > 8 {
+        ll = haar_ll(&ll);
+    }
+
+    let flat_list: Vec<f64> = ll.into_iter().flatten().collect();
+
+    let med = median(&flat_list);
+
+    pack_bits(flat_list.into_iter().map(|v| v > med))
+}
+
+/// One level of the orthonormal 2D Haar transform, keeping only the
+/// approximation band. The side length of `m` must be even.
+fn haar_ll(m: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    m.chunks(2)
+        .map(|rows| {
+            (0..rows[0].len() / 2)
+                .map(|j| {
+                    (rows[0][2 * j] + rows[0][2 * j + 1] + rows[1][2 * j] + rows[1][2 * j + 1])
+                        / 2.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Computes the 2D DCT of the pixels by applying [`dct`] to rows and columns.
+fn dct_2d(pixels: &[Vec<u8>]) -> Vec<Vec<f64>> {
+    let mut dct_row_lists: Vec<Vec<f64>> = Vec::new();
+    for row in pixels.iter() {
+        let mut row: Vec<f64> = row.iter().map(|&n| f64::from(n)).collect();
+        dct(&mut row);
+        dct_row_lists.push(row);
+    }
+
+    let mut dct_col_lists_t: Vec<Vec<f64>> = transpose(&dct_row_lists);
+    for col in dct_col_lists_t.iter_mut() {
+        dct(col);
+    }
+    transpose(&dct_col_lists_t)
 }
 
 fn transpose(m: &[Vec<f64>]) -> Vec<Vec<f64>> {
@@ -133,7 +455,53 @@ fn _transform_recursive(vector: &mut [f64], temp: &mut [f64]) {
 
 #[cfg(test)]
 mod tests {
-    use super::median;
+    use super::{haar_ll, image_hash_dct, median, unsupported_animation};
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_haar_ll() {
+        let m = vec![
+            vec![1.0, 3.0, 0.0, 0.0],
+            vec![5.0, 7.0, 0.0, 2.0],
+            vec![1.0, 1.0, 2.0, 2.0],
+            vec![1.0, 1.0, 2.0, 2.0],
+        ];
+        assert_eq!(haar_ll(&m), vec![vec![8.0, 1.0], vec![2.0, 4.0]]);
+    }
+
+    #[test]
+    fn test_unsupported_animation() {
+        let png = std::fs::read("tests/test_data/cat.png").unwrap();
+        assert_eq!(unsupported_animation(&png), None);
+        // An acTL chunk of 8 bytes after the IHDR chunk turns the PNG into an APNG.
+        let mut apng = png[..33].to_vec();
+        apng.extend(b"\x00\x00\x00\x08acTL\x00\x00\x00\x02\x00\x00\x00\x00");
+        apng.extend(&[0; 4]);
+        apng.extend(&png[33..]);
+        assert_eq!(unsupported_animation(&apng), Some("APNG"));
+        assert_eq!(unsupported_animation(&apng[..20]), None);
+
+        let mut webp = b"RIFF\x00\x00\x00\x00WEBPVP8X\x0a\x00\x00\x00".to_vec();
+        webp.extend(&[0; 10]);
+        assert_eq!(unsupported_animation(&webp), None);
+        webp[20] = 0x02;
+        assert_eq!(unsupported_animation(&webp), Some("animated WebP"));
+    }
+
+    #[test]
+    fn test_image_hash_dct_ignores_brightness() {
+        let pixels: Vec<Vec<u8>> = (0..32)
+            .map(|i| (0..32).map(|j| ((i * 7 + j * 3) % 200) as u8).collect())
+            .collect();
+        let brighter: Vec<Vec<u8>> = pixels
+            .iter()
+            .map(|row| row.iter().map(|&n| n + 50).collect())
+            .collect();
+        let hash = image_hash_dct(&pixels);
+        assert_eq!(hash.len(), 8);
+        assert_eq!(hash[0] & 0x80, 0);
+        assert_eq!(image_hash_dct(&brighter), hash);
+    }
 
     #[test]
     #[allow(clippy::float_cmp)]
@@ -1,7 +1,9 @@
 //! Content-ID-Mixed
 
 use crate::base58;
+use crate::code::{CodeKind, FullDigest};
 use crate::hashes::similarity_hash;
+use crate::result::CodeResult;
 
 const HEAD_CID_M: u8 = 0x18;
 const HEAD_CID_M_PCF: u8 = 0x19;
@@ -14,6 +16,12 @@ const HEAD_CID_M_PCF: u8 = 0x19;
 ///   "Partial Content Flag". It designates if the Content-ID applies to the
 ///   full content or just some part of it.
 pub fn content_id_mixed(cids: &[&str], partial: bool) -> String {
+    content_id_mixed_result(cids, partial).code
+}
+
+/// Generates the id like [`content_id_mixed`] and returns it together with the
+/// raw digest.
+pub fn content_id_mixed_result(cids: &[&str], partial: bool) -> CodeResult {
     let decoded: Vec<Vec<u8>> = cids.iter().map(|cid| base58::decode(cid)).collect();
 
     // Extract first 8-bytes
@@ -26,12 +34,41 @@ pub fn content_id_mixed(cids: &[&str], partial: bool) -> String {
 
     let simhash_digest = similarity_hash(truncated);
 
-    let mut content_id_mixed = if partial {
-        vec![HEAD_CID_M_PCF]
-    } else {
-        vec![HEAD_CID_M]
-    };
-    content_id_mixed.extend(&simhash_digest);
+    let header = if partial { HEAD_CID_M_PCF } else { HEAD_CID_M };
+    CodeResult::new(header, simhash_digest)
+}
+
+/// Generates the 256-bit digest of the Content-ID-Mixed from the full digests
+/// of the aggregated Content-IDs. Like [`content_id_mixed`] takes the first 8
+/// bytes of the header and body of each code, the i-th 64-bit word of the
+/// result is the `similarity_hash` of the i-th words of the header byte
+/// followed by the full digest. The first word thus equals the body of
+/// `content_id_mixed` over the 64-bit codes, see [`FullDigest`]. Panics if
+/// `digests` is empty.
+pub fn content_id_mixed_full(digests: &[FullDigest], partial: bool) -> FullDigest {
+    let components: Vec<Vec<u8>> = digests
+        .iter()
+        .map(|d| {
+            let mut component = vec![d.kind.header()];
+            component.extend(&d.digest[..31]);
+            component
+        })
+        .collect();
 
-    base58::encode(&content_id_mixed)
+    let mut digest = [0; 32];
+    for (i, word) in digest.chunks_mut(8).enumerate() {
+        let mut array = [0; 8];
+        let words: Vec<u64> = components
+            .iter()
+            .map(|component| {
+                array.copy_from_slice(&component[i * 8..i * 8 + 8]);
+                u64::from_be_bytes(array)
+            })
+            .collect();
+        word.copy_from_slice(&similarity_hash(words));
+    }
+    FullDigest {
+        kind: CodeKind::ContentMixed { partial },
+        digest,
+    }
 }
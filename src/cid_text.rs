@@ -1,10 +1,29 @@
 //! Content-ID-Text
-use bit_vec::BitVec;
+use std::collections::HashMap;
+use std::fmt;
+
 use itertools::Itertools;
 
-use crate::base58;
-use crate::hashes::{minimum_hash, sliding_window, xxhash32};
-use crate::normalization::text_normalize;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::code::{minhash_planes, CodeKind, FullDigest};
+use crate::error::IsccError;
+use crate::hashes::{
+    containment, minimum_hash, minimum_hash_update, pack_bits, sliding_window, text_features,
+    xxhash32, xxhash32_namespaced, xxhash64,
+};
+#[cfg(feature = "html")]
+use crate::html::html_to_text;
+use crate::metrics::{Metrics, Stage};
+use crate::normalization::{
+    remove_dates, text_normalize, text_normalize_robust, text_normalize_traced,
+};
+use crate::options::IsccOptions;
+use crate::result::CodeResult;
+use crate::script::{script_ratios, Script};
+use crate::tables::{CHUNKING_GEAR, MINHASH_PERMUTATIONS};
+use crate::trace::Trace;
 
 const WINDOW_SIZE_CID_T: usize = 13;
 
@@ -18,26 +37,727 @@ const HEAD_CID_T_PCF: u8 = 0x11;
 ///   "Partial Content Flag". It designates if the Content-ID applies to the
 ///   full content or just some part of it.
 pub fn content_id_text(text: &str, partial: bool) -> String {
+    content_id_text_result(text, partial).code
+}
+
+/// Generates the id like [`content_id_text`] and returns it together with the
+/// raw digest.
+pub fn content_id_text_result(text: &str, partial: bool) -> CodeResult {
+    let features = content_features(text);
+
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    CodeResult::new(header, text_soft_hash(&features).to_vec())
+}
+
+/// The Content-ID-Text together with the Unicode scripts of the normalized
+/// text it was built from, see [`content_id_text_with_scripts`]. Displays as
+/// the code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextResult {
+    pub result: CodeResult,
+    /// The script with the most letters, `None` if the text has no letters.
+    pub dominant_script: Option<Script>,
+    /// The share of every script in the letters, see [`script_ratios`].
+    pub scripts: Vec<(Script, f64)>,
+}
+
+impl fmt::Display for TextResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.result.code)
+    }
+}
+
+/// Generates the id like [`content_id_text_result`] and counts the letters
+/// of the normalized text by their Unicode script, e.g. to choose a matching
+/// threshold per script. The scripts are counted in the text that is hashed,
+/// the code is identical to `content_id_text_result`.
+pub fn content_id_text_with_scripts(text: &str, partial: bool) -> TextResult {
+    let normalized = text_normalize(text, false);
+    let features = hash_windows(&spaced_windows(&normalized), None);
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    let scripts = script_ratios(&normalized);
+    TextResult {
+        result: CodeResult::new(header, text_soft_hash(&features).to_vec()),
+        dominant_script: scripts.first().map(|&(script, _)| script),
+        scripts,
+    }
+}
+
+/// Generates the 256-bit digest of the text id, the first four bit planes of
+/// the MinHash of the features, see [`FullDigest`].
+pub fn content_id_text_full(text: &str, partial: bool) -> FullDigest {
+    let minhash = minimum_hash(content_features(text));
+    FullDigest {
+        kind: CodeKind::ContentText { partial },
+        digest: minhash_planes(&minhash),
+    }
+}
+
+/// The `xxhash32` digests of the space separated 13-character windows of the
+/// normalized text.
+pub(crate) fn content_features(text: &str) -> Vec<u32> {
     let text = text_normalize(text, false);
+    hash_windows(&spaced_windows(&text), None)
+}
 
-    let n_grams: Vec<String> = sliding_window(&text, WINDOW_SIZE_CID_T)
+fn spaced_windows(normalized: &str) -> Vec<String> {
+    sliding_window(normalized, WINDOW_SIZE_CID_T)
         .iter()
         .map(|w| w.chars().intersperse('\u{0020}').collect())
-        .collect();
+        .collect()
+}
 
-    let features: Vec<u32> = n_grams.iter().map(|n| xxhash32(n.as_bytes())).collect();
+fn hash_windows(n_grams: &[String], namespace: Option<&[u8; 16]>) -> Vec<u32> {
+    match namespace {
+        None => n_grams.iter().map(|n| xxhash32(n.as_bytes())).collect(),
+        Some(namespace) => n_grams
+            .iter()
+            .map(|n| xxhash32_namespaced(namespace, n.as_bytes()))
+            .collect(),
+    }
+}
+
+/// Generates the id like [`content_id_text_result`] with the given options,
+/// see [`IsccOptions`]. The default options produce the standard code.
+pub fn content_id_text_with_options(
+    text: &str,
+    partial: bool,
+    options: &IsccOptions,
+) -> CodeResult {
+    let robust = options.ignore_numbers || options.strip_dates;
+    if options.namespace.is_none() && !robust && options.max_features.is_none() {
+        return content_id_text_result(text, partial);
+    }
+    let (features, sampled) = features_with_options(text, options);
 
-    let minhash = minimum_hash(features);
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    let mut result = CodeResult::new(header, text_soft_hash(&features).to_vec());
+    result.namespaced = options.namespace.is_some();
+    result.robust = robust;
+    result.sampled = sampled;
+    result
+}
 
-    let lsb: BitVec = minhash.iter().map(|x| (x & 1) == 1).collect();
-    let lsb_bytes = lsb.to_bytes();
+/// Extracts the features the Content-ID-Text is built from: the text is
+/// normalized, split into windows of `13` characters and every window is
+/// hashed with [`xxhash32`], in the order of the windows. The options are
+/// applied like by [`content_id_text_with_options`], so [`minimum_hash`] over
+/// the features is the sketch the body of the code with the same options is
+/// packed from, see [`soft_hash_text_features`].
+///
+/// The features are guaranteed to be stable within an
+/// [`ALGORITHM_VERSION`](crate::version::ALGORITHM_VERSION) of the
+/// Content-ID-Text.
+pub fn extract_text_features(text: &str, options: &IsccOptions) -> Vec<u32> {
+    features_with_options(text, options).0
+}
 
-    let mut content_id_digest = if partial {
-        vec![HEAD_CID_T_PCF]
-    } else {
-        vec![HEAD_CID_T]
+/// The features of the text with the given options and whether windows were
+/// skipped.
+fn features_with_options(text: &str, options: &IsccOptions) -> (Vec<u32>, bool) {
+    let text = match (options.ignore_numbers, options.strip_dates) {
+        (false, false) => text_normalize(text, false),
+        (false, true) => text_normalize(&remove_dates(text), false),
+        (true, strip_dates) => text_normalize_robust(text, false, strip_dates),
     };
-    content_id_digest.extend(&lsb_bytes);
+    let namespace = options.namespace.as_ref();
+    match options.max_features {
+        Some(max_features) => sampled_features(&text, max_features.max(1), namespace),
+        None => (hash_windows(&spaced_windows(&text), namespace), false),
+    }
+}
+
+/// The features of every `n`-th window of the normalized text, with the
+/// smallest `n` that yields at most `max_features` features, and whether
+/// windows were skipped. The windows are borrowed from `normalized`, so a huge
+/// text costs no more than `max_features` window allocations.
+fn sampled_features(
+    normalized: &str,
+    max_features: usize,
+    namespace: Option<&[u8; 16]>,
+) -> (Vec<u32>, bool) {
+    let chars = normalized.chars().count();
+    let windows = chars.saturating_sub(WINDOW_SIZE_CID_T) + 1;
+    if windows <= max_features {
+        return (hash_windows(&spaced_windows(normalized), namespace), false);
+    }
+    let step = windows.div_ceil(max_features);
+    let ends = normalized
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(normalized.len()))
+        .skip(WINDOW_SIZE_CID_T);
+    let mut spaced = String::new();
+    let features = normalized
+        .char_indices()
+        .map(|(i, _)| i)
+        .zip(ends)
+        .step_by(step)
+        .map(|(start, end)| {
+            spaced.clear();
+            spaced.extend(Itertools::intersperse(
+                normalized[start..end].chars(),
+                '\u{0020}',
+            ));
+            match namespace {
+                None => xxhash32(spaced.as_bytes()),
+                Some(namespace) => xxhash32_namespaced(namespace, spaced.as_bytes()),
+            }
+        })
+        .collect();
+    (features, true)
+}
+
+/// Generates the id like [`content_id_text_result`] and traces the
+/// normalization of the text, see [`Trace`]. The previews of the steps hold up
+/// to `preview_chars` characters.
+pub fn content_id_text_with_trace(
+    text: &str,
+    partial: bool,
+    preview_chars: usize,
+) -> (CodeResult, Trace) {
+    let mut trace = Trace::new(preview_chars);
+    let normalized = text_normalize_traced(text, false, "text", Some(&mut trace));
+    let features = hash_windows(&spaced_windows(&normalized), None);
+
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    (
+        CodeResult::new(header, text_soft_hash(&features).to_vec()),
+        trace,
+    )
+}
+
+/// Generates the id like [`content_id_text_result`] and measures the
+/// generation, see [`Metrics`]. The features are the windows.
+pub fn content_id_text_with_metrics(text: &str, partial: bool) -> (CodeResult, Metrics) {
+    let mut metrics = Metrics {
+        input_bytes: text.len() as u64,
+        input_chars: text.chars().count() as u64,
+        ..Metrics::default()
+    };
+    let normalized = metrics.time(Stage::Normalization, || text_normalize(text, false));
+    let n_grams = metrics.time(Stage::Windowing, || spaced_windows(&normalized));
+    metrics.features = n_grams.len() as u64;
+    let features = metrics.time(Stage::FeatureHashing, || hash_windows(&n_grams, None));
+    let minhash = metrics.time(Stage::MinHash, || minimum_hash(features));
+
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    (
+        CodeResult::new(header, pack_minhash(&minhash).to_vec()),
+        metrics,
+    )
+}
+
+/// Estimates how much of `needle` is contained in `haystack`, e.g. whether a
+/// paragraph was taken from a book, which the global similarity of text codes
+/// cannot detect. Both texts are normalized with [`text_normalize`] like for
+/// [`content_id_text`] and the `xxhash32` digests of their `width`-character
+/// windows are compared with [`feature_containment`]. A text of at most
+/// `width` characters is a single window.
+///
+/// Returns an error if `width` is smaller than 2 or the normalized needle is
+/// empty.
+pub fn text_containment(needle: &str, haystack: &str, width: usize) -> Result<f64, IsccError> {
+    if width < 2 {
+        return Err(IsccError::InvalidArgument(format!(
+            "window width must be 2 or bigger, not {}",
+            width
+        )));
+    }
+    let needle = text_normalize(needle, false);
+    if needle.is_empty() {
+        return Err(IsccError::InvalidArgument(
+            "the needle is empty".to_string(),
+        ));
+    }
+    let needle: Vec<u32> = text_features(&needle, width).collect();
+    let haystack = text_normalize(haystack, false);
+    containment(&needle, text_features(&haystack, width))
+}
+
+/// Generates the id from features that were extracted elsewhere, e.g. with a
+/// custom tokenization. The features are reduced and encoded exactly like the
+/// `xxhash32` digests of the (space separated) 13-character windows of
+/// [`content_id_text`]. Returns an error if there are no features.
+pub fn content_id_text_from_features(features: &[u32], partial: bool) -> Result<String, IsccError> {
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    Ok(CodeResult::new(header, soft_hash_text_features(features)?.to_vec()).code)
+}
+
+/// Returns the 8-byte body of the text id for features that were extracted
+/// elsewhere, see [`content_id_text_from_features`].
+pub fn soft_hash_text_features(features: &[u32]) -> Result<[u8; 8], IsccError> {
+    if features.is_empty() {
+        return Err(IsccError::InvalidArgument(
+            "at least one feature is required".to_string(),
+        ));
+    }
+    Ok(text_soft_hash(features))
+}
+
+/// Packs the least significant bits of the MinHash of the features.
+fn text_soft_hash(features: &[u32]) -> [u8; 8] {
+    pack_minhash(&minimum_hash(features.iter().copied()))
+}
+
+fn pack_minhash(minhash: &[u32]) -> [u8; 8] {
+    let mut soft_hash = [0; 8];
+    soft_hash.copy_from_slice(&pack_bits(minhash.iter().map(|x| (x & 1) == 1)));
+    soft_hash
+}
+
+/// Generates the id like [`content_id_text`], but hashes and sketches the
+/// windows of the normalized text in parallel. The result is identical to
+/// `content_id_text`, which is faster for short texts.
+///
+/// The text is split into segments of about [`TEXT_SEGMENT_SIZE`] bytes at
+/// character boundaries. Each segment sketches the windows that start within
+/// it, reading up to `12` characters into the next segment, and the sketches
+/// are merged by their element-wise minimum.
+#[cfg(feature = "parallel")]
+pub fn content_id_text_parallel(text: &str, partial: bool) -> String {
+    content_id_text_segmented(text, partial, TEXT_SEGMENT_SIZE)
+}
+
+/// The segment size in bytes of [`content_id_text_parallel`].
+#[cfg(feature = "parallel")]
+pub const TEXT_SEGMENT_SIZE: usize = 1 << 20;
+
+#[cfg(feature = "parallel")]
+fn content_id_text_segmented(input: &str, partial: bool, segment_size: usize) -> String {
+    let text = text_normalize(input, false);
+    // A text of at most one window is a single feature.
+    if text.chars().nth(WINDOW_SIZE_CID_T).is_none() {
+        return content_id_text(input, partial);
+    }
+
+    let mut bounds = vec![0];
+    while let Some(&last) = bounds.last() {
+        if last == text.len() {
+            break;
+        }
+        let mut end = (last + segment_size).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        bounds.push(end);
+    }
+
+    let minhash = bounds
+        .par_windows(2)
+        .map(|segment| chunk_minhash(&text, segment[0], segment[1]))
+        .reduce_with(|a, b| a.iter().zip(b.iter()).map(|(x, y)| *x.min(y)).collect())
+        .expect("a long text has at least one segment");
+
+    let header = if partial { HEAD_CID_T_PCF } else { HEAD_CID_T };
+    CodeResult::new(header, pack_minhash(&minhash).to_vec()).code
+}
+
+/// Generates the id like [`content_id_text`] from the visible text of an HTML
+/// document, see [`html_to_text`].
+#[cfg(feature = "html")]
+pub fn content_id_text_html(html: &str, partial: bool) -> String {
+    content_id_text(&html_to_text(html), partial)
+}
+
+/// Minimum, maximum and boundary mask of the chunks of
+/// [`IncrementalTextCode`], in bytes of the normalized text.
+const INCREMENTAL_MIN_CHUNK: usize = 64;
+const INCREMENTAL_MAX_CHUNK: usize = 1024;
+const INCREMENTAL_MASK: u64 = 0xff00_0000_0000_0000;
+
+/// The hit and miss counts of the chunk cache of [`IncrementalTextCode`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of chunks whose MinHash was taken from the cache.
+    pub hits: u64,
+    /// The number of chunks whose windows were hashed.
+    pub misses: u64,
+}
+
+/// Regenerates the text id of a frequently edited text without hashing all
+/// of its windows again. The result of [`IncrementalTextCode::update`] is
+/// always equal to [`content_id_text_result`] of the text.
+///
+/// The normalized text is split into content defined chunks of 64 to 1024
+/// bytes (with a rolling gear hash, so an edit only moves the boundaries
+/// close to it). The MinHash of the windows starting in every chunk is cached
+/// under the `xxhash64` of the chunk and the 12 characters following it, the
+/// MinHash of the text is the element-wise minimum of the MinHashes of its
+/// chunks. Chunks that are not part of the latest text are evicted.
+#[derive(Debug, Clone)]
+pub struct IncrementalTextCode {
+    partial: bool,
+    cache: HashMap<(u64, usize), Vec<u32>>,
+    stats: CacheStats,
+}
+
+impl IncrementalTextCode {
+    pub fn new(partial: bool) -> IncrementalTextCode {
+        IncrementalTextCode {
+            partial,
+            cache: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Generates the id of the new version of the text.
+    pub fn update(&mut self, input: &str) -> CodeResult {
+        let text = text_normalize(input, false);
+        // A text of at most one window is a single feature.
+        if text.chars().nth(WINDOW_SIZE_CID_T).is_none() {
+            self.cache.clear();
+            return content_id_text_result(input, self.partial);
+        }
+
+        let mut minhash = vec![u32::MAX; MINHASH_PERMUTATIONS.len()];
+        let mut cache = HashMap::with_capacity(self.cache.len());
+        let mut start = 0;
+        while start < text.len() {
+            let end = next_chunk_end(&text, start);
+            let overlap_end = text[end..]
+                .char_indices()
+                .nth(WINDOW_SIZE_CID_T - 1)
+                .map_or(text.len(), |(i, _)| end + i);
+            let key = (xxhash64(&text.as_bytes()[start..overlap_end]), end - start);
+            let chunk_minhash = match self.cache.remove(&key) {
+                Some(chunk_minhash) => {
+                    self.stats.hits += 1;
+                    chunk_minhash
+                }
+                None => match cache.get(&key) {
+                    Some(chunk_minhash) => {
+                        self.stats.hits += 1;
+                        Vec::clone(chunk_minhash)
+                    }
+                    None => {
+                        self.stats.misses += 1;
+                        chunk_minhash(&text, start, end)
+                    }
+                },
+            };
+            for (min, &value) in minhash.iter_mut().zip(chunk_minhash.iter()) {
+                *min = (*min).min(value);
+            }
+            cache.insert(key, chunk_minhash);
+            start = end;
+        }
+        self.cache = cache;
+
+        let header = if self.partial {
+            HEAD_CID_T_PCF
+        } else {
+            HEAD_CID_T
+        };
+        CodeResult::new(header, pack_minhash(&minhash).to_vec())
+    }
+
+    /// The cumulated cache statistics of all updates.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// The number of cached chunks, the chunks of the latest text.
+    pub fn cached_chunks(&self) -> usize {
+        self.cache.len()
+    }
+}
+
+/// The end of the chunk of `text` starting at `start`, always a character
+/// boundary.
+fn next_chunk_end(text: &str, start: usize) -> usize {
+    let bytes = text.as_bytes();
+    let mut hash: u64 = 0;
+    let mut end = start;
+    while end < bytes.len() {
+        hash = (hash << 1).wrapping_add(CHUNKING_GEAR[bytes[end] as usize]);
+        end += 1;
+        let length = end - start;
+        if text.is_char_boundary(end)
+            && ((length >= INCREMENTAL_MIN_CHUNK && hash & INCREMENTAL_MASK == 0)
+                || length >= INCREMENTAL_MAX_CHUNK)
+        {
+            break;
+        }
+    }
+    end
+}
+
+/// The MinHash of the windows of `text` starting in `start..end`.
+fn chunk_minhash(text: &str, start: usize, end: usize) -> Vec<u32> {
+    let seq = &text[start..];
+    let ends = seq
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(seq.len()))
+        .skip(WINDOW_SIZE_CID_T);
+    let mut min_features = vec![u32::MAX; MINHASH_PERMUTATIONS.len()];
+    let mut spaced = String::new();
+    for ((i, _), j) in seq.char_indices().zip(ends) {
+        if start + i >= end {
+            break;
+        }
+        spaced.clear();
+        spaced.extend(Itertools::intersperse(seq[i..j].chars(), '\u{0020}'));
+        minimum_hash_update(&mut min_features, xxhash32(spaced.as_bytes()));
+    }
+    min_features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::base58;
+    use crate::hashes::CONTAINMENT_SAMPLE_SIZE;
+
+    use std::collections::HashSet;
+
+    const ARTICLE: &str = "The quick brown fox jumps over the lazy dog. Meanwhile the \
+                           cat watches from the window sill & wonders why anyone would \
+                           jump over a dog at all.";
+    const OTHER: &str = "Rust is a multi-paradigm programming language focused on \
+                         performance and safety, especially safe concurrency.";
+
+    #[cfg(feature = "html")]
+    fn hamming_distance(a: &str, b: &str) -> u32 {
+        base58::decode(a)[1..]
+            .iter()
+            .zip(base58::decode(b)[1..].iter())
+            .map(|(x, y)| (x ^ y).count_ones())
+            .sum()
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_content_id_text_parallel() {
+        const SEGMENT_SIZE: usize = 1000;
+        // Every segment boundary falls into a multi-byte character.
+        let mut text = String::new();
+        let filler = "thequickbrownfoxjumpsoverthelazydog";
+        for (i, ch) in filler.chars().cycle().enumerate() {
+            if text.len() >= 20 * SEGMENT_SIZE {
+                break;
+            }
+            if (text.len() + 1).is_multiple_of(SEGMENT_SIZE) {
+                text.push(if i % 2 == 0 { '驩' } else { '💩' });
+            } else {
+                text.push(ch);
+            }
+        }
+        assert_eq!(text_normalize(&text, false), text);
+        for k in 1..20 {
+            assert!(!text.is_char_boundary(k * SEGMENT_SIZE));
+        }
+
+        let serial = content_id_text(&text, false);
+        for &threads in [1, 2, 8].iter() {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                assert_eq!(
+                    content_id_text_segmented(&text, false, SEGMENT_SIZE),
+                    serial
+                );
+                assert_eq!(content_id_text_segmented(&text, false, 7), serial);
+                assert_eq!(content_id_text_parallel(&text, false), serial);
+            });
+        }
+        for &short in ["", "Hello", ARTICLE].iter() {
+            assert_eq!(
+                content_id_text_segmented(short, true, 16),
+                content_id_text(short, true)
+            );
+        }
+    }
+
+    #[test]
+    fn test_content_id_text_from_features() {
+        for &text in ["", "Hello", ARTICLE, OTHER].iter() {
+            let normalized = text_normalize(text, false);
+            let features: Vec<u32> = sliding_window(&normalized, WINDOW_SIZE_CID_T)
+                .iter()
+                .map(|w| {
+                    let spaced: Vec<String> = w.chars().map(String::from).collect();
+                    xxhash32(spaced.join(" ").as_bytes())
+                })
+                .collect();
+            for &partial in [false, true].iter() {
+                assert_eq!(
+                    content_id_text_from_features(&features, partial).unwrap(),
+                    content_id_text(text, partial)
+                );
+            }
+            let code = content_id_text(text, false);
+            let body = soft_hash_text_features(&features).unwrap();
+            assert_eq!(body[..], base58::decode(&code)[1..]);
+        }
+        assert!(content_id_text_from_features(&[], false).is_err());
+    }
+
+    #[test]
+    fn test_content_id_text_with_metrics() {
+        for &text in ["", "Hello", ARTICLE, OTHER].iter() {
+            let (result, metrics) = content_id_text_with_metrics(text, true);
+            assert_eq!(result, content_id_text_result(text, true));
+            assert_eq!(metrics.input_bytes, text.len() as u64);
+            assert_eq!(metrics.input_chars, text.chars().count() as u64);
+            let normalized = text_normalize(text, false);
+            assert_eq!(
+                metrics.features,
+                sliding_window(&normalized, WINDOW_SIZE_CID_T).len() as u64
+            );
+            assert_eq!(metrics.chunks, 0);
+            for &stage in [Stage::Normalization, Stage::Windowing, Stage::MinHash].iter() {
+                assert!(metrics.stages.iter().any(|(s, _)| *s == stage));
+            }
+        }
+    }
+
+    /// A long text of pseudo random words.
+    fn random_words(n: usize, mut seed: u64) -> String {
+        let words = [
+            "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india",
+            "juliet", "kilo", "lima", "mike", "november", "oscar", "papa",
+        ];
+        (0..n)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                words[(seed % 16) as usize]
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_incremental_text_code() {
+        let mut text: Vec<char> =
+            format!("{} Ünïcödé テキスト 😀 {}", random_words(1000, 4), ARTICLE)
+                .chars()
+                .collect();
+        let mut incremental = IncrementalTextCode::new(false);
+        let mut seed: u64 = 5;
+        let mut random = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        for _ in 0..300 {
+            let at = random(text.len());
+            match random(3) {
+                0 => {
+                    let n = random(20).min(text.len() - at);
+                    text.drain(at..at + n);
+                }
+                1 => {
+                    let insert: Vec<char> = "edit: ÄÖÜ, 42 ".chars().take(random(15)).collect();
+                    text.splice(at..at, insert);
+                }
+                _ => text[at] = 'x',
+            }
+            let text: String = text.iter().collect();
+            assert_eq!(
+                incremental.update(&text),
+                content_id_text_result(&text, false)
+            );
+        }
+        // Most chunks were reused.
+        let stats = incremental.stats();
+        assert!(stats.hits > 10 * stats.misses, "{:?}", stats);
+        assert!(incremental.cached_chunks() > 10);
+
+        // Short texts and the partial flag.
+        let mut incremental = IncrementalTextCode::new(true);
+        for &text in ["", "Hello", ARTICLE, "Hello", OTHER].iter() {
+            assert_eq!(incremental.update(text), content_id_text_result(text, true));
+        }
+    }
+
+    #[test]
+    fn test_incremental_text_code_repeated_chunks() {
+        let text = OTHER.repeat(40);
+        let mut incremental = IncrementalTextCode::new(false);
+        assert_eq!(
+            incremental.update(&text),
+            content_id_text_result(&text, false)
+        );
+        assert!(incremental.stats().hits > 0);
+        let before = incremental.stats();
+        let edited = format!("{} and some more words", text);
+        assert_eq!(
+            incremental.update(&edited),
+            content_id_text_result(&edited, false)
+        );
+        assert!(incremental.stats().misses - before.misses <= 3);
+    }
+
+    #[test]
+    fn test_text_containment() {
+        let book = random_words(50_000, 1);
+        let middle = book.len() / 2 + book[book.len() / 2..].find(' ').unwrap();
+        let planted = format!("{} {} {}", &book[..middle], ARTICLE, &book[middle..]);
+        assert_eq!(text_containment(ARTICLE, &planted, 13).unwrap(), 1.0);
+        assert!(text_containment(OTHER, &planted, 13).unwrap() < 0.05);
+        assert!(text_containment(ARTICLE, &book, 13).unwrap() < 0.05);
+        // The book is not contained in the paragraph.
+        assert!(text_containment(&planted, ARTICLE, 13).unwrap() < 0.05);
+
+        // Repeated windows count once.
+        let repeated = "abcdefgh ".repeat(50);
+        assert_eq!(
+            text_containment(&repeated, &"abcdefgh ".repeat(3), 5).unwrap(),
+            1.0
+        );
+        // Short texts are a single window.
+        assert_eq!(text_containment("Hello", "hello world", 13).unwrap(), 0.0);
+        assert_eq!(text_containment("Hello", "Hello!", 13).unwrap(), 1.0);
+
+        for &(needle, width) in [("", 13), (" .,", 13), ("Hello", 1)].iter() {
+            match text_containment(needle, ARTICLE, width) {
+                Err(IsccError::InvalidArgument(_)) => {}
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_containment_sampled() {
+        // The needle has far more distinct windows than are sampled.
+        let needle = random_words(40_000, 2);
+        let half = needle.len() / 2;
+        let haystack = format!("{} {}", &needle[..half], random_words(20_000, 3));
+
+        let features = |text: &str| -> HashSet<u32> {
+            text_features(&text_normalize(text, false), 13).collect()
+        };
+        let (a, b) = (features(&needle), features(&haystack));
+        assert!(a.len() > 4 * CONTAINMENT_SAMPLE_SIZE);
+        let exact = a.intersection(&b).count() as f64 / a.len() as f64;
+        let estimate = text_containment(&needle, &haystack, 13).unwrap();
+        assert!(
+            (estimate - exact).abs() < 0.05,
+            "estimate {}, exact {}",
+            estimate,
+            exact
+        );
+    }
 
-    base58::encode(&content_id_digest)
+    #[cfg(feature = "html")]
+    #[test]
+    fn test_content_id_text_html() {
+        let html = "<!DOCTYPE html><html><head><title></title>\
+                    <style>body { font-family: serif; }</style>\
+                    <script>var tracking = \"fox dog cat\";</script></head><body>\
+                    <!-- header --><nav></nav><article><p>The <em>quick</em> brown fox \
+                    jumps over the lazy dog.</p><p>Meanwhile the cat watches from the \
+                    <a href=\"/window?a=1&amp;b=2\">window sill</a> &amp; wonders why anyone \
+                    would jump over a dog at all.</p></article></body></html>";
+        let plain = content_id_text(ARTICLE, false);
+        assert_eq!(content_id_text_html(html, false), plain);
+        assert!(hamming_distance(&content_id_text(OTHER, false), &plain) > 10);
+    }
 }
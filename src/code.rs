@@ -0,0 +1,887 @@
+//! Typed Component Codes
+//!
+//! A [`Code`] is a component code with its kind and raw body. A [`FullDigest`]
+//! stores the 256-bit digest of a component from which codes of 64, 128, 192
+//! or 256 bits can be derived without regenerating them from the media.
+//! [`pack_codes`] and [`unpack_codes`] store any number of codes of any
+//! length in a single binary bundle. An [`Iscc`] is a fully qualified ISCC
+//! Code of several components.
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use crate::base58;
+use crate::error::IsccError;
+use crate::multihash::{decode_varint, encode_varint};
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+/// The component type of a code, including the "Partial Content Flag" of the
+/// Content-ID types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeKind {
+    Meta,
+    ContentText { partial: bool },
+    ContentImage { partial: bool },
+    ContentMixed { partial: bool },
+    Data,
+    Instance,
+}
+
+impl CodeKind {
+    /// The 1-byte component header of this kind.
+    pub fn header(self) -> u8 {
+        let (head, partial) = match self {
+            CodeKind::Meta => (0x00, false),
+            CodeKind::ContentText { partial } => (0x10, partial),
+            CodeKind::ContentImage { partial } => (0x12, partial),
+            CodeKind::ContentMixed { partial } => (0x18, partial),
+            CodeKind::Data => (0x20, false),
+            CodeKind::Instance => (0x30, false),
+        };
+        head | partial as u8
+    }
+
+    /// The kind of a 1-byte component header, `None` if the header is not one
+    /// of the component headers of the ISCC specification.
+    pub fn from_header(header: u8) -> Option<CodeKind> {
+        let partial = header & 1 == 1;
+        match header {
+            0x00 => Some(CodeKind::Meta),
+            0x10 | 0x11 => Some(CodeKind::ContentText { partial }),
+            0x12 | 0x13 => Some(CodeKind::ContentImage { partial }),
+            0x18 | 0x19 => Some(CodeKind::ContentMixed { partial }),
+            0x20 => Some(CodeKind::Data),
+            0x30 => Some(CodeKind::Instance),
+            _ => None,
+        }
+    }
+}
+
+/// A component code. Displays as the Base58-ISCC encoded code.
+///
+/// Codes with a body longer than 64 bits **are not part of the ISCC
+/// specification**. They display as the 2-character header followed by 11
+/// characters for every 8 bytes of the body.
+///
+/// A code also knows the [`AlgorithmVersion`] it was generated with. It is not
+/// part of the encoded code: parsed and decoded codes are of the current
+/// [`ALGORITHM_VERSION`] and codes stored by other versions must be tagged
+/// with [`Code::with_version`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Code {
+    kind: CodeKind,
+    body: Vec<u8>,
+    version: AlgorithmVersion,
+}
+
+impl Code {
+    pub fn kind(&self) -> CodeKind {
+        self.kind
+    }
+
+    /// The version of the algorithm the code was generated with.
+    pub fn version(&self) -> AlgorithmVersion {
+        self.version
+    }
+
+    /// Returns the code tagged with the version of the algorithm it was
+    /// generated with, e.g. for codes read from a database that were generated
+    /// by an older version of this crate.
+    pub fn with_version(mut self, version: AlgorithmVersion) -> Code {
+        self.version = version;
+        self
+    }
+
+    /// The raw component body without the header byte.
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The length of the body in bits.
+    pub fn bits(&self) -> u32 {
+        self.body.len() as u32 * 8
+    }
+
+    /// The 64-bit body as integer, `None` if the body is not 64 bits long.
+    ///
+    /// The body is read in big endian byte order: the first byte of the body
+    /// is the most significant byte. The value is thus the number encoded by
+    /// the last 11 characters of the code and can be compared directly with
+    /// [`hamming_distance_u64`](crate::compare::hamming_distance_u64).
+    pub fn body_u64(&self) -> Option<u64> {
+        let mut array = [0; 8];
+        if self.body.len() != array.len() {
+            return None;
+        }
+        array.copy_from_slice(&self.body);
+        Some(u64::from_be_bytes(array))
+    }
+
+    /// The 128-bit body as integer in big endian byte order like
+    /// [`Code::body_u64`], `None` if the body is not 128 bits long.
+    pub fn body_u128(&self) -> Option<u128> {
+        let mut array = [0; 16];
+        if self.body.len() != array.len() {
+            return None;
+        }
+        array.copy_from_slice(&self.body);
+        Some(u128::from_be_bytes(array))
+    }
+
+    /// Builds a code from its kind and a body as returned by
+    /// [`Code::body_u64`]. `bits` is the length of the body and must be `64`.
+    pub fn from_parts(kind: CodeKind, bits: u32, body: u64) -> Result<Code, IsccError> {
+        if bits != 64 {
+            return Err(IsccError::InvalidArgument(format!(
+                "a 64-bit body can not build a code of {} bits",
+                bits
+            )));
+        }
+        Ok(Code {
+            kind,
+            body: body.to_be_bytes().to_vec(),
+            version: ALGORITHM_VERSION,
+        })
+    }
+}
+
+impl Code {
+    /// The header byte followed by the body, i.e. the bytes that are Base58-ISCC
+    /// encoded to the code.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![self.kind.header()];
+        bytes.extend(&self.body);
+        bytes
+    }
+
+    /// Reads a code written with [`Code::to_bytes`]. The body is the longest
+    /// run of 8, 16, 24 or 32 bytes that fits after the header byte.
+    ///
+    /// Returns [`IsccError::InvalidHeader`] for an unknown header,
+    /// [`IsccError::TruncatedCode`] if there are less than 8 bytes after the
+    /// header and [`IsccError::TrailingBytes`] if bytes are left after the
+    /// body.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Code, IsccError> {
+        let (&header, body) = bytes.split_first().ok_or(IsccError::TruncatedCode {
+            expected: 9,
+            actual: 0,
+        })?;
+        let kind = CodeKind::from_header(header).ok_or(IsccError::InvalidHeader(header))?;
+        if body.len() < 8 {
+            return Err(IsccError::TruncatedCode {
+                expected: 9,
+                actual: bytes.len(),
+            });
+        }
+        let len = (body.len() / 8 * 8).min(32);
+        if body.len() > len {
+            return Err(IsccError::TrailingBytes(body.len() - len));
+        }
+        Ok(Code {
+            kind,
+            body: body.to_vec(),
+            version: ALGORITHM_VERSION,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for Code {
+    type Error = IsccError;
+
+    fn try_from(bytes: &[u8]) -> Result<Code, IsccError> {
+        Code::from_bytes(bytes)
+    }
+}
+
+impl From<&Code> for Vec<u8> {
+    fn from(code: &Code) -> Vec<u8> {
+        code.to_bytes()
+    }
+}
+
+impl FromStr for Code {
+    type Err = IsccError;
+
+    /// Parses a code as displayed by [`Code`].
+    fn from_str(code: &str) -> Result<Code, IsccError> {
+        // The header and at most four 64-bit words, checked before decoding
+        if code.len() > 2 + 4 * 11 {
+            return Err(IsccError::InvalidArgument(format!(
+                "a code is at most {} chars, not {}",
+                2 + 4 * 11,
+                code.len()
+            )));
+        }
+        let digest = base58::try_decode_words(code)?;
+        let kind = CodeKind::from_header(digest[0]).ok_or(IsccError::InvalidHeader(digest[0]))?;
+        Ok(Code {
+            kind,
+            body: digest[1..].to_vec(),
+            version: ALGORITHM_VERSION,
+        })
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&base58::encode(&self.to_bytes()))
+    }
+}
+
+/// The maximum number of components of an [`Iscc`].
+pub const MAX_COMPONENTS: usize = 4;
+
+/// A fully qualified ISCC Code, the component codes joined with `-`, e.g. the
+/// Meta-ID, Content-ID, Data-ID and Instance-ID of a content.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Iscc {
+    pub components: Vec<Code>,
+}
+
+impl FromStr for Iscc {
+    type Err = IsccError;
+
+    /// Parses 1 to [`MAX_COMPONENTS`] codes separated by `-`. Returns
+    /// [`IsccError::InvalidArgument`] for more components and the errors of
+    /// [`Code::from_str`] for an invalid component.
+    fn from_str(iscc: &str) -> Result<Iscc, IsccError> {
+        let parts: Vec<&str> = iscc.splitn(MAX_COMPONENTS + 1, '-').collect();
+        if parts.len() > MAX_COMPONENTS {
+            return Err(IsccError::InvalidArgument(format!(
+                "an ISCC has at most {} components",
+                MAX_COMPONENTS
+            )));
+        }
+        let components = parts
+            .into_iter()
+            .map(str::parse)
+            .collect::<Result<Vec<Code>, IsccError>>()?;
+        Ok(Iscc { components })
+    }
+}
+
+impl fmt::Display for Iscc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, code) in self.components.iter().enumerate() {
+            if i > 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{}", code)?;
+        }
+        Ok(())
+    }
+}
+
+/// Normalizes a stored code to its canonical form and checks it. Surrounding
+/// whitespace is removed and
+///
+/// * component codes of any length, checked codes and fully qualified ISCC
+///   Codes in Base58-ISCC are returned unchanged if they are valid,
+/// * hex encoded tophashes and hex encoded codes, see [`Code::to_bytes`], are
+///   returned in lower case.
+///
+/// Base58-ISCC is case sensitive, see [`base58`](crate::base58), codes in
+/// changed case are rejected, as [`IsccError::NonCanonicalCase`] if they are
+/// valid with the case swapped, or, if they happen to be valid, returned as
+/// different codes. Systems that do not preserve case should thus store the
+/// hex encoding of the codes.
+pub fn canonicalize(code: &str) -> Result<String, IsccError> {
+    let code = code.trim();
+    let is_hex = !code.is_empty() && code.bytes().all(|b| b.is_ascii_hexdigit());
+    if is_hex && code.len() == 64 {
+        return Ok(hex::encode(crate::iid::parse_tophash(code)?));
+    }
+    if is_hex && [18, 34, 50, 66].contains(&code.len()) {
+        let bytes = hex::decode(code)
+            .map_err(|e| IsccError::InvalidArgument(format!("invalid hex code: {}", e)))?;
+        return Ok(hex::encode(Code::from_bytes(&bytes)?.to_bytes()));
+    }
+    if code.contains('-') {
+        return Ok(code.parse::<Iscc>()?.to_string());
+    }
+    if code.len() == 16 {
+        base58::decode_checked(code)?;
+        return Ok(code.to_string());
+    }
+    Ok(code.parse::<Code>()?.to_string())
+}
+
+/// The 256-bit digest of a component. Codes are derived from it with
+/// [`FullDigest::to_code`], the first 64 bits are the body of the standard
+/// 64-bit code of the component:
+///
+/// * Meta-ID - the `similarity_hash` of each 64-bit word of the 256-bit n-gram
+///   digests, the first word being the standard `xxhash64` digest.
+/// * Content-ID-Text and Data-ID - four bit planes of the 64 MinHash values,
+///   the least significant bits first. The standard code packs only the least
+///   significant bits, so shorter codes are the first planes.
+/// * Content-ID-Image - the median hashes of the four 8x8 quadrants of the
+///   16x16 lowest frequency DCT coefficients, the upper left quadrant first.
+/// * Content-ID-Mixed - the `similarity_hash` of each 64-bit word of the
+///   header byte followed by the full digest of the aggregated Content-IDs.
+/// * Instance-ID - the tophash.
+///
+/// For all kinds the projection to a shorter code is thus a prefix of the
+/// digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FullDigest {
+    pub kind: CodeKind,
+    pub digest: [u8; 32],
+}
+
+impl FullDigest {
+    /// Returns the code of `bits` bits, which must be 64, 128, 192 or 256.
+    pub fn to_code(&self, bits: u32) -> Result<Code, IsccError> {
+        if bits == 0 || bits > 256 || !bits.is_multiple_of(64) {
+            return Err(IsccError::InvalidArgument(format!(
+                "bits must be 64, 128, 192 or 256, not {}",
+                bits
+            )));
+        }
+        Ok(Code {
+            kind: self.kind,
+            body: self.digest[..bits as usize / 8].to_vec(),
+            version: ALGORITHM_VERSION,
+        })
+    }
+}
+
+const BUNDLE_MAGIC: u8 = 0xcc;
+const BUNDLE_VERSION: u8 = 1;
+
+/// Packs codes into a bundle of
+///
+/// * the magic byte `0xcc`,
+/// * the format version `1` (1 byte),
+/// * the number of codes (unsigned LEB128 varint),
+/// * for every code the length of [`Code::to_bytes`] (unsigned LEB128
+///   varint) followed by these bytes.
+///
+/// The format will not change without a new version, bundles written by this
+/// version will always be read by [`unpack_codes`] and [`CodeReader`].
+pub fn pack_codes(codes: &[Code]) -> Vec<u8> {
+    let mut bytes = vec![BUNDLE_MAGIC, BUNDLE_VERSION];
+    encode_varint(codes.len() as u64, &mut bytes);
+    for code in codes {
+        let entry = code.to_bytes();
+        encode_varint(entry.len() as u64, &mut bytes);
+        bytes.extend(entry);
+    }
+    bytes
+}
+
+/// Reads a bundle written with [`pack_codes`].
+///
+/// Returns [`IsccError::InvalidBundle`] if the magic byte is wrong or an
+/// entry is not 9, 17, 25 or 33 bytes long,
+/// [`IsccError::UnsupportedBundleVersion`] for an unknown version,
+/// [`IsccError::TruncatedEntry`] if the bundle ends within an entry,
+/// [`IsccError::TrailingBytes`] if bytes are left after the last entry and the
+/// errors of [`Code::from_bytes`] for an invalid entry.
+pub fn unpack_codes(bytes: &[u8]) -> Result<Vec<Code>, IsccError> {
+    let mut reader = CodeReader::new(bytes)?;
+    // Every entry takes at least 10 bytes, so a corrupted count does not
+    // allocate more than the bundle size.
+    let mut codes = Vec::with_capacity(reader.remaining.min(bytes.len() as u64 / 10) as usize);
+    for code in &mut reader {
+        codes.push(code?);
+    }
+    Ok(codes)
+}
+
+/// Streams the codes of a bundle written with [`pack_codes`] from a reader,
+/// with the same validation as [`unpack_codes`]. After the last code the
+/// reader is read to its end, any bytes left are reported as
+/// [`IsccError::TrailingBytes`]. The iterator ends after the first error.
+pub struct CodeReader<R: Read> {
+    reader: R,
+    remaining: u64,
+    index: usize,
+    done: bool,
+}
+
+impl<R: Read> CodeReader<R> {
+    /// Reads the header of the bundle.
+    pub fn new(mut reader: R) -> Result<CodeReader<R>, IsccError> {
+        let truncated = || IsccError::InvalidBundle("truncated header".to_string());
+        let mut header = [0; 2];
+        read_exact(&mut reader, &mut header, truncated)?;
+        if header[0] != BUNDLE_MAGIC {
+            return Err(IsccError::InvalidBundle(format!(
+                "invalid magic byte {:#04x}",
+                header[0]
+            )));
+        }
+        if header[1] != BUNDLE_VERSION {
+            return Err(IsccError::UnsupportedBundleVersion(header[1]));
+        }
+        let remaining = read_varint(&mut reader, truncated)?;
+        Ok(CodeReader {
+            reader,
+            remaining,
+            index: 0,
+            done: false,
+        })
+    }
+
+    /// The number of codes that have not been read yet.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    fn read_code(&mut self) -> Result<Code, IsccError> {
+        let index = self.index;
+        let truncated = || IsccError::TruncatedEntry { index };
+        let len = read_varint(&mut self.reader, truncated)?;
+        if !(9..=33).contains(&len) || (len - 1) % 8 > 0 {
+            return Err(IsccError::InvalidBundle(format!(
+                "entry {} has invalid length {}",
+                index, len
+            )));
+        }
+        let mut entry = vec![0; len as usize];
+        read_exact(&mut self.reader, &mut entry, truncated)?;
+        Code::from_bytes(&entry)
+    }
+
+    fn read_trailing(&mut self) -> Result<(), IsccError> {
+        match io::copy(&mut self.reader, &mut io::sink())? {
+            0 => Ok(()),
+            n => Err(IsccError::TrailingBytes(n as usize)),
+        }
+    }
+}
+
+impl<R: Read> Iterator for CodeReader<R> {
+    type Item = Result<Code, IsccError>;
+
+    fn next(&mut self) -> Option<Result<Code, IsccError>> {
+        if self.done {
+            return None;
+        }
+        if self.remaining == 0 {
+            self.done = true;
+            return self.read_trailing().err().map(Err);
+        }
+        let result = self.read_code();
+        self.remaining -= 1;
+        self.index += 1;
+        self.done = result.is_err();
+        Some(result)
+    }
+}
+
+/// Like `Read::read_exact`, but returns the error of `eof` at a premature end
+/// of the data.
+fn read_exact<R: Read, F: Fn() -> IsccError>(
+    reader: &mut R,
+    buf: &mut [u8],
+    eof: F,
+) -> Result<(), IsccError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => eof(),
+        _ => IsccError::Io(e),
+    })
+}
+
+/// Reads an unsigned LEB128 varint byte by byte, see `decode_varint`.
+fn read_varint<R: Read, F: Fn() -> IsccError>(reader: &mut R, eof: F) -> Result<u64, IsccError> {
+    let mut bytes = Vec::with_capacity(10);
+    let mut byte = [0];
+    while bytes.len() < 10 {
+        read_exact(reader, &mut byte, &eof)?;
+        bytes.push(byte[0]);
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    decode_varint(&bytes).map(|(value, _)| value)
+}
+
+/// Packs four bit planes of a 64 value MinHash, bit `0` of every value first.
+pub(crate) fn minhash_planes(minhash: &[u32]) -> [u8; 32] {
+    let mut planes = [0; 32];
+    for (plane, bytes) in planes.chunks_mut(8).enumerate() {
+        for (i, value) in minhash.iter().enumerate() {
+            bytes[i / 8] |= (((value >> plane) & 1) as u8) << (7 - i % 8);
+        }
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        for header in 0..=255 {
+            if let Some(kind) = CodeKind::from_header(header) {
+                assert_eq!(kind.header(), header);
+            }
+        }
+        assert_eq!(CodeKind::from_header(0x31), None);
+        assert_eq!(
+            CodeKind::from_header(0x13),
+            Some(CodeKind::ContentImage { partial: true })
+        );
+    }
+
+    #[test]
+    fn test_to_code() {
+        let decoded = base58::decode("CR6xpnrJkvQDH");
+        let mut digest = [0; 32];
+        digest[..8].copy_from_slice(&decoded[1..]);
+        let full = FullDigest {
+            kind: CodeKind::Instance,
+            digest,
+        };
+        assert_eq!(full.to_code(64).unwrap().to_string(), "CR6xpnrJkvQDH");
+        let long = full.to_code(128).unwrap();
+        assert_eq!(long.bits(), 128);
+        assert_eq!(long.to_string(), "CR6xpnrJkvQDHCCCCCCCCCCC");
+        for bits in &[0, 32, 65, 320] {
+            assert!(full.to_code(*bits).is_err());
+        }
+    }
+
+    #[test]
+    fn test_body_u64_endianness() {
+        let code: Code = "CR6xpnrJkvQDH".parse().unwrap();
+        assert_eq!(code.kind(), CodeKind::Instance);
+        assert_eq!(code.body()[0], 0x23);
+        assert_eq!(code.body_u64(), Some(0x23a5_b78a_0441_43b6));
+        assert_eq!(code.body_u128(), None);
+        assert_eq!(
+            Code::from_parts(CodeKind::Instance, 64, 0x23a5_b78a_0441_43b6)
+                .unwrap()
+                .to_string(),
+            "CR6xpnrJkvQDH"
+        );
+        assert!(Code::from_parts(CodeKind::Instance, 128, 0).is_err());
+    }
+
+    #[test]
+    fn test_body_u128() {
+        let mut digest = [0; 32];
+        digest[0] = 0x80;
+        digest[15] = 0x01;
+        let full = FullDigest {
+            kind: CodeKind::Meta,
+            digest,
+        };
+        let code = full.to_code(128).unwrap();
+        assert_eq!(code.body_u128(), Some(1 << 127 | 1));
+        assert_eq!(code.body_u64(), None);
+        assert_eq!(code.to_string().parse::<Code>().unwrap(), code);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for code in &[
+            "",
+            "CR6xpnrJkvQD",
+            "CR6xpnrJkvQDH1",
+            "Cr6xpnrJkvQDH",
+            "0R6xpnrJkvQDH",
+        ] {
+            assert!(code.parse::<Code>().is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_parse_long() {
+        // Rejected by length before decoding
+        let long = format!("CR{}", "C".repeat(1 << 20));
+        assert!(matches!(
+            long.parse::<Code>(),
+            Err(IsccError::InvalidArgument(_))
+        ));
+        let code = format!("CR{}", "C".repeat(5 * 11));
+        assert!(code.parse::<Code>().is_err());
+    }
+
+    #[test]
+    fn test_parse_iscc() {
+        let iscc = "CCh2TW8YiFhSN-CT7A4zpmccuEv-CDC7Lg4oHA8DC-CRLdd9g4BSUyY";
+        let parsed: Iscc = iscc.parse().unwrap();
+        assert_eq!(parsed.components.len(), 4);
+        assert_eq!(
+            parsed.components[1].kind(),
+            CodeKind::ContentText { partial: false }
+        );
+        assert_eq!(parsed.to_string(), iscc);
+
+        for invalid in &[
+            "",
+            "CCh2TW8YiFhSN-",
+            "CCh2TW8YiFhSN--CT7A4zpmccuEv",
+            "CCh2TW8YiFhSN-CT7A4zpmccuEv-CDC7Lg4oHA8DC-CRLdd9g4BSUyY-CCh2TW8YiFhSN",
+        ] {
+            assert!(invalid.parse::<Iscc>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_regression_component_count() {
+        // Found by fuzzing: the components are capped before they are decoded
+        let many = vec!["CCh2TW8YiFhSN"; 100_000].join("-");
+        assert!(matches!(
+            many.parse::<Iscc>(),
+            Err(IsccError::InvalidArgument(_))
+        ));
+        let five = vec!["CCh2TW8YiFhSN"; MAX_COMPONENTS + 1].join("-");
+        assert!(matches!(
+            five.parse::<Iscc>(),
+            Err(IsccError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_regression_truncated_header() {
+        // Found by fuzzing: every length is checked before slicing
+        for bytes in &[&[][..], &[0x10][..], &[0x10, 0, 0, 0][..]] {
+            assert!(
+                matches!(
+                    Code::from_bytes(bytes),
+                    Err(IsccError::TruncatedCode { .. })
+                ),
+                "{:?}",
+                bytes
+            );
+        }
+        let bundle = pack_codes(&bundle()[..1]);
+        for end in 0..bundle.len() {
+            assert!(unpack_codes(&bundle[..end]).is_err(), "{}", end);
+            assert!(CodeReader::new(&bundle[..end])
+                .and_then(|reader| reader.collect::<Result<Vec<_>, _>>())
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn test_canonical_case() {
+        let full = FullDigest {
+            kind: CodeKind::Meta,
+            digest: [0x5a; 32],
+        };
+        let kinds = [
+            CodeKind::Meta,
+            CodeKind::ContentText { partial: false },
+            CodeKind::ContentText { partial: true },
+            CodeKind::ContentImage { partial: false },
+            CodeKind::ContentImage { partial: true },
+            CodeKind::ContentMixed { partial: false },
+            CodeKind::ContentMixed { partial: true },
+            CodeKind::Data,
+            CodeKind::Instance,
+        ];
+        for &kind in kinds.iter() {
+            let code = FullDigest { kind, ..full }.to_code(64).unwrap();
+            let canonical = code.to_string();
+            assert_eq!(
+                canonicalize(&format!(" {}\n", canonical)).unwrap(),
+                canonical
+            );
+            let checked = base58::encode_checked(&code.to_bytes());
+            assert_eq!(canonicalize(&checked).unwrap(), checked);
+
+            let swapped: String = canonical
+                .chars()
+                .map(|c| match c {
+                    c if c.is_ascii_uppercase() => c.to_ascii_lowercase(),
+                    c => c.to_ascii_uppercase(),
+                })
+                .collect();
+            for variant in &[canonical.to_uppercase(), canonical.to_lowercase(), swapped] {
+                // A case variant is rejected or a different code, never folded
+                match variant.parse::<Code>() {
+                    Ok(parsed) => assert_ne!(parsed, code, "{}", variant),
+                    Err(_) => assert!(canonicalize(variant).is_err()),
+                }
+            }
+            assert!(canonicalize(&checked.to_uppercase()).is_err());
+            assert!(canonicalize(&checked.to_lowercase()).is_err());
+
+            // Hex is case insensitive and canonical in lower case
+            let hex_code = hex::encode(code.to_bytes());
+            for variant in &[hex_code.to_uppercase(), hex_code.clone()] {
+                assert_eq!(canonicalize(variant).unwrap(), hex_code);
+            }
+        }
+
+        let tophash = "23a5b78a044143b612a0d033384a5ebf95343ec812c3e62caff393852d0328a0";
+        assert_eq!(canonicalize(&tophash.to_uppercase()).unwrap(), tophash);
+        let iscc = "CCh2TW8YiFhSN-CT7A4zpmccuEv-CDC7Lg4oHA8DC-CRLdd9g4BSUyY";
+        assert_eq!(canonicalize(iscc).unwrap(), iscc);
+        assert!(canonicalize(&iscc.to_lowercase()).is_err());
+        // Valid with the case swapped
+        assert!(matches!(
+            canonicalize("ccdzz1thkdq5a"),
+            Err(IsccError::NonCanonicalCase { .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_bytes() {
+        let code: Code = "CR6xpnrJkvQDH".parse().unwrap();
+        let bytes: Vec<u8> = (&code).into();
+        assert_eq!(bytes, base58::decode("CR6xpnrJkvQDH"));
+        assert_eq!(Code::try_from(&bytes[..]).unwrap(), code);
+
+        let mut long = bytes.clone();
+        long.extend(&[7; 8]);
+        assert_eq!(Code::from_bytes(&long).unwrap().bits(), 128);
+
+        assert!(matches!(
+            Code::from_bytes(&[]),
+            Err(IsccError::TruncatedCode {
+                expected: 9,
+                actual: 0
+            })
+        ));
+        assert!(matches!(
+            Code::from_bytes(&bytes[..5]),
+            Err(IsccError::TruncatedCode {
+                expected: 9,
+                actual: 5
+            })
+        ));
+        let mut invalid = bytes.clone();
+        invalid[0] = 0x31;
+        assert!(matches!(
+            Code::from_bytes(&invalid),
+            Err(IsccError::InvalidHeader(0x31))
+        ));
+        let mut trailing = bytes.clone();
+        trailing.extend(&[0, 0, 0]);
+        assert!(matches!(
+            Code::from_bytes(&trailing),
+            Err(IsccError::TrailingBytes(3))
+        ));
+        let mut trailing = bytes;
+        trailing.extend(&[0; 40]);
+        assert!(matches!(
+            Code::from_bytes(&trailing),
+            Err(IsccError::TrailingBytes(16))
+        ));
+    }
+
+    fn bundle() -> Vec<Code> {
+        let mut digest = [0; 32];
+        for (i, byte) in digest.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_mul(37);
+        }
+        let kinds = [
+            CodeKind::Meta,
+            CodeKind::ContentText { partial: false },
+            CodeKind::ContentImage { partial: true },
+            CodeKind::ContentMixed { partial: false },
+            CodeKind::Data,
+            CodeKind::Instance,
+        ];
+        kinds
+            .iter()
+            .zip([64, 128, 192, 256, 64, 256].iter())
+            .map(|(&kind, &bits)| FullDigest { kind, digest }.to_code(bits).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_pack_codes() {
+        let codes = bundle();
+        let bytes = pack_codes(&codes);
+        assert_eq!(&bytes[..4], &[0xcc, 1, 6, 9]);
+        assert_eq!(bytes.len(), 3 + 6 + (9 + 17 + 25 + 33 + 9 + 33));
+        assert_eq!(unpack_codes(&bytes).unwrap(), codes);
+        assert_eq!(unpack_codes(&pack_codes(&[])).unwrap(), vec![]);
+
+        let mut reader = CodeReader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.remaining(), 6);
+        let streamed: Vec<Code> = (&mut reader).map(Result::unwrap).collect();
+        assert_eq!(streamed, codes);
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn test_unpack_codes_invalid() {
+        let bytes = pack_codes(&bundle()[..2]);
+        let mut version = bytes.clone();
+        version[1] = 2;
+        assert!(matches!(
+            unpack_codes(&version),
+            Err(IsccError::UnsupportedBundleVersion(2))
+        ));
+        let mut magic = bytes.clone();
+        magic[0] = 0;
+        assert!(matches!(
+            unpack_codes(&magic),
+            Err(IsccError::InvalidBundle(_))
+        ));
+        assert!(matches!(
+            unpack_codes(&bytes[..bytes.len() - 3]),
+            Err(IsccError::TruncatedEntry { index: 1 })
+        ));
+        assert!(matches!(
+            unpack_codes(&bytes[..3 + 10]),
+            Err(IsccError::TruncatedEntry { index: 1 })
+        ));
+        let mut trailing = bytes.clone();
+        trailing.extend(&[0, 0]);
+        assert!(matches!(
+            unpack_codes(&trailing),
+            Err(IsccError::TrailingBytes(2))
+        ));
+        let mut length = bytes.clone();
+        length[3] = 10;
+        assert!(matches!(
+            unpack_codes(&length),
+            Err(IsccError::InvalidBundle(_))
+        ));
+        for end in 0..2 {
+            assert!(matches!(
+                unpack_codes(&bytes[..end]),
+                Err(IsccError::InvalidBundle(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_unpack_codes_corrupted() {
+        let codes = bundle()[..3].to_vec();
+        let bytes = pack_codes(&codes);
+        for i in 0..bytes.len() {
+            for &flip in [0x01, 0x80, 0xff].iter() {
+                let mut corrupted = bytes.clone();
+                corrupted[i] ^= flip;
+                if let Ok(unpacked) = unpack_codes(&corrupted) {
+                    assert_ne!(unpacked, codes, "byte {} ^ {:#x}", i, flip);
+                }
+                let streamed: Result<Vec<Code>, IsccError> = match CodeReader::new(&corrupted[..]) {
+                    Ok(reader) => reader.collect(),
+                    Err(e) => Err(e),
+                };
+                assert_eq!(
+                    streamed.is_ok(),
+                    unpack_codes(&corrupted).is_ok(),
+                    "byte {} ^ {:#x}",
+                    i,
+                    flip
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_minhash_planes() {
+        let mut minhash = vec![0u32; 64];
+        minhash[0] = 0b0001;
+        minhash[9] = 0b1010;
+        let planes = minhash_planes(&minhash);
+        assert_eq!(planes[0], 0x80);
+        assert_eq!(planes[8 + 1], 0x40);
+        assert_eq!(planes[24 + 1], 0x40);
+        assert_eq!(planes.iter().map(|b| b.count_ones()).sum::<u32>(), 3);
+    }
+}
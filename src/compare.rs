@@ -0,0 +1,766 @@
+//! Code Comparison
+//!
+//! Hamming distance based search over the 64-bit bodies of similarity
+//! preserving component codes (e.g. Meta-ID, Content-ID and Data-ID), shard
+//! keys for sharded indexes of such codes and constant-time equality for
+//! digests like the Instance-ID tophash.
+//!
+//! The functions over [`Code`]s refuse to compare codes of algorithm versions
+//! that construct their component differently, see [`is_comparable`].
+use std::collections::{BinaryHeap, HashMap};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::code::Code;
+use crate::error::IsccError;
+
+/// Number of differing bits between two 64-bit code bodies, e.g. from
+/// [`Code::body_u64`](crate::code::Code::body_u64).
+pub fn hamming_distance_u64(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Returns `true` if two codes are of the same component type, ignoring the
+/// partial content flag, and were generated by algorithm versions that
+/// construct this component the same way, see
+/// [`AlgorithmVersion::construction_of`](crate::version::AlgorithmVersion::construction_of).
+pub fn is_comparable(a: &Code, b: &Code) -> bool {
+    a.kind().header() >> 1 == b.kind().header() >> 1
+        && a.version().construction_of(a.kind()) == b.version().construction_of(b.kind())
+}
+
+/// Number of differing bits between the bodies of two codes of equal length.
+///
+/// Returns [`IsccError::VersionMismatch`] if the codes were generated by
+/// incompatible algorithm versions and [`IsccError::InvalidArgument`] if they
+/// are of different component types or lengths.
+pub fn distance(a: &Code, b: &Code) -> Result<u32, IsccError> {
+    check_comparable(a, b)?;
+    if a.bits() != b.bits() {
+        return Err(IsccError::InvalidArgument(format!(
+            "cannot compare codes of {} and {} bits",
+            a.bits(),
+            b.bits()
+        )));
+    }
+    Ok(a.body()
+        .iter()
+        .zip(b.body())
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum())
+}
+
+/// Returns the indices and distances of the `k` candidates closest to
+/// `query`, sorted by ascending distance. Candidates with equal distance are
+/// ordered by index, so ties at the `k` boundary keep the lowest indices.
+pub fn nearest(query: u64, candidates: &[u64], k: usize) -> Vec<(usize, u32)> {
+    nearest_from(query, candidates, 0, k)
+}
+
+/// Like [`nearest`], but searches chunks of the candidates in parallel. The
+/// result is identical to [`nearest`].
+#[cfg(feature = "parallel")]
+pub fn nearest_parallel(query: u64, candidates: &[u64], k: usize) -> Vec<(usize, u32)> {
+    const CHUNK_SIZE: usize = 1 << 16;
+    let mut merged: Vec<(usize, u32)> = candidates
+        .par_chunks(CHUNK_SIZE)
+        .enumerate()
+        .flat_map(|(i, chunk)| nearest_from(query, chunk, i * CHUNK_SIZE, k))
+        .collect();
+    merged.sort_unstable_by_key(|&(index, distance)| (distance, index));
+    merged.truncate(k);
+    merged
+}
+
+/// Returns the indices and distances of all candidates within `max_distance`
+/// of `query`, sorted like [`nearest`].
+pub fn within(query: u64, candidates: &[u64], max_distance: u32) -> Vec<(usize, u32)> {
+    let mut result: Vec<(usize, u32)> = candidates
+        .iter()
+        .map(|&candidate| hamming_distance_u64(query, candidate))
+        .enumerate()
+        .filter(|&(_, distance)| distance <= max_distance)
+        .collect();
+    result.sort_by_key(|&(index, distance)| (distance, index));
+    result
+}
+
+/// Groups the indices of `codes` whose bodies are within `max_distance` of
+/// each other. Clusters are transitive (single linkage): two codes end up in
+/// the same cluster if they are connected by a chain of close pairs. Every
+/// cluster is sorted and the clusters are ordered by their first index, codes
+/// without close neighbours form clusters of their own.
+///
+/// Instead of comparing all pairs, only codes that share one of the four
+/// 16-bit chunks of their body are compared. Any pair within
+/// `max_distance <= 3` shares at least one chunk and is guaranteed to land in
+/// the same cluster, for larger distances pairs that differ in all four
+/// chunks may be missed.
+///
+/// All codes must have a 64-bit body and the same component type, the partial
+/// content flag is ignored. Returns an error otherwise, and
+/// [`IsccError::VersionMismatch`] for codes that are not [`is_comparable`].
+pub fn cluster(codes: &[Code], max_distance: u32) -> Result<Vec<Vec<usize>>, IsccError> {
+    let bodies = bodies(codes, codes.first())?;
+    let mut sets = DisjointSets::new(bodies.len());
+    let mut buckets: HashMap<u16, Vec<usize>> = HashMap::new();
+    for band in 0..4 {
+        buckets.clear();
+        for (i, &body) in bodies.iter().enumerate() {
+            let chunk = (body >> (band * 16)) as u16;
+            buckets.entry(chunk).or_default().push(i);
+        }
+        for bucket in buckets.values() {
+            for (n, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[n + 1..] {
+                    if hamming_distance_u64(bodies[a], bodies[b]) <= max_distance {
+                        sets.union(a, b);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut cluster_of_root: HashMap<usize, usize> = HashMap::new();
+    for i in 0..bodies.len() {
+        let root = sets.find(i);
+        let next = clusters.len();
+        let cluster = *cluster_of_root.entry(root).or_insert(next);
+        if cluster == next {
+            clusters.push(Vec::new());
+        }
+        clusters[cluster].push(i);
+    }
+    Ok(clusters)
+}
+
+/// Returns the indices into `a` and `b` and the distances of all pairs of
+/// codes whose bodies are within `max_distance`, sorted by index into `a` and
+/// then into `b`.
+///
+/// The shorter list is indexed by `max_distance + 1` chunks of the 64-bit
+/// body and the longer list is probed against the index. Two bodies within
+/// `max_distance` are equal in at least one chunk, so no pair is missed. For
+/// `max_distance >= 64` every pair matches and all pairs are compared
+/// directly. With the `parallel` feature the longer list is probed in
+/// parallel.
+///
+/// All codes must have a 64-bit body and the same component type, the partial
+/// content flag is ignored. Returns an error otherwise, and
+/// [`IsccError::VersionMismatch`] for codes that are not [`is_comparable`].
+pub fn match_pairs(
+    a: &[Code],
+    b: &[Code],
+    max_distance: u32,
+) -> Result<Vec<(usize, usize, u32)>, IsccError> {
+    let first = a.first().or_else(|| b.first());
+    let (a_bodies, b_bodies) = (bodies(a, first)?, bodies(b, first)?);
+    let swapped = a_bodies.len() > b_bodies.len();
+    let (indexed, probes) = if swapped {
+        (&b_bodies, &a_bodies)
+    } else {
+        (&a_bodies, &b_bodies)
+    };
+
+    if max_distance >= 64 {
+        let mut pairs = Vec::with_capacity(a_bodies.len() * b_bodies.len());
+        for (i, &x) in a_bodies.iter().enumerate() {
+            for (j, &y) in b_bodies.iter().enumerate() {
+                pairs.push((i, j, hamming_distance_u64(x, y)));
+            }
+        }
+        return Ok(pairs);
+    }
+
+    let bands = band_ranges(max_distance);
+    let mut index: Vec<HashMap<u64, Vec<usize>>> = vec![HashMap::new(); bands.len()];
+    for (i, &body) in indexed.iter().enumerate() {
+        for (band, &mask) in index.iter_mut().zip(bands.iter()) {
+            band.entry(body & mask).or_default().push(i);
+        }
+    }
+
+    let probe = |(p, &body): (usize, &u64)| {
+        let mut found = Vec::new();
+        for (k, (band, &mask)) in index.iter().zip(bands.iter()).enumerate() {
+            let candidates = match band.get(&(body & mask)) {
+                Some(candidates) => candidates,
+                None => continue,
+            };
+            for &i in candidates {
+                let other = indexed[i];
+                // Report every pair only for the first equal chunk.
+                if bands[..k].iter().any(|&m| body & m == other & m) {
+                    continue;
+                }
+                let distance = hamming_distance_u64(body, other);
+                if distance <= max_distance {
+                    found.push(if swapped {
+                        (p, i, distance)
+                    } else {
+                        (i, p, distance)
+                    });
+                }
+            }
+        }
+        found
+    };
+    #[cfg(not(feature = "parallel"))]
+    let mut pairs: Vec<(usize, usize, u32)> = probes.iter().enumerate().flat_map(probe).collect();
+    #[cfg(feature = "parallel")]
+    let mut pairs: Vec<(usize, usize, u32)> =
+        probes.par_iter().enumerate().flat_map_iter(probe).collect();
+    pairs.sort_unstable();
+    Ok(pairs)
+}
+
+/// Returns the first `prefix_bits` bits (1 to 32) of the body of `code` as
+/// integer, e.g. to pick the shard of a sharded index.
+///
+/// The body is read in big endian byte order like
+/// [`Code::body_u64`](crate::code::Code::body_u64): the first bit of the body
+/// is the most significant bit of the body and of the key. The key is thus
+/// `body_u64 >> (64 - prefix_bits)` and a bit flip in the prefix flips the
+/// same bit of the key that [`hamming_distance_u64`] counts. Returns an error
+/// if `prefix_bits` is out of range.
+pub fn shard_key(code: &Code, prefix_bits: u32) -> Result<u32, IsccError> {
+    if !(1..=32).contains(&prefix_bits) {
+        return Err(IsccError::InvalidArgument(format!(
+            "prefix_bits must be between 1 and 32, not {}",
+            prefix_bits
+        )));
+    }
+    let body = code.body();
+    let prefix = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+    Ok((u64::from(prefix) >> (32 - prefix_bits)) as u32)
+}
+
+/// Returns the [`shard_key`]s of all codes whose prefix differs from the
+/// prefix of `code` in at most `max_distance` bits, in ascending order. The
+/// key of `code` itself is always included.
+///
+/// A code within `max_distance` of `code` has one of these keys, so probing
+/// these shards finds all near duplicates. There are
+/// `sum(C(prefix_bits, i))` keys for `i` up to `max_distance`, which grows
+/// fast: keep `max_distance` small for long prefixes.
+pub fn shard_keys_within(
+    code: &Code,
+    prefix_bits: u32,
+    max_distance: u32,
+) -> Result<Vec<u32>, IsccError> {
+    let key = shard_key(code, prefix_bits)?;
+    let mut keys = vec![key];
+    flip_bits(
+        key,
+        0,
+        prefix_bits,
+        max_distance.min(prefix_bits),
+        &mut keys,
+    );
+    keys.sort_unstable();
+    Ok(keys)
+}
+
+/// Pushes `key` with every combination of 1 to `flips` bits at positions
+/// `from..bits` flipped.
+fn flip_bits(key: u32, from: u32, bits: u32, flips: u32, keys: &mut Vec<u32>) {
+    if flips == 0 {
+        return;
+    }
+    for bit in from..bits {
+        let flipped = key ^ (1 << bit);
+        keys.push(flipped);
+        flip_bits(flipped, bit + 1, bits, flips - 1, keys);
+    }
+}
+
+/// Bit masks of `max_distance + 1` contiguous chunks covering the 64 bits of
+/// a body. `max_distance` must be below 64.
+fn band_ranges(max_distance: u32) -> Vec<u64> {
+    debug_assert!(max_distance < 64);
+    let bands = max_distance as usize + 1;
+    (0..bands)
+        .map(|k| {
+            let (start, end) = (k * 64 / bands, (k + 1) * 64 / bands);
+            let width = end - start;
+            let mask = if width == 64 {
+                u64::MAX
+            } else {
+                (1 << width) - 1
+            };
+            mask << start
+        })
+        .collect()
+}
+
+/// The 64-bit bodies of `codes`, which must be of the component type of
+/// `first`.
+fn bodies(codes: &[Code], first: Option<&Code>) -> Result<Vec<u64>, IsccError> {
+    let mut bodies = Vec::with_capacity(codes.len());
+    for code in codes {
+        check_comparable(code, first.unwrap_or(code))?;
+        bodies.push(code.body_u64().ok_or_else(|| {
+            IsccError::InvalidArgument(format!(
+                "can only compare 64-bit codes, got {} bits",
+                code.bits()
+            ))
+        })?);
+    }
+    Ok(bodies)
+}
+
+/// Returns an error if `a` and `b` are not [`is_comparable`].
+fn check_comparable(a: &Code, b: &Code) -> Result<(), IsccError> {
+    if a.kind().header() >> 1 != b.kind().header() >> 1 {
+        return Err(IsccError::InvalidArgument(format!(
+            "cannot compare {:?} with {:?} codes",
+            a.kind(),
+            b.kind()
+        )));
+    }
+    if !is_comparable(a, b) {
+        return Err(IsccError::VersionMismatch {
+            expected: b.version(),
+            actual: a.version(),
+        });
+    }
+    Ok(())
+}
+
+/// Union-find with path halving and union by size.
+struct DisjointSets {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(n: usize) -> DisjointSets {
+        DisjointSets {
+            parent: (0..n).collect(),
+            size: vec![1; n],
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parent[i] != i {
+            self.parent[i] = self.parent[self.parent[i]];
+            i = self.parent[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+}
+
+/// Bounded max-heap search, `offset` is added to the returned indices.
+fn nearest_from(query: u64, candidates: &[u64], offset: usize, k: usize) -> Vec<(usize, u32)> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap: BinaryHeap<(u32, usize)> = BinaryHeap::with_capacity(k + 1);
+    for (i, &candidate) in candidates.iter().enumerate() {
+        let entry = (hamming_distance_u64(query, candidate), offset + i);
+        if heap.len() < k {
+            heap.push(entry);
+        } else if entry < *heap.peek().unwrap() {
+            heap.pop();
+            heap.push(entry);
+        }
+    }
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|(distance, index)| (index, distance))
+        .collect()
+}
+
+/// Compares two digests in time that only depends on their length, not on
+/// their content. Digests of different length are never equal.
+///
+/// Use this instead of `==` when comparing a computed digest against a stored
+/// or claimed one, e.g. an Instance-ID tophash.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a
+        .iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    diff == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::code::{CodeKind, FullDigest};
+
+    #[test]
+    fn test_ct_eq() {
+        let a = [0x5a_u8; 32];
+        assert!(ct_eq(&a, &a.clone()));
+        assert!(ct_eq(&[], &[]));
+        let mut b = a;
+        b[0] ^= 1;
+        assert!(!ct_eq(&a, &b));
+        let mut b = a;
+        b[31] ^= 0x80;
+        assert!(!ct_eq(&a, &b));
+        assert!(!ct_eq(&a, &a[..31]));
+        assert!(!ct_eq(&a[..1], &a));
+    }
+
+    #[test]
+    fn test_shard_key() {
+        let code: Code = "CR6xpnrJkvQDH".parse().unwrap();
+        // The body is 0x23a5_b78a_0441_43b6, 0x23 = 0b0010_0011
+        assert_eq!(shard_key(&code, 1).unwrap(), 0);
+        assert_eq!(shard_key(&code, 3).unwrap(), 0b001);
+        assert_eq!(shard_key(&code, 8).unwrap(), 0x23);
+        assert_eq!(shard_key(&code, 12).unwrap(), 0x23a);
+        assert_eq!(shard_key(&code, 32).unwrap(), 0x23a5_b78a);
+        for bits in 1..=32 {
+            assert_eq!(
+                u64::from(shard_key(&code, bits).unwrap()),
+                code.body_u64().unwrap() >> (64 - bits)
+            );
+        }
+        assert!(shard_key(&code, 0).is_err());
+        assert!(shard_key(&code, 33).is_err());
+
+        let long = FullDigest {
+            kind: CodeKind::Data,
+            digest: [0xf0; 32],
+        }
+        .to_code(256)
+        .unwrap();
+        assert_eq!(shard_key(&long, 4).unwrap(), 0xf);
+    }
+
+    #[test]
+    fn test_shard_keys_within() {
+        let code: Code = "CR6xpnrJkvQDH".parse().unwrap();
+        for &(bits, max_distance, count) in [
+            (16, 0, 1),
+            (16, 1, 17),
+            (16, 2, 1 + 16 + 120),
+            (16, 3, 1 + 16 + 120 + 560),
+            (4, 10, 16),
+            (32, 1, 33),
+        ]
+        .iter()
+        {
+            let key = shard_key(&code, bits).unwrap();
+            let keys = shard_keys_within(&code, bits, max_distance).unwrap();
+            assert_eq!(keys.len(), count, "{} bits within {}", bits, max_distance);
+            assert!(keys.binary_search(&key).is_ok());
+            assert!(keys.windows(2).all(|w| w[0] < w[1]));
+            assert!(keys
+                .iter()
+                .all(|k| (k ^ key).count_ones() <= max_distance && k >> (bits - 1) <= 1));
+        }
+
+        // Every code within the distance lands in one of the probed shards
+        let body = code.body_u64().unwrap();
+        let keys = shard_keys_within(&code, 12, 2).unwrap();
+        for a in 0..64 {
+            for b in 0..64 {
+                let near = Code::from_parts(code.kind(), 64, body ^ (1 << a) ^ (1 << b)).unwrap();
+                assert!(keys.contains(&shard_key(&near, 12).unwrap()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+        let a: Code = "CR6xpnrJkvQDH".parse().unwrap();
+        let b = Code::from_parts(a.kind(), 64, a.body_u64().unwrap() ^ 0b101).unwrap();
+        assert_eq!(a.version(), ALGORITHM_VERSION);
+        assert!(is_comparable(&a, &b));
+        assert_eq!(distance(&a, &b).unwrap(), 2);
+
+        // A future version that changed the construction of every component
+        let future = b
+            .clone()
+            .with_version(AlgorithmVersion(ALGORITHM_VERSION.0 + 1));
+        assert!(!is_comparable(&a, &future));
+        assert!(matches!(
+            distance(&a, &future),
+            Err(IsccError::VersionMismatch { .. })
+        ));
+        assert!(matches!(
+            cluster(&[a.clone(), future.clone()], 3),
+            Err(IsccError::VersionMismatch { .. })
+        ));
+        assert!(matches!(
+            match_pairs(std::slice::from_ref(&a), &[future], 3),
+            Err(IsccError::VersionMismatch { .. })
+        ));
+
+        let meta: Code = "CCh2TW8YiFhSN".parse().unwrap();
+        assert!(!is_comparable(&a, &meta));
+        assert!(matches!(
+            distance(&a, &meta),
+            Err(IsccError::InvalidArgument(_))
+        ));
+    }
+
+    fn random_u64s(n: usize, mut seed: u64) -> Vec<u64> {
+        (0..n)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed
+            })
+            .collect()
+    }
+
+    fn brute_force(query: u64, candidates: &[u64], k: usize) -> Vec<(usize, u32)> {
+        let mut all: Vec<(usize, u32)> = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| (i, (query ^ c).count_ones()))
+            .collect();
+        all.sort_by_key(|&(i, d)| (d, i));
+        all.truncate(k);
+        all
+    }
+
+    #[test]
+    fn test_nearest() {
+        let candidates = random_u64s(5000, 1);
+        for &query in random_u64s(5, 2).iter() {
+            for &k in [0, 1, 10, 100, 6000].iter() {
+                assert_eq!(
+                    nearest(query, &candidates, k),
+                    brute_force(query, &candidates, k)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_nearest_ties() {
+        // Only 4 distinct bit patterns, so nearly all distances are ties
+        let candidates: Vec<u64> = random_u64s(1000, 3).iter().map(|v| v & 0b11).collect();
+        for &k in [1, 7, 250, 999].iter() {
+            let result = nearest(0, &candidates, k);
+            assert_eq!(result, brute_force(0, &candidates, k));
+        }
+    }
+
+    #[test]
+    fn test_within() {
+        let candidates = random_u64s(2000, 4);
+        let query = candidates[17] ^ 0b101;
+        let result = within(query, &candidates, 20);
+        assert_eq!(result[0], (17, 2));
+        let expected: Vec<(usize, u32)> = brute_force(query, &candidates, 2000)
+            .into_iter()
+            .filter(|&(_, d)| d <= 20)
+            .collect();
+        assert_eq!(result, expected);
+    }
+
+    fn data_codes(bodies: &[u64]) -> Vec<Code> {
+        bodies
+            .iter()
+            .map(|&body| Code::from_parts(CodeKind::Data, 64, body).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_cluster() {
+        // Three planted clusters of codes within distance 3 of their centre
+        // and of each other, and unrelated singletons in between.
+        let centres = random_u64s(3, 6);
+        let singletons = random_u64s(20, 7);
+        let flips = [0, 0b1, 0b1 << 20, 0b1 << 40 | 0b1 << 63, 0b11 << 33];
+        let mut bodies = Vec::new();
+        let mut expected: Vec<Vec<usize>> = vec![Vec::new(); 3];
+        for (i, &singleton) in singletons.iter().enumerate() {
+            let centre = i % 3;
+            if i < flips.len() * 3 {
+                expected[centre].push(bodies.len());
+                bodies.push(centres[centre] ^ flips[i / 3]);
+            }
+            bodies.push(singleton);
+        }
+        let clusters = cluster(&data_codes(&bodies), 3).unwrap();
+
+        let mut planted: Vec<Vec<usize>> =
+            clusters.iter().filter(|c| c.len() > 1).cloned().collect();
+        planted.sort();
+        expected.sort();
+        assert_eq!(planted, expected);
+        assert_eq!(
+            clusters.iter().map(|c| c.len()).sum::<usize>(),
+            bodies.len()
+        );
+        assert_eq!(clusters.len(), 3 + singletons.len());
+        assert!(clusters.windows(2).all(|w| w[0][0] < w[1][0]));
+
+        // Everything is a singleton without tolerance for the flipped bits.
+        let mut bodies = bodies.clone();
+        bodies.sort();
+        bodies.dedup();
+        assert_eq!(
+            cluster(&data_codes(&bodies), 0).unwrap().len(),
+            bodies.len()
+        );
+        assert_eq!(cluster(&[], 3).unwrap(), Vec::<Vec<usize>>::new());
+    }
+
+    #[test]
+    fn test_cluster_transitive() {
+        // a - b and b - c are within distance 2, a - c is not.
+        let bodies = [0, 0b11, 0b1111];
+        assert_eq!(
+            cluster(&data_codes(&bodies), 2).unwrap(),
+            vec![vec![0, 1, 2]]
+        );
+    }
+
+    #[test]
+    fn test_cluster_invalid() {
+        let mut codes = data_codes(&[1, 2]);
+        codes.push(Code::from_parts(CodeKind::Meta, 64, 1).unwrap());
+        match cluster(&codes, 3) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        let codes = vec![
+            Code::from_parts(CodeKind::ContentText { partial: false }, 64, 1).unwrap(),
+            Code::from_parts(CodeKind::ContentText { partial: true }, 64, 3).unwrap(),
+        ];
+        assert_eq!(cluster(&codes, 1).unwrap(), vec![vec![0, 1]]);
+        let codes = vec![
+            Code::from_parts(CodeKind::ContentText { partial: false }, 64, 1).unwrap(),
+            Code::from_parts(CodeKind::ContentImage { partial: false }, 64, 1).unwrap(),
+        ];
+        assert!(cluster(&codes, 1).is_err());
+        let wide: Code = FullDigest {
+            kind: CodeKind::Data,
+            digest: [0; 32],
+        }
+        .to_code(128)
+        .unwrap();
+        match cluster(&[wide], 3) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_cluster_scale() {
+        let mut bodies = random_u64s(100_000, 8);
+        // Plant 1000 near duplicates of the first codes.
+        for i in 0..1000 {
+            let body = bodies[i] ^ (1 << (i % 64));
+            bodies.push(body);
+        }
+        let start = std::time::Instant::now();
+        let clusters = cluster(&data_codes(&bodies), 3).unwrap();
+        assert!(start.elapsed().as_secs() < 30);
+        assert_eq!(clusters.len(), 100_000);
+        for (i, cluster) in clusters.iter().enumerate().take(1000) {
+            assert_eq!(cluster, &vec![i, 100_000 + i]);
+        }
+    }
+
+    fn brute_force_pairs(a: &[u64], b: &[u64], max_distance: u32) -> Vec<(usize, usize, u32)> {
+        let mut pairs = Vec::new();
+        for (i, &x) in a.iter().enumerate() {
+            for (j, &y) in b.iter().enumerate() {
+                let distance = hamming_distance_u64(x, y);
+                if distance <= max_distance {
+                    pairs.push((i, j, distance));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn test_match_pairs() {
+        let a = random_u64s(50, 9);
+        let mut b = random_u64s(3000, 10);
+        // Planted matches at, below and above the threshold of 5.
+        b[10] = a[3];
+        b[20] = a[7] ^ 0b11111;
+        b[30] = a[9] ^ 0b111111;
+        b[40] = a[11] ^ (1 << 63 | 1 << 31 | 1);
+        let pairs = match_pairs(&data_codes(&a), &data_codes(&b), 5).unwrap();
+        assert!(pairs.contains(&(3, 10, 0)));
+        assert!(pairs.contains(&(7, 20, 5)));
+        assert!(!pairs.iter().any(|&(i, j, _)| (i, j) == (9, 30)));
+        assert!(pairs.contains(&(11, 40, 3)));
+        assert_eq!(pairs, brute_force_pairs(&a, &b, 5));
+
+        // The indices refer to the lists as given, whichever is longer.
+        let swapped = match_pairs(&data_codes(&b), &data_codes(&a), 5).unwrap();
+        let mut expected: Vec<(usize, usize, u32)> =
+            pairs.iter().map(|&(i, j, d)| (j, i, d)).collect();
+        expected.sort();
+        assert_eq!(swapped, expected);
+
+        assert_eq!(match_pairs(&[], &data_codes(&a), 5).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_match_pairs_brute_force() {
+        let mut a = random_u64s(300, 11);
+        a.push(0);
+        let mut b = random_u64s(200, 12);
+        b.push(u64::MAX);
+        for &max_distance in [0, 1, 3, 8, 20, 63, 64, 100].iter() {
+            assert_eq!(
+                match_pairs(&data_codes(&a), &data_codes(&b), max_distance).unwrap(),
+                brute_force_pairs(&a, &b, max_distance),
+                "max distance {}",
+                max_distance
+            );
+        }
+    }
+
+    #[test]
+    fn test_match_pairs_invalid() {
+        let a = data_codes(&[1, 2]);
+        let b = vec![Code::from_parts(CodeKind::Meta, 64, 1).unwrap()];
+        match match_pairs(&a, &b, 3) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match match_pairs(&[], &[b[0].clone(), a[0].clone()], 3) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_nearest_parallel() {
+        let candidates: Vec<u64> = random_u64s(300_000, 5).iter().map(|v| v & 0xff).collect();
+        for &k in [1, 10, 1000].iter() {
+            assert_eq!(
+                nearest_parallel(0, &candidates, k),
+                nearest(0, &candidates, k)
+            );
+        }
+    }
+}
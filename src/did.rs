@@ -1,12 +1,19 @@
 //! Data-ID
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::Read;
+use std::time::{Duration, Instant};
 
-use bit_vec::BitVec;
-
-use crate::base58::encode;
-use crate::constants::CHUNKING_GEAR;
-use crate::hashes::{minimum_hash, xxhash32};
+use crate::checkpoint;
+use crate::code::{minhash_planes, CodeKind, FullDigest};
+use crate::error::IsccError;
+use crate::hashes::{
+    minhash_similarity, minimum_hash, minimum_hash_update, pack_bits, xxhash32, xxhash32_namespaced,
+};
+use crate::metrics::{Metrics, Stage, TimedReader};
+use crate::options::{ChunkingOptions, IsccOptions};
+use crate::result::CodeResult;
+use crate::tables::CHUNKING_GEAR;
 
 const GEAR1_NORM: usize = 40;
 const GEAR1_MIN: usize = 20;
@@ -25,24 +32,384 @@ const HEAD_DID: u8 = 0x20;
 /// For the Data-ID that encodes data similarity we use a content defined
 /// chunking algorithm that provides some shift resistance and calculate the
 /// MinHash from those chunks.
+///
+/// Empty data has no chunks and the MinHash of no features is undefined, so
+/// like the reference implementation, which fails on empty data, every
+/// function of this module returns an error for it instead of a code:
+/// [`IsccError::EmptyInput`] or an `io::Error` of kind `InvalidInput`.
 pub fn data_id(data_path: &str) -> std::io::Result<String> {
+    Ok(data_id_result(data_path)?.code)
+}
+
+/// Generates the Data-ID like [`data_id`] and returns it together with the
+/// raw digest.
+pub fn data_id_result(data_path: &str) -> std::io::Result<CodeResult> {
     let data = File::open(data_path)?;
+    extract_data_features(data, &ChunkingOptions::default())
+        .and_then(data_id_from_features)
+        .map_err(io_error)
+}
+
+/// Generates the 256-bit digest of the Data-ID, the first four bit planes of
+/// the MinHash of the chunk features, see [`FullDigest`].
+pub fn data_id_full(data_path: &str) -> std::io::Result<FullDigest> {
+    let data = File::open(data_path)?;
+    let sketch = data_sketch(data).map_err(io_error)?;
+    Ok(FullDigest {
+        kind: CodeKind::Data,
+        digest: minhash_planes(&sketch.minhash),
+    })
+}
+
+/// Generates the Data-ID like [`data_id`] and additionally reports how the
+/// data was chunked.
+pub fn data_id_with_report<R: Read>(data: R) -> Result<(String, DataReport), IsccError> {
+    let mut report = DataReport::default();
+    let mut offset = 0;
+    let features = chunk_features(data, &ChunkingOptions::default(), |chunk, hash| {
+        report.chunks.push(ChunkInfo {
+            offset,
+            length: chunk.len(),
+            hash,
+        });
+        offset += chunk.len();
+    })?;
+    Ok((data_id_from_features(features)?.code, report))
+}
+
+/// Generates the Data-ID from any reader and measures the generation, see
+/// [`Metrics`]. The features are the chunks. The time spent in the reader is
+/// reported as [`Stage::Reading`], not as [`Stage::Chunking`].
+pub fn data_id_with_metrics<R: Read>(data: R) -> Result<(CodeResult, Metrics), IsccError> {
+    let mut metrics = Metrics::default();
+    let mut reader = TimedReader::new(data);
+    let mut features = Vec::new();
+    let mut chunking = Duration::default();
+    {
+        let mut chunks = Chunk::new(&mut reader);
+        loop {
+            let start = Instant::now();
+            let chunk = chunks.next();
+            chunking += start.elapsed();
+            let chunk = match chunk {
+                Some(chunk) => chunk,
+                None => break,
+            };
+            features.push(metrics.time(Stage::FeatureHashing, || xxhash32(&chunk)));
+        }
+        if let Some(e) = chunks.take_error() {
+            return Err(e.into());
+        }
+    }
+    if features.is_empty() {
+        return Err(IsccError::EmptyInput);
+    }
+    metrics.input_bytes = reader.bytes;
+    metrics.features = features.len() as u64;
+    metrics.chunks = features.len() as u64;
+    metrics.add(Stage::Reading, reader.elapsed);
+    metrics.add(
+        Stage::Chunking,
+        chunking.checked_sub(reader.elapsed).unwrap_or_default(),
+    );
+    let minhash = metrics.time(Stage::MinHash, || minimum_hash(features));
+    Ok((data_id_from_minhash(&minhash), metrics))
+}
+
+/// Generates the Data-ID from any reader with the given options, see
+/// [`IsccOptions`]. The default options produce the standard code.
+pub fn data_id_with_options<R: Read>(
+    data: R,
+    options: &IsccOptions,
+) -> Result<CodeResult, IsccError> {
+    let features = extract_data_features(data, &ChunkingOptions::from(options))?;
+    let mut result = data_id_from_features(features)?;
+    result.namespaced = options.namespace.is_some();
+    Ok(result)
+}
+
+/// Extracts the features the Data-ID is built from: the content defined
+/// chunks of the data, each hashed with [`xxhash32`], in the order of the
+/// chunks. [`minimum_hash`] over the features is the sketch the Data-ID body
+/// is packed from, so features extracted elsewhere can be sketched and
+/// compared with the codes of this crate.
+///
+/// The features are guaranteed to be stable within an
+/// [`ALGORITHM_VERSION`](crate::version::ALGORITHM_VERSION) of the Data-ID.
+/// Empty data has no features.
+pub fn extract_data_features(
+    data: impl Read,
+    options: &ChunkingOptions,
+) -> Result<Vec<u32>, IsccError> {
+    chunk_features(data, options, |_, _| {})
+}
+
+/// Extracts the features like [`extract_data_features`] and calls `each` with
+/// every chunk and its feature.
+fn chunk_features(
+    data: impl Read,
+    options: &ChunkingOptions,
+    mut each: impl FnMut(&[u8], u32),
+) -> Result<Vec<u32>, IsccError> {
+    let mut chunks = Chunk::new(data);
+    let mut features = Vec::new();
+    for chunk in chunks.by_ref() {
+        let feature = match &options.namespace {
+            None => xxhash32(&chunk),
+            Some(namespace) => xxhash32_namespaced(namespace, &chunk),
+        };
+        each(&chunk, feature);
+        features.push(feature);
+    }
+    if let Some(e) = chunks.take_error() {
+        return Err(e.into());
+    }
+    Ok(features)
+}
+
+fn data_id_from_features(features: Vec<u32>) -> Result<CodeResult, IsccError> {
+    if features.is_empty() {
+        return Err(IsccError::EmptyInput);
+    }
+    Ok(data_id_from_minhash(&minimum_hash(features)))
+}
+
+fn io_error(e: IsccError) -> std::io::Error {
+    match e {
+        IsccError::Io(e) => e,
+        e => std::io::Error::new(std::io::ErrorKind::InvalidInput, e.to_string()),
+    }
+}
+
+pub(crate) fn data_id_from_minhash(minhash: &[u32]) -> CodeResult {
+    CodeResult::new(HEAD_DID, pack_bits(minhash.iter().map(|x| (x & 1) == 1)))
+}
+
+/// Incremental Data-ID generation for data that arrives in pieces. The result
+/// does not depend on how the data is split across `update` calls.
+///
+/// The state can be saved with [`DataHasher::checkpoint`] and restored with
+/// [`DataHasher::resume`] to continue hashing in another process.
+#[derive(Debug, Clone)]
+pub struct DataHasher {
+    chunker: GearChunker,
+    section: Vec<u8>,
+    min_features: Vec<u32>,
+}
+
+const DATA_CHECKPOINT_MAGIC: &[u8; 4] = b"ISDH";
+
+impl DataHasher {
+    pub fn new() -> DataHasher {
+        DataHasher {
+            chunker: GearChunker::new(),
+            section: Vec::new(),
+            min_features: vec![u32::MAX; 64],
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.section.extend(data);
+        // Only cut chunks that can not depend on data that is still to come.
+        while self.section.len() >= self.chunker.max_size() {
+            self.cut();
+        }
+    }
+
+    fn cut(&mut self) {
+        let boundary = self.chunker.chunk_length(&self.section);
+        minimum_hash_update(&mut self.min_features, xxhash32(&self.section[..boundary]));
+        self.section.drain(..boundary);
+    }
+
+    /// Returns the Data-ID, or [`IsccError::EmptyInput`] if no data was
+    /// hashed.
+    pub fn finish(mut self) -> Result<CodeResult, IsccError> {
+        while !self.section.is_empty() {
+            self.cut();
+        }
+        if self.chunker.counter == 0 {
+            return Err(IsccError::EmptyInput);
+        }
+        Ok(data_id_from_minhash(&self.min_features))
+    }
+
+    /// Serializes the state of the hasher. The checkpoint consists of
+    ///
+    /// * the magic `ISDH` and the format version `1` (1 byte),
+    /// * the number of chunks cut so far (`u64`),
+    /// * the 64 running MinHash minimums (`u32` each),
+    /// * the length (`u32`) and the bytes of the data that is not yet chunked,
+    /// * the `xxhash64` of all preceding bytes (`u64`).
+    ///
+    /// All integers are little endian.
+    pub fn checkpoint(&self) -> Vec<u8> {
+        let mut writer = checkpoint::Writer::new(DATA_CHECKPOINT_MAGIC);
+        writer.u64(self.chunker.counter as u64);
+        for &min in self.min_features.iter() {
+            writer.u32(min);
+        }
+        writer.bytes(&self.section);
+        writer.finish()
+    }
+
+    /// Restores a hasher from a [`DataHasher::checkpoint`]. Returns
+    /// [`IsccError::InvalidCheckpoint`] if the checkpoint is corrupted.
+    pub fn resume(bytes: &[u8]) -> Result<DataHasher, IsccError> {
+        let mut reader = checkpoint::Reader::new(bytes, DATA_CHECKPOINT_MAGIC)?;
+        let counter = reader.u64()? as usize;
+        let mut min_features = Vec::with_capacity(64);
+        for _ in 0..64 {
+            min_features.push(reader.u32()?);
+        }
+        let section = reader.bytes()?.to_vec();
+        reader.finish()?;
 
-    let features: Vec<u32> = data_chunks(data).map(|chunk| xxhash32(&chunk)).collect();
+        let chunker = GearChunker { counter };
+        if section.len() >= chunker.max_size() {
+            return Err(checkpoint::invalid("pending data exceeds the chunk size"));
+        }
+        Ok(DataHasher {
+            chunker,
+            section,
+            min_features,
+        })
+    }
+}
+
+impl Default for DataHasher {
+    fn default() -> DataHasher {
+        DataHasher::new()
+    }
+}
+
+/// The full MinHash sketch over the chunk hashes the Data-ID is built from,
+/// see [`data_sketch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataSketch {
+    /// The 64 minimum hashes as returned by [`minimum_hash`].
+    pub minhash: Vec<u32>,
+}
+
+impl DataSketch {
+    /// The Data-ID of the sketched data, identical to [`data_id`].
+    pub fn to_data_code(&self) -> String {
+        data_id_from_minhash(&self.minhash).code
+    }
+
+    /// Estimates the Jaccard similarity of the chunks of two sketched inputs.
+    pub fn similarity(&self, other: &DataSketch) -> f64 {
+        minhash_similarity(&self.minhash, &other.minhash)
+    }
+}
+
+/// Chunks and hashes the data like [`data_id`] and returns the MinHash sketch
+/// before it is truncated to the 64-bit Data-ID body. Returns
+/// [`IsccError::EmptyInput`] if the data is empty.
+pub fn data_sketch(data: impl Read) -> Result<DataSketch, IsccError> {
+    let features = extract_data_features(data, &ChunkingOptions::default())?;
+    if features.is_empty() {
+        return Err(IsccError::EmptyInput);
+    }
+    Ok(DataSketch {
+        minhash: minimum_hash(features),
+    })
+}
+
+/// The similarity of two inputs as estimated by [`data_similarity`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataSimilarity {
+    /// The estimated Jaccard similarity of the chunks of both inputs.
+    pub similarity: f64,
+    pub code_a: String,
+    pub code_b: String,
+}
+
+/// Estimates how similar two inputs are on the byte level. Both inputs are
+/// chunked and hashed like for the Data-ID and the Jaccard similarity of
+/// their chunks is estimated from the full 64-element MinHash sketches with
+/// [`DataSketch::similarity`], i.e. without the truncation to the 64-bit
+/// code.
+///
+/// Returns an error if any of the inputs is empty.
+pub fn data_similarity(a: impl Read, b: impl Read) -> Result<DataSimilarity, IsccError> {
+    let sketch_a = data_sketch(a)?;
+    let sketch_b = data_sketch(b)?;
+    Ok(DataSimilarity {
+        similarity: sketch_a.similarity(&sketch_b),
+        code_a: sketch_a.to_data_code(),
+        code_b: sketch_b.to_data_code(),
+    })
+}
+
+/// A single chunk of the data as used for the Data-ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkInfo {
+    pub offset: usize,
+    pub length: usize,
+    /// The `xxhash32` of the chunk, i.e. the feature used for the MinHash.
+    pub hash: u32,
+}
+
+/// The chunks the Data-ID was computed from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DataReport {
+    pub chunks: Vec<ChunkInfo>,
+}
+
+impl DataReport {
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
 
-    let minhash = minimum_hash(features);
+    pub fn min_size(&self) -> usize {
+        self.chunks.iter().map(|c| c.length).min().unwrap_or(0)
+    }
 
-    let lsb: BitVec = minhash.iter().map(|x| (x & 1) == 1).collect();
+    pub fn max_size(&self) -> usize {
+        self.chunks.iter().map(|c| c.length).max().unwrap_or(0)
+    }
 
-    let lsb_bytes = lsb.to_bytes();
+    pub fn avg_size(&self) -> f64 {
+        if self.chunks.is_empty() {
+            return 0.0;
+        }
+        let total: usize = self.chunks.iter().map(|c| c.length).sum();
+        total as f64 / self.chunks.len() as f64
+    }
+}
 
-    let mut data_id_digest = vec![HEAD_DID];
-    data_id_digest.extend(&lsb_bytes);
+/// The chunks that are only present in one of two [`DataReport`]s. Chunks are
+/// considered equal if their length and hash are equal, regardless of their
+/// offset.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkDiff {
+    pub only_in_a: Vec<ChunkInfo>,
+    pub only_in_b: Vec<ChunkInfo>,
+}
 
-    Ok(encode(&data_id_digest))
+pub fn diff_reports(a: &DataReport, b: &DataReport) -> ChunkDiff {
+    let keys = |report: &DataReport| -> HashSet<(usize, u32)> {
+        report.chunks.iter().map(|c| (c.length, c.hash)).collect()
+    };
+    let (keys_a, keys_b) = (keys(a), keys(b));
+    ChunkDiff {
+        only_in_a: a
+            .chunks
+            .iter()
+            .filter(|c| !keys_b.contains(&(c.length, c.hash)))
+            .cloned()
+            .collect(),
+        only_in_b: b
+            .chunks
+            .iter()
+            .filter(|c| !keys_a.contains(&(c.length, c.hash)))
+            .cloned()
+            .collect(),
+    }
 }
 
-pub fn data_chunks(data: File) -> impl Iterator<Item = Vec<u8>> {
+pub fn data_chunks<R: Read>(data: R) -> impl Iterator<Item = Vec<u8>> {
     Chunk::new(data)
 }
 
@@ -76,73 +443,248 @@ pub fn chunk_length(
     }
     i
 }
-struct Chunk {
-    // TODO: Generalize with Reader trait
-    // TODO: Maybe use BufReader
-    data: File,
+
+/// A content defined chunking backend used by the [`Chunk`] iterator.
+///
+/// The iterator buffers at least `max_size()` bytes (or whatever is left of
+/// the input) and asks the chunker for the length of the next chunk at the
+/// start of that buffer.
+pub trait Chunker {
+    /// The maximum length of the next chunk.
+    fn max_size(&self) -> usize;
+
+    /// Returns the length of the next chunk at the start of `data`. `data` is
+    /// never empty and holds at least `max_size()` bytes unless the end of the
+    /// input is reached. The returned length must be in `1..=data.len()`.
+    fn chunk_length(&mut self, data: &[u8]) -> usize;
+}
+
+/// The gear based chunker used for the Data-ID. The first 100 chunks are cut
+/// with small chunk sizes, all following chunks with bigger ones.
+#[derive(Debug, Default, Clone)]
+pub struct GearChunker {
     counter: usize,
-    section: Vec<u8>,
 }
 
-impl Chunk {
-    fn new(mut data: File) -> Chunk {
-        let mut buffer = [0; GEAR1_MAX];
-        let n = data.read(&mut buffer).unwrap();
-        let mut section: Vec<u8> = Vec::new();
-        section.extend(&buffer[..n]);
-        Chunk {
-            data,
-            counter: 0,
-            section,
-        }
+impl GearChunker {
+    pub fn new() -> GearChunker {
+        GearChunker::default()
     }
 }
 
-impl Iterator for Chunk {
-    type Item = Vec<u8>;
-    fn next(&mut self) -> Option<Vec<u8>> {
-        let mut buffer = [0; GEAR2_MAX];
-        let boundary: usize;
-
-        let counter = self.counter;
-        let mut section = self.section.clone();
-        let mut data = &self.data;
-        if counter < 100 {
-            if section.len() < GEAR1_MAX {
-                let n = data.read(&mut buffer).unwrap();
-                section.extend(&buffer[..n]);
-            }
-            if section.is_empty() {
-                return None;
-            }
-            boundary = chunk_length(
-                &section,
+impl Chunker for GearChunker {
+    fn max_size(&self) -> usize {
+        if self.counter < 100 {
+            GEAR1_MAX
+        } else {
+            GEAR2_MAX
+        }
+    }
+
+    fn chunk_length(&mut self, data: &[u8]) -> usize {
+        let boundary = if self.counter < 100 {
+            chunk_length(
+                data,
                 GEAR1_NORM,
                 GEAR1_MIN,
                 GEAR1_MAX,
                 GEAR1_MASK1,
                 GEAR1_MASK2,
-            );
+            )
         } else {
-            if section.len() < GEAR2_MAX {
-                let n = data.read(&mut buffer).unwrap();
-                section.extend(&buffer[..n]);
-            }
-            if section.is_empty() {
-                return None;
-            }
-            boundary = chunk_length(
-                &section,
+            chunk_length(
+                data,
                 GEAR2_NORM,
                 GEAR2_MIN,
                 GEAR2_MAX,
                 GEAR2_MASK1,
                 GEAR2_MASK2,
-            );
-        }
-        self.section = section[boundary..].to_vec();
+            )
+        };
         self.counter += 1;
-        Some(section[..boundary].to_vec())
+        boundary
+    }
+}
+
+/// A chunker based on classic Rabin fingerprints over a sliding window of
+/// bytes. It is not used for the Data-ID but allows to reproduce the chunking
+/// of systems that use Rabin fingerprinting.
+///
+/// The fingerprint of the window is computed modulo an irreducible polynomial
+/// over GF(2). A chunk ends after the first byte (past `min_size`) for which
+/// `fingerprint & mask == 0`, or after `max_size` bytes. The window starts
+/// empty at the beginning of every chunk.
+#[derive(Debug, Clone)]
+pub struct RabinChunker {
+    window_size: usize,
+    mask: u64,
+    min_size: usize,
+    max_size: usize,
+    degree: u32,
+    mod_table: [u64; 256],
+    out_table: [u64; 256],
+}
+
+impl RabinChunker {
+    /// Creates a new Rabin chunker.
+    ///
+    /// * `polynomial` - An irreducible polynomial over GF(2) of degree 8 to 56,
+    ///   bit `i` being the coefficient of `x^i`.
+    /// * `window_size` - The number of bytes the fingerprint is computed over.
+    /// * `mask` - Bit mask applied to the fingerprint to find chunk boundaries.
+    /// * `min_size` / `max_size` - Bounds for the length of a chunk.
+    pub fn new(
+        polynomial: u64,
+        window_size: usize,
+        mask: u64,
+        min_size: usize,
+        max_size: usize,
+    ) -> RabinChunker {
+        let degree = 63 - polynomial.leading_zeros().min(63);
+        assert!(
+            (8..=56).contains(&degree),
+            "Polynomial degree must be between 8 and 56. Not {}",
+            degree
+        );
+        assert!(window_size >= 1, "Window size must be 1 or bigger.");
+        assert!(
+            min_size >= 1 && min_size <= max_size,
+            "Chunk sizes must satisfy 1 <= min_size <= max_size."
+        );
+
+        let mut mod_table = [0; 256];
+        for (top, entry) in mod_table.iter_mut().enumerate() {
+            *entry = poly_mod((top as u64) << degree, polynomial);
+        }
+
+        let mut chunker = RabinChunker {
+            window_size,
+            mask,
+            min_size,
+            max_size,
+            degree,
+            mod_table,
+            out_table: [0; 256],
+        };
+        // The contribution of a byte when it is the oldest one in the window.
+        for byte in 0..256 {
+            let mut fingerprint = chunker.append(0, byte as u8);
+            for _ in 1..window_size {
+                fingerprint = chunker.append(fingerprint, 0);
+            }
+            chunker.out_table[byte] = fingerprint;
+        }
+        chunker
+    }
+
+    fn append(&self, fingerprint: u64, byte: u8) -> u64 {
+        let shifted = (fingerprint << 8) | u64::from(byte);
+        let top = (shifted >> self.degree) as usize;
+        (shifted & ((1 << self.degree) - 1)) ^ self.mod_table[top]
+    }
+}
+
+impl Default for RabinChunker {
+    /// A 48-byte window, an average chunk size of 8 KiB and chunks between
+    /// 2 KiB and 64 KiB.
+    fn default() -> RabinChunker {
+        RabinChunker::new(0x003D_A335_8B4D_C173, 48, (1 << 13) - 1, 2048, 65536)
+    }
+}
+
+impl Chunker for RabinChunker {
+    fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    fn chunk_length(&mut self, data: &[u8]) -> usize {
+        let barrier = self.max_size.min(data.len());
+        let mut fingerprint: u64 = 0;
+        for i in 0..barrier {
+            if i >= self.window_size {
+                fingerprint ^= self.out_table[data[i - self.window_size] as usize];
+            }
+            fingerprint = self.append(fingerprint, data[i]);
+            if i + 1 >= self.min_size && (fingerprint & self.mask) == 0 {
+                return i + 1;
+            }
+        }
+        barrier
+    }
+}
+
+/// Reduces `value` modulo `polynomial` over GF(2).
+fn poly_mod(mut value: u64, polynomial: u64) -> u64 {
+    let degree = 63 - polynomial.leading_zeros();
+    while value != 0 && 63 - value.leading_zeros() >= degree {
+        value ^= polynomial << (63 - value.leading_zeros() - degree);
+    }
+    value
+}
+
+/// Iterator over the content defined chunks of a reader. Uses the
+/// [`GearChunker`] of the Data-ID unless created with another [`Chunker`].
+///
+/// The iteration ends early if reading fails, the error can be retrieved with
+/// [`Chunk::take_error`].
+pub struct Chunk<R: Read, C: Chunker = GearChunker> {
+    data: R,
+    chunker: C,
+    section: Vec<u8>,
+    eof: bool,
+    error: Option<std::io::Error>,
+}
+
+impl<R: Read> Chunk<R> {
+    pub fn new(data: R) -> Chunk<R> {
+        Chunk::with_chunker(data, GearChunker::new())
+    }
+}
+
+impl<R: Read, C: Chunker> Chunk<R, C> {
+    pub fn with_chunker(data: R, chunker: C) -> Chunk<R, C> {
+        Chunk {
+            data,
+            chunker,
+            section: Vec::new(),
+            eof: false,
+            error: None,
+        }
+    }
+
+    /// Returns the error that ended the iteration, if any.
+    pub fn take_error(&mut self) -> Option<std::io::Error> {
+        self.error.take()
+    }
+
+    /// Reads from the input until the section holds at least `size` bytes or
+    /// the input is exhausted.
+    fn fill(&mut self, size: usize) {
+        let mut buffer = [0; GEAR2_MAX];
+        while !self.eof && self.section.len() < size {
+            match self.data.read(&mut buffer) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.section.extend(&buffer[..n]),
+                Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.error = Some(e);
+                    self.eof = true;
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read, C: Chunker> Iterator for Chunk<R, C> {
+    type Item = Vec<u8>;
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let max_size = self.chunker.max_size();
+        self.fill(max_size);
+        if self.section.is_empty() || self.error.is_some() {
+            return None;
+        }
+        let boundary = self.chunker.chunk_length(&self.section);
+        Some(self.section.drain(..boundary).collect())
     }
 }
 
@@ -160,4 +702,228 @@ mod tests {
         assert_eq!(chunks1[0].len(), 38);
         assert_eq!(chunks1.last().unwrap().len(), 2840);
     }
+
+    #[test]
+    fn test_data_id_with_metrics() {
+        let path = "tests/test_data/lenna.jpg";
+        let (result, metrics) = data_id_with_metrics(File::open(path).unwrap()).unwrap();
+        assert_eq!(result, data_id_result(path).unwrap());
+        let chunk_lengths: usize = data_chunks(File::open(path).unwrap())
+            .map(|c| c.len())
+            .sum();
+        assert_eq!(metrics.input_bytes, chunk_lengths as u64);
+        assert_eq!(metrics.input_bytes, std::fs::metadata(path).unwrap().len());
+        assert_eq!((metrics.chunks, metrics.features), (112, 112));
+        assert_eq!(metrics.input_chars, 0);
+        assert!(metrics.duration(Stage::MinHash) > Duration::default());
+    }
+
+    /// Pseudo random test data from a xorshift generator.
+    fn random_bytes(n: usize, mut seed: u64) -> Vec<u8> {
+        (0..n)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                (seed >> 32) as u8
+            })
+            .collect()
+    }
+
+    /// Naive Rabin fingerprint of `bytes`, reducing bit by bit.
+    fn reference_fingerprint(bytes: &[u8], polynomial: u64) -> u64 {
+        let degree = 63 - polynomial.leading_zeros();
+        let mut fingerprint: u64 = 0;
+        for byte in bytes {
+            for bit in (0..8).rev() {
+                fingerprint = (fingerprint << 1) | u64::from((byte >> bit) & 1);
+                if (fingerprint >> degree) & 1 == 1 {
+                    fingerprint ^= polynomial;
+                }
+            }
+        }
+        fingerprint
+    }
+
+    /// Reference chunking computing the fingerprint of every window from
+    /// scratch.
+    fn reference_rabin_lengths(
+        data: &[u8],
+        polynomial: u64,
+        window: usize,
+        mask: u64,
+        min_size: usize,
+        max_size: usize,
+    ) -> Vec<usize> {
+        let mut lengths = Vec::new();
+        let mut start = 0;
+        while start < data.len() {
+            let chunk = &data[start..data.len().min(start + max_size)];
+            let mut length = chunk.len();
+            for i in min_size - 1..chunk.len() {
+                let window_start = (i + 1).saturating_sub(window);
+                let fingerprint = reference_fingerprint(&chunk[window_start..=i], polynomial);
+                if fingerprint & mask == 0 {
+                    length = i + 1;
+                    break;
+                }
+            }
+            lengths.push(length);
+            start += length;
+        }
+        lengths
+    }
+
+    #[test]
+    fn test_rabin_chunker_reference() {
+        let data = random_bytes(200_000, 42);
+        for &(polynomial, window, mask, min_size, max_size) in [
+            (0x003D_A335_8B4D_C173, 48, (1 << 10) - 1, 256, 8192),
+            (0x003D_A335_8B4D_C173, 16, (1 << 8) - 1, 64, 1024),
+            (0x11B, 4, (1 << 4) - 1, 1, 256),
+        ]
+        .iter()
+        {
+            let chunker = RabinChunker::new(polynomial, window, mask, min_size, max_size);
+            let lengths: Vec<usize> = Chunk::with_chunker(&data[..], chunker)
+                .map(|chunk| chunk.len())
+                .collect();
+            let expected =
+                reference_rabin_lengths(&data, polynomial, window, mask, min_size, max_size);
+            assert!(expected.len() > 10);
+            assert_eq!(lengths, expected);
+        }
+    }
+
+    #[test]
+    fn test_data_id_with_report() {
+        let path = "tests/test_data/lenna.jpg";
+        let data = std::fs::read(path).unwrap();
+        let (code, report) = data_id_with_report(&data[..]).unwrap();
+        assert_eq!(code, data_id(path).unwrap());
+        assert_eq!(report.chunk_count(), 112);
+        assert_eq!(report.chunks[0].length, 38);
+        assert!(report.min_size() <= 38);
+        assert_eq!(report.chunks.last().unwrap().length, 2840);
+        let total: usize = report.chunks.iter().map(|c| c.length).sum();
+        assert_eq!(total, data.len());
+        assert!((report.avg_size() - total as f64 / 112.0).abs() < 1e-9);
+        assert!(report.max_size() <= GEAR2_MAX);
+
+        let mut appended = data.clone();
+        appended.extend(random_bytes(5000, 3));
+        let (_, report_appended) = data_id_with_report(&appended[..]).unwrap();
+        let diff = diff_reports(&report, &report_appended);
+        assert_eq!(diff.only_in_a, vec![*report.chunks.last().unwrap()]);
+        assert!(!diff.only_in_b.is_empty());
+        let first_changed = diff.only_in_b[0].offset;
+        assert_eq!(first_changed, report.chunks.last().unwrap().offset);
+        assert!(diff.only_in_b.iter().all(|c| c.offset >= first_changed));
+        assert_eq!(diff_reports(&report, &report), ChunkDiff::default());
+    }
+
+    #[test]
+    fn test_data_sketch() {
+        for &path in [
+            "tests/test_data/lenna.jpg",
+            "tests/test_data/cat.jpg",
+            "tests/test_data/cat.png",
+            "tests/test_data/cat.gif",
+            "tests/test_data/test-70k.txt",
+        ]
+        .iter()
+        {
+            let sketch = data_sketch(File::open(path).unwrap()).unwrap();
+            assert_eq!(sketch.minhash.len(), 64);
+            assert_eq!(sketch.to_data_code(), data_id(path).unwrap(), "{}", path);
+        }
+
+        let data = std::fs::read("tests/test_data/lenna.jpg").unwrap();
+        let mut edited = data.clone();
+        edited[data.len() / 2] ^= 0xFF;
+        let sketch = data_sketch(&data[..]).unwrap();
+        let similarity =
+            minhash_similarity(&sketch.minhash, &data_sketch(&edited[..]).unwrap().minhash);
+        assert!(similarity > 0.8, "{}", similarity);
+    }
+
+    #[test]
+    fn test_data_hasher() {
+        let data = random_bytes(400_000, 5);
+        let expected = data_id_with_report(&data[..]).unwrap().0;
+        for &piece in [1, 1000, 65536, 400_000].iter() {
+            let mut hasher = DataHasher::new();
+            for chunk in data.chunks(piece) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finish().unwrap().code, expected);
+        }
+        assert!(DataHasher::new().finish().is_err());
+    }
+
+    #[test]
+    fn test_data_hasher_checkpoint() {
+        let data = random_bytes(400_000, 9);
+        let expected = data_id_with_report(&data[..]).unwrap().0;
+        let offsets = random_bytes(8, 1);
+        let mut offsets: Vec<usize> = offsets
+            .iter()
+            .map(|&b| b as usize * data.len() / 256)
+            .collect();
+        offsets.sort_unstable();
+        offsets.push(data.len());
+
+        let mut hasher = DataHasher::new();
+        let mut start = 0;
+        for &end in offsets.iter() {
+            hasher.update(&data[start..end]);
+            hasher = DataHasher::resume(&hasher.checkpoint()).unwrap();
+            start = end;
+        }
+        assert_eq!(hasher.finish().unwrap().code, expected);
+
+        let mut hasher = DataHasher::new();
+        hasher.update(&data[..123_456]);
+        let checkpoint = hasher.checkpoint();
+        let positions = (0..300).chain((300..checkpoint.len()).step_by(997));
+        for i in positions {
+            let mut corrupted = checkpoint.clone();
+            corrupted[i] ^= 0x04;
+            assert!(DataHasher::resume(&corrupted).is_err(), "byte {}", i);
+        }
+        assert!(DataHasher::resume(&checkpoint[..checkpoint.len() - 1]).is_err());
+        assert!(DataHasher::resume(&[]).is_err());
+    }
+
+    #[test]
+    fn test_data_similarity() {
+        let data = std::fs::read("tests/test_data/lenna.jpg").unwrap();
+        let same = data_similarity(&data[..], &data[..]).unwrap();
+        assert!((same.similarity - 1.0).abs() < 1e-9);
+        assert_eq!(same.code_a, data_id("tests/test_data/lenna.jpg").unwrap());
+        assert_eq!(same.code_a, same.code_b);
+
+        let mut appended = data.clone();
+        appended.extend(random_bytes(5000, 3));
+        let near = data_similarity(&data[..], &appended[..]).unwrap();
+        assert!(near.similarity > 0.8, "{}", near.similarity);
+
+        let unrelated = random_bytes(data.len(), 11);
+        let far = data_similarity(&data[..], &unrelated[..]).unwrap();
+        assert!(far.similarity < 0.1, "{}", far.similarity);
+        assert_ne!(far.code_a, far.code_b);
+
+        assert!(data_similarity(&data[..], &b""[..]).is_err());
+    }
+
+    #[test]
+    fn test_chunks_reassemble() {
+        let data = random_bytes(300_000, 7);
+        let gear: Vec<Vec<u8>> = Chunk::new(&data[..]).collect();
+        assert_eq!(gear.concat(), data);
+        let rabin: Vec<Vec<u8>> = Chunk::with_chunker(&data[..], RabinChunker::default()).collect();
+        assert_eq!(rabin.concat(), data);
+        assert!(rabin.len() > 1);
+        assert_eq!(Chunk::new(&b""[..]).count(), 0);
+    }
 }
@@ -0,0 +1,172 @@
+//! Error type
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::version::AlgorithmVersion;
+
+/// Errors returned by the fallible functions of this crate.
+#[derive(Debug)]
+pub enum IsccError {
+    /// Reading the input failed.
+    Io(io::Error),
+    /// Decoding or encoding an image failed.
+    #[cfg(feature = "image")]
+    Image(image::ImageError),
+    /// A function argument is out of its valid range.
+    InvalidArgument(String),
+    /// A varint is truncated or does not fit into 64 bits.
+    InvalidVarint,
+    /// The declared length of some data does not match its actual length.
+    LengthMismatch { expected: usize, actual: usize },
+    /// The metadata contains no title.
+    MissingTitle,
+    /// The document format (file extension) is not supported.
+    UnsupportedFormat(String),
+    /// The document is malformed.
+    InvalidDocument(String),
+    /// The checksum of a checked code does not match its digest.
+    ChecksumMismatch { expected: u16, actual: u16 },
+    /// A hasher checkpoint is corrupted or of an unsupported version.
+    InvalidCheckpoint(String),
+    /// The header byte of a code is not a component header of the ISCC
+    /// specification.
+    InvalidHeader(u8),
+    /// A binary code is shorter than its header and 8-byte body.
+    TruncatedCode { expected: usize, actual: usize },
+    /// A binary code is followed by this number of unexpected bytes.
+    TrailingBytes(usize),
+    /// A serialized MinHash sketch is malformed or of an unsupported version.
+    InvalidSketch(String),
+    /// A line of a fixture file is malformed.
+    InvalidFixture { line: usize, reason: String },
+    /// The input is empty and has no code, e.g. the Data-ID of empty data.
+    EmptyInput,
+    /// Two Bloom filters differ in their size or number of hash functions.
+    FilterMismatch(String),
+    /// A serialized Bloom filter is malformed or of an unsupported version.
+    InvalidFilter(String),
+    /// A bundle of codes has an invalid magic byte or entry.
+    InvalidBundle(String),
+    /// A bundle of codes is of an unsupported format version.
+    UnsupportedBundleVersion(u8),
+    /// A bundle of codes ends within the entry with this index.
+    TruncatedEntry { index: usize },
+    /// Two codes were generated by algorithm versions that construct their
+    /// component differently and can not be compared.
+    VersionMismatch {
+        expected: AlgorithmVersion,
+        actual: AlgorithmVersion,
+    },
+    /// An invalid Base58-ISCC code is entirely in upper or lower case and
+    /// valid with the case of its letters swapped, most likely a code whose
+    /// case was changed. Base58-ISCC is case sensitive, `expected` is the
+    /// code in the case it decodes in.
+    NonCanonicalCase { code: String, expected: String },
+    /// More features were added than the limit of the hasher, whose counters
+    /// could otherwise overflow.
+    TooManyFeatures { limit: u64 },
+    /// An animated image is in a format whose frames can not be decoded, e.g.
+    /// animated WebP or APNG.
+    UnsupportedAnimation(String),
+    /// A panic of this crate or a dependency was caught, e.g. of a decoder on
+    /// a broken file.
+    Internal(String),
+}
+
+impl fmt::Display for IsccError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IsccError::Io(e) => write!(f, "IO error: {}", e),
+            #[cfg(feature = "image")]
+            IsccError::Image(e) => write!(f, "Image error: {}", e),
+            IsccError::InvalidArgument(reason) => write!(f, "Invalid argument: {}", reason),
+            IsccError::InvalidVarint => write!(f, "Invalid or truncated varint"),
+            IsccError::LengthMismatch { expected, actual } => write!(
+                f,
+                "Length mismatch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            IsccError::MissingTitle => write!(f, "Metadata contains no title"),
+            IsccError::UnsupportedFormat(format) => {
+                write!(f, "Unsupported document format '{}'", format)
+            }
+            IsccError::InvalidDocument(reason) => write!(f, "Invalid document: {}", reason),
+            IsccError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: expected {:04x}, got {:04x}",
+                expected, actual
+            ),
+            IsccError::InvalidCheckpoint(reason) => write!(f, "Invalid checkpoint: {}", reason),
+            IsccError::InvalidHeader(header) => {
+                write!(f, "Invalid component header {:#04x}", header)
+            }
+            IsccError::TruncatedCode { expected, actual } => write!(
+                f,
+                "Truncated code: expected at least {} bytes, got {}",
+                expected, actual
+            ),
+            IsccError::TrailingBytes(n) => write!(f, "Code is followed by {} trailing bytes", n),
+            IsccError::InvalidSketch(reason) => write!(f, "Invalid sketch: {}", reason),
+            IsccError::InvalidFixture { line, reason } => {
+                write!(f, "Invalid fixture on line {}: {}", line, reason)
+            }
+            IsccError::EmptyInput => write!(f, "Input is empty"),
+            IsccError::FilterMismatch(reason) => write!(f, "Filter mismatch: {}", reason),
+            IsccError::InvalidFilter(reason) => write!(f, "Invalid filter: {}", reason),
+            IsccError::InvalidBundle(reason) => write!(f, "Invalid bundle: {}", reason),
+            IsccError::UnsupportedBundleVersion(version) => {
+                write!(f, "Unsupported bundle version {}", version)
+            }
+            IsccError::TruncatedEntry { index } => {
+                write!(f, "Bundle is truncated in entry {}", index)
+            }
+            IsccError::VersionMismatch { expected, actual } => write!(
+                f,
+                "Version mismatch: can not compare codes of algorithm {} with {}",
+                expected, actual
+            ),
+            IsccError::NonCanonicalCase { code, expected } => write!(
+                f,
+                "'{}' is not in canonical case: Base58-ISCC codes are case sensitive, \
+                 expected '{}'",
+                code, expected
+            ),
+            IsccError::TooManyFeatures { limit } => {
+                write!(f, "Too many features: at most {} are supported", limit)
+            }
+            IsccError::UnsupportedAnimation(format) => {
+                write!(
+                    f,
+                    "Unsupported animation: can not decode the frames of {}",
+                    format
+                )
+            }
+            IsccError::Internal(message) => write!(f, "Internal error: {}", message),
+        }
+    }
+}
+
+impl Error for IsccError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            IsccError::Io(e) => Some(e),
+            #[cfg(feature = "image")]
+            IsccError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for IsccError {
+    fn from(e: io::Error) -> IsccError {
+        IsccError::Io(e)
+    }
+}
+
+#[cfg(feature = "image")]
+impl From<image::ImageError> for IsccError {
+    fn from(e: image::ImageError) -> IsccError {
+        IsccError::Image(e)
+    }
+}
@@ -0,0 +1,315 @@
+//! Document Text Extraction
+//!
+//! Extracts the plain text of common document formats for the
+//! Content-ID-Text:
+//!
+//! * **EPUB** - The text of the spine items in reading order, one item per
+//!   line. Only the `body` of each XHTML item is used.
+//! * **DOCX** - The text runs of `word/document.xml`, one paragraph per line.
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use zip::ZipArchive;
+
+use crate::cid_text::content_id_text;
+use crate::error::IsccError;
+use crate::html::html_to_text;
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+/// The Content-ID-Text of a document with some information about the
+/// extracted text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextCodeResult {
+    pub code: String,
+    /// The number of characters of the extracted text.
+    pub characters: usize,
+    /// The title declared in the package metadata of the document.
+    pub title: Option<String>,
+    /// The version of the algorithm that generated the code.
+    pub version: AlgorithmVersion,
+}
+
+/// Generates the Content-ID-Text from the text of an EPUB or DOCX file. The
+/// format is selected by the file extension.
+pub fn content_id_text_from_document(path: &Path) -> Result<TextCodeResult, IsccError> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let extract = match extension.as_ref() {
+        "epub" => extract_epub,
+        "docx" => extract_docx,
+        _ => return Err(IsccError::UnsupportedFormat(extension)),
+    };
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(invalid_document)?;
+    let (text, title) = extract(&mut archive)?;
+    Ok(TextCodeResult {
+        code: content_id_text(&text, false),
+        characters: text.chars().count(),
+        title,
+        version: ALGORITHM_VERSION,
+    })
+}
+
+fn extract_epub<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<(String, Option<String>), IsccError> {
+    let container = read_entry(archive, "META-INF/container.xml")?;
+    let opf_path = tags(&container)
+        .find(|tag| tag.name == "rootfile" && !tag.end)
+        .and_then(|tag| tag.attr("full-path"))
+        .ok_or_else(|| missing("rootfile in META-INF/container.xml"))?;
+    let opf = read_entry(archive, &opf_path)?;
+    let opf_dir = match opf_path.rfind('/') {
+        Some(pos) => &opf_path[..=pos],
+        None => "",
+    };
+
+    let manifest: Vec<(String, String)> = tags(&opf)
+        .filter(|tag| tag.name == "item" && !tag.end)
+        .filter_map(|tag| Some((tag.attr("id")?, tag.attr("href")?)))
+        .collect();
+    let spine: Vec<String> = tags(&opf)
+        .filter(|tag| tag.name == "itemref" && !tag.end)
+        .filter_map(|tag| tag.attr("idref"))
+        .collect();
+
+    let mut texts = Vec::new();
+    for idref in spine {
+        let href = manifest
+            .iter()
+            .find(|(id, _)| *id == idref)
+            .map(|(_, href)| href)
+            .ok_or_else(|| missing(&format!("manifest item {}", idref)))?;
+        let xhtml = read_entry(archive, &resolve(opf_dir, href))?;
+        let body = match xhtml.find("<body") {
+            Some(pos) => &xhtml[pos..],
+            None => &xhtml,
+        };
+        texts.push(html_to_text(body));
+    }
+    Ok((texts.join("\n"), element_text(&opf, "title")))
+}
+
+fn extract_docx<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> Result<(String, Option<String>), IsccError> {
+    let document = read_entry(archive, "word/document.xml")?;
+    let mut paragraphs = Vec::new();
+    let mut paragraph = String::new();
+    for tag in tags(&document) {
+        match (tag.name, tag.end) {
+            ("t", false) if !tag.empty => {
+                let text = &document[tag.offset..];
+                let end = text.find('<').unwrap_or(text.len());
+                paragraph.push_str(&html_to_text(&text[..end]));
+            }
+            ("tab", false) => paragraph.push('\t'),
+            ("br", false) | ("cr", false) => paragraph.push('\n'),
+            ("p", true) => paragraphs.push(std::mem::take(&mut paragraph)),
+            ("p", false) if tag.empty => paragraphs.push(String::new()),
+            _ => {}
+        }
+    }
+    let title = match read_entry(archive, "docProps/core.xml") {
+        Ok(core) => element_text(&core, "title"),
+        Err(_) => None,
+    };
+    Ok((paragraphs.join("\n"), title))
+}
+
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<String, IsccError> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| missing(&format!("entry {}", name)))?;
+    let mut content = String::new();
+    entry
+        .read_to_string(&mut content)
+        .map_err(|_| IsccError::InvalidDocument(format!("entry {} is not valid UTF-8", name)))?;
+    Ok(content)
+}
+
+fn invalid_document(e: zip::result::ZipError) -> IsccError {
+    IsccError::InvalidDocument(e.to_string())
+}
+
+fn missing(what: &str) -> IsccError {
+    IsccError::InvalidDocument(format!("missing {}", what))
+}
+
+/// Resolves an href relative to the directory `base` of an archive entry.
+fn resolve(base: &str, href: &str) -> String {
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for part in href.split('/') {
+        match part {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// The trimmed text content of the first element with the given local name.
+fn element_text(xml: &str, name: &str) -> Option<String> {
+    let start = tags(xml).find(|tag| tag.name == name && !tag.end && !tag.empty)?;
+    let text = &xml[start.offset..];
+    let end = text.find('<').unwrap_or(text.len());
+    let title = html_to_text(&text[..end]).trim().to_string();
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+/// A start, end or empty-element tag of an XML document.
+struct Tag<'a> {
+    /// The local name without namespace prefix.
+    name: &'a str,
+    /// The full tag including the attributes.
+    raw: &'a str,
+    end: bool,
+    empty: bool,
+    /// The offset just after the tag.
+    offset: usize,
+}
+
+impl<'a> Tag<'a> {
+    /// Returns the value of the attribute with the given (qualified) name.
+    fn attr(&self, name: &str) -> Option<String> {
+        let mut rest = self.raw;
+        while let Some(pos) = rest.find(name) {
+            let before = rest[..pos].chars().last();
+            let after = rest[pos + name.len()..].trim_start();
+            rest = &rest[pos + name.len()..];
+            if before.is_some_and(|c| !c.is_whitespace()) || !after.starts_with('=') {
+                continue;
+            }
+            let value = after[1..].trim_start();
+            let quote = value.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                return None;
+            }
+            let end = value[1..].find(quote)?;
+            return Some(html_to_text(&value[1..=end]));
+        }
+        None
+    }
+}
+
+/// Iterates over the tags of an XML document, skipping comments, processing
+/// instructions, doctypes and CDATA sections. The iteration ends at a tag
+/// without terminator.
+fn tags(xml: &str) -> impl Iterator<Item = Tag<'_>> {
+    let mut pos = 0;
+    std::iter::from_fn(move || loop {
+        let start = pos + xml[pos..].find('<')?;
+        let rest = &xml[start..];
+        let terminator = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<![CDATA[") {
+            "]]>"
+        } else {
+            ">"
+        };
+        // A truncated document ends within its last tag.
+        let len = rest.find(terminator)? + terminator.len();
+        pos = start + len;
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            continue;
+        }
+        let raw = &rest[1..len - 1];
+        let end = raw.starts_with('/');
+        let name = raw
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or("");
+        let name = name.rsplit(':').next().unwrap_or(name);
+        return Some(Tag {
+            name,
+            raw,
+            end,
+            empty: raw.ends_with('/'),
+            offset: pos,
+        });
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_id_text_from_epub() {
+        let result =
+            content_id_text_from_document(Path::new("tests/test_data/sample.epub")).unwrap();
+        assert_eq!(result.code, "CTANRHVkroMwA");
+        assert_eq!(result.characters, 194);
+        assert_eq!(result.title, Some("The Fox & the Dog".to_string()));
+        // Spine order, not manifest order
+        let text = "Chapter One The quick brown fox jumps over the lazy dog. It was a bright \
+                    cold day in April, and the clocks were striking thirteen. Chapter Two The \
+                    dog did not mind — it was asleep.";
+        assert_eq!(result.code, content_id_text(text, false));
+    }
+
+    #[test]
+    fn test_content_id_text_from_docx() {
+        let result =
+            content_id_text_from_document(Path::new("tests/test_data/sample.docx")).unwrap();
+        assert_eq!(result.code, "CTeGD54U4QGUL");
+        assert_eq!(result.characters, 78);
+        assert_eq!(result.title, Some("Quarterly Report".to_string()));
+        let text =
+            "Quarterly Report\nRevenue grew by twelve percent & costs fell.\tOutlook:\nstable\n";
+        assert_eq!(result.code, content_id_text(text, false));
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        match content_id_text_from_document(Path::new("tests/test_data/cat.jpg")) {
+            Err(IsccError::UnsupportedFormat(ref format)) if format == "jpg" => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("OEBPS/", "text/ch1.xhtml"), "OEBPS/text/ch1.xhtml");
+        assert_eq!(resolve("OEBPS/text/", "../ch1.xhtml"), "OEBPS/ch1.xhtml");
+        assert_eq!(resolve("", "./ch1.xhtml"), "ch1.xhtml");
+    }
+
+    #[test]
+    fn test_tags() {
+        let xml = "<?xml version=\"1.0\"?><!-- <x/> --><a:b id='1' xml:id=\"2\">t</a:b><c/>";
+        let tags: Vec<Tag> = tags(xml).collect();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(
+            (tags[0].name, tags[0].end, tags[0].empty),
+            ("b", false, false)
+        );
+        assert_eq!(tags[0].attr("id"), Some("1".to_string()));
+        assert_eq!(tags[0].attr("xml:id"), Some("2".to_string()));
+        assert_eq!(&xml[tags[0].offset..tags[0].offset + 1], "t");
+        assert_eq!((tags[1].name, tags[1].end), ("b", true));
+        assert_eq!((tags[2].name, tags[2].empty), ("c", true));
+    }
+
+    #[test]
+    fn test_tags_truncated() {
+        for &xml in ["<a>text<", "<a>text<\u{e9}", "<a>text<b", "<a><!-- open"].iter() {
+            let names: Vec<&str> = tags(xml).map(|tag| tag.name).collect();
+            assert_eq!(names, vec!["a"], "{:?}", xml);
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! File ISCCs
+//!
+//! The components of a file in one call. [`iscc_best_effort`] generates every
+//! component it can and reports the errors of the others, e.g. the Data-ID
+//! and Instance-ID of a media file whose content can not be decoded.
+use std::any::Any;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+#[cfg(feature = "image")]
+use crate::cid_image::content_id_image;
+use crate::cid_text::content_id_text;
+use crate::code::{Code, Iscc};
+use crate::did::data_id;
+use crate::error::IsccError;
+#[cfg(feature = "extract")]
+use crate::extract::content_id_text_from_document;
+use crate::iid::instance_id;
+
+/// The components of a file, see [`iscc_best_effort`].
+#[derive(Debug)]
+pub struct BestEffortResult {
+    /// The Content-ID, `None` if the format of the file has none.
+    pub content: Option<Result<Code, IsccError>>,
+    pub data: Result<Code, IsccError>,
+    pub instance: Result<Code, IsccError>,
+    /// The ISCC of the generated components, `None` unless the Data-ID and
+    /// the Instance-ID were generated.
+    pub iscc: Option<Iscc>,
+    /// `true` if every attempted component was generated.
+    pub complete: bool,
+}
+
+/// Generates the Content-ID, Data-ID and Instance-ID of the file at `path`.
+/// Every component is generated on its own, an error or a panic while
+/// generating one of them is reported in its slot of the result and does not
+/// affect the others. The ISCC is built from the generated components if at
+/// least the Data-ID and the Instance-ID were generated.
+///
+/// The Content-ID is selected by the file extension: `txt` files are read as
+/// UTF-8 text, with the `image` feature `jpg`, `jpeg`, `png`, `gif` and `bmp`
+/// files are images and with the `extract` feature `epub` and `docx` files
+/// are documents. Other files have no Content-ID.
+pub fn iscc_best_effort(path: &Path) -> BestEffortResult {
+    let content = attempt(|| content_code(path)).transpose();
+    let data = attempt(|| Ok(data_id(utf8_path(path)?)?));
+    let instance = attempt(|| Ok(instance_id(utf8_path(path)?)?.0));
+    let content = content.map(|code| code.and_then(|code| code.parse::<Code>()));
+    let data = data.and_then(|code| code.parse::<Code>());
+    let instance = instance.and_then(|code| code.parse::<Code>());
+
+    let complete = !matches!(content, Some(Err(_))) && data.is_ok() && instance.is_ok();
+    let iscc = match (&data, &instance) {
+        (Ok(data), Ok(instance)) => {
+            let mut components: Vec<Code> = content.iter().flatten().cloned().collect();
+            components.push(data.clone());
+            components.push(instance.clone());
+            Some(Iscc { components })
+        }
+        _ => None,
+    };
+    BestEffortResult {
+        content,
+        data,
+        instance,
+        iscc,
+        complete,
+    }
+}
+
+/// Runs `f` and reports a panic as [`IsccError::Internal`].
+fn attempt<T>(f: impl FnOnce() -> Result<T, IsccError>) -> Result<T, IsccError> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(IsccError::Internal(panic_message(payload.as_ref()))))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".to_string())
+}
+
+fn utf8_path(path: &Path) -> Result<&str, IsccError> {
+    path.to_str()
+        .ok_or_else(|| IsccError::InvalidArgument(format!("{:?} is not valid UTF-8", path)))
+}
+
+/// The format of a file by its extension, which selects its Content-ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ContentFormat {
+    Text,
+    #[cfg(feature = "image")]
+    Image,
+    #[cfg(feature = "extract")]
+    Document,
+    /// A format without Content-ID.
+    Unsupported,
+}
+
+impl ContentFormat {
+    pub(crate) fn of(path: &Path) -> ContentFormat {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match extension.as_ref() {
+            "txt" => ContentFormat::Text,
+            #[cfg(feature = "image")]
+            "jpg" | "jpeg" | "png" | "gif" | "bmp" => ContentFormat::Image,
+            #[cfg(feature = "extract")]
+            "epub" | "docx" => ContentFormat::Document,
+            _ => ContentFormat::Unsupported,
+        }
+    }
+
+    #[cfg(feature = "cache")]
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ContentFormat::Text => "text",
+            #[cfg(feature = "image")]
+            ContentFormat::Image => "image",
+            #[cfg(feature = "extract")]
+            ContentFormat::Document => "document",
+            ContentFormat::Unsupported => "none",
+        }
+    }
+}
+
+/// The Content-ID of the file at `path` by its extension, `None` if the
+/// format has no Content-ID, see [`iscc_best_effort`].
+pub(crate) fn content_code(path: &Path) -> Result<Option<String>, IsccError> {
+    match ContentFormat::of(path) {
+        ContentFormat::Text => Ok(Some(content_id_text(&fs::read_to_string(path)?, false))),
+        #[cfg(feature = "image")]
+        ContentFormat::Image => Ok(Some(content_id_image(utf8_path(path)?, false)?)),
+        #[cfg(feature = "extract")]
+        ContentFormat::Document => Ok(Some(content_id_text_from_document(path)?.code)),
+        ContentFormat::Unsupported => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_file() {
+        let result = iscc_best_effort(Path::new("tests/test_data/invoice.txt"));
+        assert!(result.complete);
+        let content = result.content.unwrap().unwrap();
+        let data = result.data.unwrap();
+        let instance = result.instance.unwrap();
+        let iscc = result.iscc.unwrap();
+        assert_eq!(iscc.components, vec![content, data.clone(), instance]);
+        assert_eq!(
+            data.to_string(),
+            crate::did::data_id("tests/test_data/invoice.txt").unwrap()
+        );
+
+        let html = iscc_best_effort(Path::new("tests/test_data/mediafile.html"));
+        assert!(html.complete && html.content.is_none());
+        assert_eq!(html.iscc.unwrap().components.len(), 2);
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_corrupt_image() {
+        let mut png = std::fs::read("tests/test_data/cat.png").unwrap();
+        png.truncate(200);
+        png.extend(vec![0xa5; 5000]);
+        let path = std::env::temp_dir().join(format!("iscc-corrupt-{}.png", std::process::id()));
+        std::fs::write(&path, &png).unwrap();
+        let result = iscc_best_effort(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!result.complete);
+        assert!(result.content.unwrap().is_err());
+        assert!(result.data.is_ok() && result.instance.is_ok());
+        assert_eq!(result.iscc.unwrap().components.len(), 2);
+    }
+
+    #[test]
+    fn test_unreadable_path() {
+        let result = iscc_best_effort(Path::new("tests/test_data/missing.txt"));
+        assert!(!result.complete);
+        assert!(matches!(result.content, Some(Err(IsccError::Io(_)))));
+        assert!(matches!(result.data, Err(IsccError::Io(_))));
+        assert!(matches!(result.instance, Err(IsccError::Io(_))));
+        assert!(result.iscc.is_none());
+    }
+
+    #[test]
+    fn test_attempt_catches_panics() {
+        let result: Result<(), IsccError> = attempt(|| panic!("broken decoder"));
+        match result {
+            Err(IsccError::Internal(message)) => assert_eq!(message, "broken decoder"),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -1,10 +1,13 @@
 //! Feature Hashing
+use std::collections::HashSet;
 use std::hash::Hasher;
 
-use bit_vec::BitVec;
 use twox_hash::{XxHash32, XxHash64};
 
-use crate::constants::MINHASH_PERMUTATIONS;
+use crate::error::IsccError;
+use crate::multihash::{decode_varint, encode_varint};
+use crate::tables::MINHASH_PERMUTATIONS;
+use crate::xxh3;
 
 const MERSENNE_PRIME: u64 = 2_305_843_009_213_693_951;
 
@@ -13,19 +16,300 @@ const MERSENNE_PRIME: u64 = 2_305_843_009_213_693_951;
 /// preserves similarity with other sets. It is based on the MinHash
 /// implementation of the [datasketch](https://ekzhu.github.io/datasketch/)
 /// library by [Eric Zhu](https://github.com/ekzhu).
-pub fn minimum_hash(features: Vec<u32>) -> Vec<u32> {
-    let mut min_features: Vec<u32> = Vec::new();
-    for [a, b] in MINHASH_PERMUTATIONS.iter() {
-        let min = features
-            .iter()
-            .map(|f| ((a.wrapping_mul((*f).into())).wrapping_add(*b) % MERSENNE_PRIME) as u32)
-            .min()
-            .unwrap();
-        min_features.push(min);
+///
+/// The features are consumed in a single pass, so any iterator (e.g.
+/// [`text_features`]) can be sketched without collecting it first. Panics if
+/// there are no features.
+pub fn minimum_hash<I: IntoIterator<Item = u32>>(features: I) -> Vec<u32> {
+    let mut min_features: Vec<u32> = vec![u32::MAX; MINHASH_PERMUTATIONS.len()];
+    let mut n_features = 0;
+    for f in features {
+        minimum_hash_update(&mut min_features, f);
+        n_features += 1;
     }
+    assert!(n_features > 0, "MinHash requires at least one feature.");
     min_features
 }
 
+/// Adds a feature to the running minimums of [`minimum_hash`], which start as
+/// `u32::MAX`.
+pub(crate) fn minimum_hash_update(min_features: &mut [u32], feature: u32) {
+    for (min, [a, b]) in min_features.iter_mut().zip(MINHASH_PERMUTATIONS.iter()) {
+        let hashed = ((a.wrapping_mul(feature.into())).wrapping_add(*b) % MERSENNE_PRIME) as u32;
+        *min = (*min).min(hashed);
+    }
+}
+
+/// How [`MinHasher::push_unique`] detects features that were pushed before.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FeatureDedup {
+    /// Remembers every feature in a hash set. Repeated features are always
+    /// skipped and new ones never, so the sketch is identical to
+    /// [`minimum_hash`] of all features.
+    #[default]
+    Exact,
+    /// Remembers the features in a Bloom filter of `bits` bits (rounded up to
+    /// a power of two, at least 64) with 4 hash functions. Repeated features
+    /// are always skipped, but a new feature is also skipped with the false
+    /// positive rate `(1 - e^(-4n/bits))^4` after `n` distinct features, about
+    /// 2.4% for `bits = 8n`. A wrongly skipped feature only changes the sketch
+    /// if it would have been one of its minima, so the sketch may differ in a
+    /// few positions from the one of [`minimum_hash`].
+    Bloom { bits: usize },
+}
+
+/// Incremental [`minimum_hash`] with optional skipping of repeated features.
+/// MinHash only depends on the set of features, not on how often each of
+/// them occurs, so for repetitive inputs (e.g. log files) skipping repeated
+/// features with [`MinHasher::push_unique`] saves most of the work.
+#[derive(Debug, Clone)]
+pub struct MinHasher {
+    min_features: Vec<u32>,
+    n_features: u64,
+    skipped: u64,
+    seen: Seen,
+}
+
+#[derive(Debug, Clone)]
+enum Seen {
+    Exact(HashSet<u32>),
+    Bloom(Vec<u64>),
+}
+
+impl MinHasher {
+    /// Creates a hasher with [`FeatureDedup::Exact`].
+    pub fn new() -> MinHasher {
+        MinHasher::with_dedup(FeatureDedup::Exact).expect("exact dedup is valid")
+    }
+
+    /// Creates a hasher that detects repeated features with `dedup`. Returns
+    /// an error if a Bloom filter has `0` or more than `2^40` bits.
+    pub fn with_dedup(dedup: FeatureDedup) -> Result<MinHasher, IsccError> {
+        let seen = match dedup {
+            FeatureDedup::Exact => Seen::Exact(HashSet::new()),
+            FeatureDedup::Bloom { bits } => {
+                if bits == 0 || bits as u64 > 1 << 40 {
+                    return Err(IsccError::InvalidArgument(format!(
+                        "a Bloom filter must have between 1 and 2^40 bits, not {}",
+                        bits
+                    )));
+                }
+                Seen::Bloom(vec![0; bits.next_power_of_two().max(64) / 64])
+            }
+        };
+        Ok(MinHasher {
+            min_features: vec![u32::MAX; MINHASH_PERMUTATIONS.len()],
+            n_features: 0,
+            skipped: 0,
+            seen,
+        })
+    }
+
+    /// Adds a feature.
+    pub fn push(&mut self, feature: u32) {
+        minimum_hash_update(&mut self.min_features, feature);
+        self.n_features += 1;
+    }
+
+    /// Adds a feature unless it was pushed with `push_unique` before (see
+    /// [`FeatureDedup`]). Returns `false` if the feature was skipped.
+    pub fn push_unique(&mut self, feature: u32) -> bool {
+        let new = match &mut self.seen {
+            Seen::Exact(set) => set.insert(feature),
+            Seen::Bloom(filter) => bloom_insert(filter, feature),
+        };
+        if new {
+            self.push(feature);
+        } else {
+            self.skipped += 1;
+        }
+        new
+    }
+
+    /// The number of features skipped by [`MinHasher::push_unique`].
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns the sketch. Returns an error if no feature was added.
+    pub fn finish(self) -> Result<Vec<u32>, IsccError> {
+        if self.n_features == 0 && self.skipped == 0 {
+            return Err(IsccError::InvalidArgument(
+                "at least one feature is required".to_string(),
+            ));
+        }
+        Ok(self.min_features)
+    }
+}
+
+impl Default for MinHasher {
+    fn default() -> MinHasher {
+        MinHasher::new()
+    }
+}
+
+/// Sets the 4 bits of `feature` in the filter, returns `false` if all of
+/// them were set already. The bit positions are derived by double hashing.
+fn bloom_insert(filter: &mut [u64], feature: u32) -> bool {
+    let mask = filter.len() as u64 * 64 - 1;
+    let hash = u64::from(feature).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    let (h1, h2) = (hash >> 32, (hash & 0xffff_ffff) | 1);
+    let mut new = false;
+    for i in 0..4 {
+        let bit = h1.wrapping_add(i * h2) & mask;
+        let word = &mut filter[(bit / 64) as usize];
+        new |= *word & (1 << (bit % 64)) == 0;
+        *word |= 1 << (bit % 64);
+    }
+    new
+}
+
+const SKETCH_VERSION: u8 = 1;
+
+/// The encoding of the values of a serialized sketch, see
+/// [`minhash_to_bytes_with`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum SketchEncoding {
+    /// Every value as 4-byte little endian `u32`.
+    #[default]
+    Fixed,
+    /// Every value as unsigned LEB128 varint. MinHash values are minima and
+    /// get smaller the more features were sketched, so this saves space for
+    /// sketches of large feature sets.
+    Varint,
+}
+
+/// Serializes a sketch as returned by [`minimum_hash`] with
+/// [`SketchEncoding::Fixed`], see [`minhash_to_bytes_with`].
+pub fn minhash_to_bytes(sketch: &[u32]) -> Vec<u8> {
+    minhash_to_bytes_with(sketch, SketchEncoding::Fixed)
+}
+
+/// Serializes a sketch to
+///
+/// * the format version `1` (1 byte),
+/// * the encoding (1 byte, `0` for [`SketchEncoding::Fixed`] and `1` for
+///   [`SketchEncoding::Varint`]),
+/// * the number of values (`u16`, little endian),
+/// * the values in the given encoding.
+///
+/// The format will not change without a new version, sketches written by this
+/// version will always be read by [`minhash_from_bytes`]. Panics if the
+/// sketch has more than 65535 values.
+pub fn minhash_to_bytes_with(sketch: &[u32], encoding: SketchEncoding) -> Vec<u8> {
+    assert!(
+        sketch.len() <= u16::MAX as usize,
+        "A sketch has at most 65535 values."
+    );
+    let mut bytes = vec![SKETCH_VERSION, encoding as u8];
+    bytes.extend(&(sketch.len() as u16).to_le_bytes());
+    for &value in sketch {
+        match encoding {
+            SketchEncoding::Fixed => bytes.extend(&value.to_le_bytes()),
+            SketchEncoding::Varint => encode_varint(value.into(), &mut bytes),
+        }
+    }
+    bytes
+}
+
+/// Reads a sketch written with [`minhash_to_bytes_with`] in any encoding.
+/// Returns [`IsccError::InvalidSketch`] if the data is truncated, followed by
+/// trailing bytes or of an unknown version or encoding.
+pub fn minhash_from_bytes(bytes: &[u8]) -> Result<Vec<u32>, IsccError> {
+    let invalid = |reason: &str| IsccError::InvalidSketch(reason.to_string());
+    if bytes.len() < 4 {
+        return Err(invalid("truncated"));
+    }
+    if bytes[0] != SKETCH_VERSION {
+        return Err(invalid(&format!("unsupported version {}", bytes[0])));
+    }
+    let count = u16::from_le_bytes([bytes[2], bytes[3]]) as usize;
+    let mut data = &bytes[4..];
+    let mut sketch = Vec::with_capacity(count);
+    match bytes[1] {
+        0 => {
+            if data.len() < count * 4 {
+                return Err(invalid("truncated"));
+            }
+            sketch.extend(
+                data.chunks_exact(4)
+                    .take(count)
+                    .map(|v| u32::from_le_bytes([v[0], v[1], v[2], v[3]])),
+            );
+            data = &data[count * 4..];
+        }
+        1 => {
+            for _ in 0..count {
+                let (value, n) = decode_varint(data).map_err(|_| invalid("truncated"))?;
+                if value > u64::from(u32::MAX) {
+                    return Err(invalid("value out of range"));
+                }
+                sketch.push(value as u32);
+                data = &data[n..];
+            }
+        }
+        encoding => return Err(invalid(&format!("unknown encoding {}", encoding))),
+    }
+    if !data.is_empty() {
+        return Err(invalid("trailing bytes"));
+    }
+    Ok(sketch)
+}
+
+/// The maximum number of distinct needle features compared by
+/// [`feature_containment`].
+pub const CONTAINMENT_SAMPLE_SIZE: usize = 4096;
+
+/// Estimates how much of the feature set of `needle` is contained in the
+/// feature set of `haystack`, `|A ∩ B| / |A|`. Repeated features count once.
+///
+/// If `needle` has at most [`CONTAINMENT_SAMPLE_SIZE`] distinct features the
+/// result is exact. Otherwise it is estimated from the bottom-k sample of the
+/// smallest distinct needle features, with a standard error of at most
+/// `0.5 / sqrt(CONTAINMENT_SAMPLE_SIZE)` (below 0.008). The haystack is
+/// streamed and never collected. Returns an error if `needle` is empty.
+pub fn feature_containment(needle: &[u32], haystack: &[u32]) -> Result<f64, IsccError> {
+    containment(needle, haystack.iter().copied())
+}
+
+pub(crate) fn containment<I: IntoIterator<Item = u32>>(
+    needle: &[u32],
+    haystack: I,
+) -> Result<f64, IsccError> {
+    if needle.is_empty() {
+        return Err(IsccError::InvalidArgument(
+            "the needle has no features".to_string(),
+        ));
+    }
+    let mut sample = needle.to_vec();
+    sample.sort_unstable();
+    sample.dedup();
+    sample.truncate(CONTAINMENT_SAMPLE_SIZE);
+    let sample: HashSet<u32> = sample.into_iter().collect();
+
+    let mut found = HashSet::new();
+    for feature in haystack {
+        if sample.contains(&feature) {
+            found.insert(feature);
+            if found.len() == sample.len() {
+                break;
+            }
+        }
+    }
+    Ok(found.len() as f64 / sample.len() as f64)
+}
+
+/// Estimates the Jaccard similarity of the feature sets of two sketches
+/// produced by [`minimum_hash`] as the fraction of equal positions.
+pub fn minhash_similarity(a: &[u32], b: &[u32]) -> f64 {
+    assert_eq!(a.len(), b.len(), "Sketches must be of equal length.");
+    if a.is_empty() {
+        return 0.0;
+    }
+    let equal = a.iter().zip(b.iter()).filter(|(x, y)| x == y).count();
+    equal as f64 / a.len() as f64
+}
+
 pub fn sliding_window(seq: &str, width: usize) -> Vec<String> {
     assert!(width >= 2, "Sliding window width must be 2 or bigger.");
     let characters: Vec<char> = seq.chars().collect();
@@ -41,6 +325,102 @@ pub fn sliding_window(seq: &str, width: usize) -> Vec<String> {
     result
 }
 
+/// The unit of the window width of [`sliding_window_units`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WidthUnit {
+    /// Unicode scalar values, like [`sliding_window`].
+    #[default]
+    Chars,
+    /// UTF-8 bytes.
+    Bytes,
+}
+
+/// Returns the windows of `seq` that are `width` units wide.
+///
+/// With [`WidthUnit::Chars`] this is [`sliding_window`]. With
+/// [`WidthUnit::Bytes`] a window starts at every byte offset `start` of `seq`
+/// that is a character boundary and not greater than `seq.len() - width`. It
+/// ends at the last character boundary at or before `start + width`, so no
+/// UTF-8 sequence is split and windows may be shorter than `width` bytes. If
+/// the first character of a window is longer than `width` bytes, the window
+/// holds just that character. Unless the last of these windows ends with
+/// `seq`, a final window holds the characters that start in the last `width`
+/// bytes, so that every character is in a window. If `seq` has at most
+/// `width` bytes it is a single window. Panics if `width` is smaller than 2.
+pub fn sliding_window_units(seq: &str, width: usize, unit: WidthUnit) -> Vec<String> {
+    match unit {
+        WidthUnit::Chars => sliding_window(seq, width),
+        WidthUnit::Bytes => {
+            assert!(width >= 2, "Sliding window width must be 2 or bigger.");
+            if seq.len() <= width {
+                return vec![seq.to_string()];
+            }
+            let mut windows: Vec<&str> = seq
+                .char_indices()
+                .take_while(|&(start, _)| start <= seq.len() - width)
+                .map(|(start, c)| {
+                    let mut end = start + width;
+                    while !seq.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    &seq[start..end.max(start + c.len_utf8())]
+                })
+                .collect();
+            if !windows.last().is_some_and(|w| seq.ends_with(w)) {
+                let mut start = seq.len() - width;
+                while !seq.is_char_boundary(start) {
+                    start += 1;
+                }
+                windows.push(&seq[start..]);
+            }
+            windows.into_iter().map(String::from).collect()
+        }
+    }
+}
+
+/// Returns the same windows as [`sliding_window`] as slices borrowed from
+/// `seq` if `seq` is pure ASCII, so that no window has to be allocated.
+/// Returns `None` for any other input, use `sliding_window` then.
+pub fn sliding_window_slices(seq: &str, width: usize) -> Option<Vec<&str>> {
+    assert!(width >= 2, "Sliding window width must be 2 or bigger.");
+    if !seq.is_ascii() {
+        return None;
+    }
+    if seq.len() <= width {
+        return Some(vec![seq]);
+    }
+    Some(
+        (0..=seq.len() - width)
+            .map(|i| &seq[i..i + width])
+            .collect(),
+    )
+}
+
+/// Lazily yields the `xxhash32` of each window of [`sliding_window`] without
+/// allocating the windows. The windows are borrowed from `seq`.
+pub fn text_features(seq: &str, width: usize) -> impl Iterator<Item = u32> + '_ {
+    assert!(width >= 2, "Sliding window width must be 2 or bigger.");
+    // Like `sliding_window`, a sequence of at most `width` characters is a
+    // single window.
+    let short = seq.chars().nth(width).is_none();
+    let starts = seq.char_indices().map(|(i, _)| i);
+    let ends = seq
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(seq.len()))
+        .skip(width);
+    let windows = starts
+        .zip(ends)
+        .map(move |(start, end)| &seq[start..end])
+        .take(if short { 0 } else { usize::MAX });
+    let whole = if short { Some(seq) } else { None };
+
+    whole
+        .into_iter()
+        .chain(windows)
+        .map(|window| xxhash32(window.as_bytes()))
+}
+
 /// The `similarity_hash` function takes a sequence of hash digests which
 /// represent a set of features. Each of the digests MUST be of equal size. The
 /// function returns a new hash digest (raw 8-bit bytes) of the same size. For
@@ -50,25 +430,254 @@ pub fn sliding_window(seq: &str, width: usize) -> Vec<String> {
 /// zero or positive. The resulting hash digest will retain similarity for
 /// similar sets of input hashes. See also
 /// [Charikar2002](http://dx.doi.org/10.1145/509907.509965).
+///
+/// The votes are counted in `u64` on every target. Panics if `hash_digests`
+/// is empty.
 pub fn similarity_hash(hash_digests: Vec<u64>) -> Vec<u8> {
     assert!(!hash_digests.is_empty());
-    let n_digests = hash_digests.len();
+    let n_digests = hash_digests.len() as u64;
 
-    let mut bitcounts: Vec<u64> = vec![0; 64];
+    let mut bitcounts = [0u64; 64];
     for digest in hash_digests {
-        #[allow(clippy::needless_range_loop)]
-        for i in 0..64 {
-            let bit_i = (digest >> i) & 1;
-            bitcounts[i] += bit_i;
+        for (i, bitcount) in bitcounts.iter_mut().enumerate() {
+            *bitcount += (digest >> i) & 1;
         }
     }
-    let minfeatures = (n_digests / 2 + n_digests % 2) as u64;
-    let shash: BitVec<u64> = bitcounts
-        .into_iter()
-        .rev()
-        .map(|bitcount| bitcount >= minfeatures)
-        .collect();
-    shash.to_bytes().to_vec()
+    simhash_vote(&bitcounts, n_digests)
+}
+
+/// Sets the bit of every count that is at least half of `n_digests`, rounded
+/// up. The last count is the most significant bit of the first byte.
+fn simhash_vote(bitcounts: &[u64], n_digests: u64) -> Vec<u8> {
+    let minfeatures = n_digests.div_ceil(2);
+    pack_bits(
+        bitcounts
+            .iter()
+            .rev()
+            .map(|&bitcount| bitcount >= minfeatures),
+    )
+}
+
+/// Packs bits into bytes, the first bit into the most significant bit of the
+/// first byte, and pads the last byte with zero bits. All component bodies
+/// are packed in this order, which does not depend on the byte order of the
+/// platform: the packed bits of an integer from its most significant bit down
+/// are its big endian bytes.
+pub(crate) fn pack_bits<I: IntoIterator<Item = bool>>(bits: I) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (i, bit) in bits.into_iter().enumerate() {
+        if i / 8 == bytes.len() {
+            bytes.push(0);
+        }
+        if bit {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    bytes
+}
+
+/// The maximum number of digests of a [`SimHasher`], `2^48`. The votes of
+/// more digests would still fit into the 64-bit counters, the limit leaves a
+/// wide margin to any wrap point and is far beyond the digests any input of
+/// this crate produces.
+pub const MAX_SIMHASH_DIGESTS: u64 = 1 << 48;
+
+/// Incremental [`similarity_hash`] for digests that arrive one by one. The
+/// hash is identical to `similarity_hash` of all pushed digests.
+///
+/// All counters are `u64` on every target, and pushing more than
+/// [`MAX_SIMHASH_DIGESTS`] digests fails with
+/// [`IsccError::TooManyFeatures`] instead of corrupting the vote.
+#[derive(Debug, Clone)]
+pub struct SimHasher {
+    bitcounts: [u64; 64],
+    n_digests: u64,
+}
+
+impl SimHasher {
+    pub fn new() -> SimHasher {
+        SimHasher {
+            bitcounts: [0; 64],
+            n_digests: 0,
+        }
+    }
+
+    /// Adds a digest.
+    pub fn push(&mut self, digest: u64) -> Result<(), IsccError> {
+        self.push_repeated(digest, 1)
+    }
+
+    /// Adds a digest `count` times, like `count` calls of
+    /// [`SimHasher::push`]. Returns [`IsccError::TooManyFeatures`] and adds
+    /// nothing if the hasher would exceed [`MAX_SIMHASH_DIGESTS`].
+    pub fn push_repeated(&mut self, digest: u64, count: u64) -> Result<(), IsccError> {
+        if count > MAX_SIMHASH_DIGESTS - self.n_digests {
+            return Err(IsccError::TooManyFeatures {
+                limit: MAX_SIMHASH_DIGESTS,
+            });
+        }
+        for (i, bitcount) in self.bitcounts.iter_mut().enumerate() {
+            *bitcount += ((digest >> i) & 1) * count;
+        }
+        self.n_digests += count;
+        Ok(())
+    }
+
+    /// The number of digests added so far.
+    pub fn len(&self) -> u64 {
+        self.n_digests
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.n_digests == 0
+    }
+
+    /// Returns the hash. Returns an error if no digest was added.
+    pub fn finish(self) -> Result<Vec<u8>, IsccError> {
+        if self.n_digests == 0 {
+            return Err(IsccError::InvalidArgument(
+                "at least one digest is required".to_string(),
+            ));
+        }
+        Ok(simhash_vote(&self.bitcounts, self.n_digests))
+    }
+}
+
+impl Default for SimHasher {
+    fn default() -> SimHasher {
+        SimHasher::new()
+    }
+}
+
+/// The byte layout of the output of [`similarity_hash_with_order`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BitOrder {
+    /// The layout of [`similarity_hash`]: the vote on bit `i` of the input
+    /// digests (bit `0` being the least significant) is bit `i % 8` of output
+    /// byte `7 - i / 8`. The output is the big endian representation of the
+    /// voted value. This is the output of the Python reference implementation
+    /// if it is given the big endian bytes of the digests.
+    #[default]
+    Current,
+    /// The vote on bit `i` of the input digests is bit `i % 8` of output byte
+    /// `i / 8`, i.e. the little endian representation of the voted value and
+    /// the reversed bytes of [`BitOrder::Current`]. This is the output of the
+    /// Python reference implementation if it is given the little endian bytes
+    /// of the digests. **Codes with this layout do not match the codes of
+    /// this crate.**
+    Reference,
+}
+
+/// Computes the 64-bit [`similarity_hash`] with the given output layout.
+pub fn similarity_hash_with_order(hash_digests: &[u64], order: BitOrder) -> Vec<u8> {
+    let mut shash = similarity_hash(hash_digests.to_vec());
+    if order == BitOrder::Reference {
+        shash.reverse();
+    }
+    shash
+}
+
+/// Computes the 64-bit [`similarity_hash`] and returns only its first `bits`
+/// bits. `bits` must be a multiple of 8 between 8 and 64.
+///
+/// The output of `similarity_hash` is the big endian byte representation of
+/// the voted 64-bit value: The most significant bit of the first byte is the
+/// vote on bit 63 (the most significant bit) of the input digests, the least
+/// significant bit of the last byte the vote on bit 0. The truncated hash thus
+/// holds the votes on the `bits` most significant bits of the input digests and
+/// is always a prefix of the full 64-bit hash.
+///
+/// Returns [`IsccError::InvalidArgument`] for an invalid `bits` and for empty
+/// `hash_digests`.
+pub fn similarity_hash_bits(hash_digests: &[u64], bits: u32) -> Result<Vec<u8>, IsccError> {
+    if bits == 0 || bits > 64 || !bits.is_multiple_of(8) {
+        return Err(IsccError::InvalidArgument(format!(
+            "bits must be a multiple of 8 between 8 and 64, not {}",
+            bits
+        )));
+    }
+    if hash_digests.is_empty() {
+        return Err(IsccError::InvalidArgument(
+            "the similarity hash of no digests is undefined".to_string(),
+        ));
+    }
+    let mut shash = similarity_hash(hash_digests.to_vec());
+    shash.truncate(bits as usize / 8);
+    Ok(shash)
+}
+
+/// Extends [`similarity_hash`] to 128-bit digests and returns a 16 byte hash.
+/// Like with `similarity_hash` the most significant bit of the first output
+/// byte is the vote on the most significant bit of the input digests.
+pub fn similarity_hash_u128(hash_digests: &[u128]) -> Vec<u8> {
+    assert!(!hash_digests.is_empty());
+    let n_digests = hash_digests.len() as u64;
+
+    let mut bitcounts = [0u64; 128];
+    for digest in hash_digests {
+        for (i, bitcount) in bitcounts.iter_mut().enumerate() {
+            *bitcount += ((digest >> i) & 1) as u64;
+        }
+    }
+    simhash_vote(&bitcounts, n_digests)
+}
+
+/// Computes the 64-bit [`similarity_hash`] together with the margin of the
+/// vote on every bit, i.e. the absolute difference between the number of
+/// digests with the bit set and the number of digests with the bit not set,
+/// saturated at `u16::MAX`. Bits with a small margin flip more easily when the
+/// features change slightly.
+///
+/// The margins are in the bit order of the output hash: `margins[j]` belongs
+/// to bit `7 - j % 8` of output byte `j / 8`, which is the vote on bit
+/// `63 - j` of the input digests. So `margins[0]` belongs to the most
+/// significant bit of the first byte and `margins[63]` to the least
+/// significant bit of the last byte.
+///
+/// Panics if `hash_digests` is empty.
+pub fn similarity_hash_confidence(hash_digests: &[u64]) -> (Vec<u8>, [u16; 64]) {
+    assert!(!hash_digests.is_empty());
+    let n_digests = hash_digests.len() as u64;
+
+    let mut bitcounts = [0u64; 64];
+    for digest in hash_digests {
+        for (i, bitcount) in bitcounts.iter_mut().enumerate() {
+            *bitcount += (digest >> i) & 1;
+        }
+    }
+    let mut margins = [0u16; 64];
+    for (j, margin) in margins.iter_mut().enumerate() {
+        let ones = bitcounts[63 - j];
+        let zeros = n_digests - ones;
+        *margin = ones
+            .max(zeros)
+            .saturating_sub(ones.min(zeros))
+            .min(u16::MAX as u64) as u16;
+    }
+    (similarity_hash(hash_digests.to_vec()), margins)
+}
+
+/// The Hamming distance of two hashes of [`similarity_hash_confidence`] where
+/// every disagreeing bit is weighted by the smaller of its two confidences.
+/// The confidence of a bit is its margin relative to the largest margin of its
+/// hash, so a disagreement on a bit that both hashes decided clearly counts
+/// `1.0` and a disagreement on a bit that either hash decided by a tie counts
+/// `0.0`. The distance is between `0.0` and `64.0` and never larger than the
+/// Hamming distance of the hashes.
+pub fn weighted_distance(a: &(Vec<u8>, [u16; 64]), b: &(Vec<u8>, [u16; 64])) -> f64 {
+    let confidence = |margins: &[u16; 64], j: usize| {
+        let max = *margins.iter().max().unwrap();
+        if max == 0 {
+            0.0
+        } else {
+            margins[j] as f64 / max as f64
+        }
+    };
+    let bit = |hash: &[u8], j: usize| (hash[j / 8] >> (7 - j % 8)) & 1;
+    (0..64)
+        .filter(|&j| bit(&a.0, j) != bit(&b.0, j))
+        .map(|j| confidence(&a.1, j).min(confidence(&b.1, j)))
+        .sum()
 }
 
 pub fn xxhash32(data: &[u8]) -> u32 {
@@ -77,15 +686,42 @@ pub fn xxhash32(data: &[u8]) -> u32 {
     hasher.finish() as u32
 }
 
-pub fn xxhash64(data: &[u8]) -> u64 {
+/// The `xxhash32` of `namespace` followed by `data`.
+pub(crate) fn xxhash32_namespaced(namespace: &[u8], data: &[u8]) -> u32 {
+    let mut hasher = XxHash32::with_seed(0);
+    hasher.write(namespace);
+    hasher.write(data);
+    hasher.finish() as u32
+}
+
+/// The `xxhash64` of `namespace` followed by `data`.
+pub(crate) fn xxhash64_namespaced(namespace: &[u8], data: &[u8]) -> u64 {
     let mut hasher = XxHash64::with_seed(0);
+    hasher.write(namespace);
     hasher.write(data);
     hasher.finish()
 }
 
+pub fn xxhash64(data: &[u8]) -> u64 {
+    xxhash64_with_seed(data, 0)
+}
+
+pub fn xxhash64_with_seed(data: &[u8], seed: u64) -> u64 {
+    let mut hasher = XxHash64::with_seed(seed);
+    hasher.write(data);
+    hasher.finish()
+}
+
+/// The 128-bit XXH3 hash (xxHash 0.8) of `data` with seed `0`.
+pub fn xxhash128(data: &[u8]) -> u128 {
+    xxh3::hash128(data)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    use std::convert::TryInto;
     #[allow(clippy::unreadable_literal)]
     #[test]
     fn test_minimum_hash() {
@@ -107,6 +743,17 @@ mod tests {
 
         assert_eq!(minimum_hash(features), outputs);
     }
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn test_minhash_similarity() {
+        let a = minimum_hash(0..100);
+        let b = minimum_hash(50..150);
+        assert_eq!(minhash_similarity(&a, &a), 1.0);
+        let similarity = minhash_similarity(&a, &b);
+        assert!(similarity > 0.15 && similarity < 0.55, "{}", similarity);
+        assert_eq!(minhash_similarity(&[1, 2, 3, 4], &[1, 0, 3, 0]), 0.5);
+    }
+
     #[test]
     fn test_sliding_window() {
         assert_eq!(sliding_window("", 4), vec!["".to_string()]);
@@ -116,6 +763,60 @@ mod tests {
             vec!["Hell".to_string(), "ello".to_string()]
         );
     }
+    #[test]
+    fn test_minimum_hash_from_iterator() {
+        let features: Vec<u32> = (0..1000).map(|i| xxhash32(&[i as u8, 7])).collect();
+        assert_eq!(
+            minimum_hash(features.iter().copied()),
+            minimum_hash(features)
+        );
+    }
+
+    #[test]
+    fn test_sliding_window_slices() {
+        for &text in ["", "A", "Hell", "Hello", "The quick brown fox"].iter() {
+            for &width in [2, 4, 13].iter() {
+                assert_eq!(
+                    sliding_window_slices(text, width).unwrap(),
+                    sliding_window(text, width)
+                );
+            }
+        }
+        for &text in ["Iñtërnâtiônàlizætiøn", "crème brûlée", "fox 🦊 dog 🐶"].iter()
+        {
+            assert_eq!(sliding_window_slices(text, 4), None);
+        }
+    }
+
+    #[test]
+    fn test_text_features() {
+        for &text in ["", "A", "Hell", "Hello", "Iñtërnâtiônàlizætiøn☃💩"].iter() {
+            for &width in [2, 4, 13].iter() {
+                let eager: Vec<u32> = sliding_window(text, width)
+                    .iter()
+                    .map(|w| xxhash32(w.as_bytes()))
+                    .collect();
+                let lazy: Vec<u32> = text_features(text, width).collect();
+                assert_eq!(lazy, eager, "{:?} width {}", text, width);
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_features_filtered() {
+        // Drop the features of windows that are whitespace only before sketching.
+        let text = "a    b    c    d    e    f    g    h";
+        let blank = xxhash32(b"    ");
+        let filtered = minimum_hash(text_features(text, 4).filter(|&f| f != blank));
+        let expected: Vec<u32> = sliding_window(text, 4)
+            .iter()
+            .filter(|w| !w.trim().is_empty())
+            .map(|w| xxhash32(w.as_bytes()))
+            .collect();
+        assert_eq!(filtered, minimum_hash(expected));
+        assert_ne!(filtered, minimum_hash(text_features(text, 4)));
+    }
+
     #[test]
     fn test_similarity_hash() {
         let hash_digests: Vec<u64> = vec![0; 16];
@@ -125,4 +826,453 @@ mod tests {
         //TODO: More tests
     }
 
+    #[test]
+    fn test_pack_bits() {
+        assert!(pack_bits(Vec::new()).is_empty());
+        assert_eq!(pack_bits(vec![true]), vec![0x80]);
+        assert_eq!(
+            pack_bits(vec![
+                false, true, false, false, false, false, false, true, true
+            ]),
+            vec![0x41, 0x80]
+        );
+        for &value in [0u64, 1, 0x0123_4567_89ab_cdef, u64::MAX].iter() {
+            let bits = (0..64).rev().map(|i| (value >> i) & 1 == 1);
+            assert_eq!(pack_bits(bits), value.to_be_bytes());
+        }
+    }
+
+    #[test]
+    fn test_sim_hasher() {
+        let digests = [0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210, u64::MAX, 0xff];
+        for n in 1..=digests.len() {
+            let mut hasher = SimHasher::new();
+            for &digest in &digests[..n] {
+                hasher.push(digest).unwrap();
+            }
+            assert_eq!(hasher.len(), n as u64);
+            assert_eq!(
+                hasher.finish().unwrap(),
+                similarity_hash(digests[..n].to_vec())
+            );
+        }
+        assert!(SimHasher::new().finish().is_err());
+    }
+
+    #[test]
+    fn test_sim_hasher_limit() {
+        // Repeating every digest equally often does not change the vote, so
+        // the batch hash of three digests is the hash of 2^48 - 1 digests.
+        let digests = [0x0123_4567_89ab_cdef, 0xfedc_ba98_7654_3210, 0xff];
+        let repeat = (MAX_SIMHASH_DIGESTS - 1) / 3;
+        let mut hasher = SimHasher::new();
+        for &digest in digests.iter() {
+            hasher.push_repeated(digest, repeat).unwrap();
+        }
+        assert_eq!(hasher.len(), MAX_SIMHASH_DIGESTS - 1);
+        let expected = similarity_hash(digests.to_vec());
+        assert_eq!(hasher.clone().finish().unwrap(), expected);
+
+        // Just over the limit nothing is added.
+        match hasher.push_repeated(digests[0], 2) {
+            Err(IsccError::TooManyFeatures { limit }) => assert_eq!(limit, MAX_SIMHASH_DIGESTS),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(hasher.len(), MAX_SIMHASH_DIGESTS - 1);
+        assert_eq!(hasher.clone().finish().unwrap(), expected);
+
+        // The limit itself is accepted.
+        hasher.push(digests[0]).unwrap();
+        assert_eq!(hasher.len(), MAX_SIMHASH_DIGESTS);
+        assert!(matches!(
+            hasher.push(digests[0]),
+            Err(IsccError::TooManyFeatures { .. })
+        ));
+        assert!(hasher.finish().is_ok());
+    }
+    #[test]
+    fn test_similarity_hash_confidence() {
+        // Bit 0 is set in two of three digests, bit 63 in all, bit 62 in one.
+        let digests = [
+            0x8000_0000_0000_0001,
+            0xC000_0000_0000_0001,
+            0x8000_0000_0000_0000,
+        ];
+        let (hash, margins) = similarity_hash_confidence(&digests);
+        assert_eq!(hash, similarity_hash(digests.to_vec()));
+        assert_eq!(hash, [0x80, 0, 0, 0, 0, 0, 0, 0x01]);
+        assert_eq!(margins[0], 3);
+        assert_eq!(margins[1], 1);
+        assert_eq!(margins[63], 1);
+        assert!(margins[2..63].iter().all(|&m| m == 3));
+
+        // A tie sets the bit with a margin of zero.
+        let (hash, margins) = similarity_hash_confidence(&[1, 0]);
+        assert_eq!((hash[7], margins[63], margins[0]), (1, 0, 2));
+    }
+
+    #[test]
+    fn test_similarity_hash_confidence_noise() {
+        // The low 32 bits are set in 51 of 101 digests, the high 32 bits in 91.
+        let digests: Vec<u64> = (0..101u64)
+            .map(|i| {
+                let low = if i < 51 { 0xFFFF_FFFF } else { 0 };
+                let high = if i < 91 { 0xFFFF_FFFF_0000_0000 } else { 0 };
+                low | high
+            })
+            .collect();
+        let original = similarity_hash_confidence(&digests);
+        assert!(original.1[..32].iter().all(|&m| m == 81));
+        assert!(original.1[32..].iter().all(|&m| m == 1));
+
+        let (mut low_flips, mut high_flips) = (0, 0);
+        for trial in 0..20u64 {
+            let mut noisy = digests.clone();
+            noisy.extend((0..30u64).map(|i| xxhash64(&(trial * 100 + i).to_le_bytes())));
+            let noisy = similarity_hash_confidence(&noisy);
+            let original = u64::from_be_bytes(original.0[..].try_into().unwrap());
+            let flipped = original ^ u64::from_be_bytes(noisy.0[..].try_into().unwrap());
+            low_flips += (flipped & 0xFFFF_FFFF).count_ones();
+            high_flips += (flipped >> 32).count_ones();
+        }
+        assert_eq!(high_flips, 0);
+        assert!(low_flips > 100, "{} low margin bits flipped", low_flips);
+    }
+
+    #[test]
+    fn test_weighted_distance() {
+        let digests: Vec<u64> = (0..25u64).map(|i| xxhash64(&i.to_le_bytes())).collect();
+        let a = similarity_hash_confidence(&digests);
+        assert_eq!(weighted_distance(&a, &a), 0.0);
+
+        let mut edited = digests.clone();
+        edited.truncate(22);
+        edited.extend((100..103u64).map(|i| xxhash64(&i.to_le_bytes())));
+        let b = similarity_hash_confidence(&edited);
+        let hamming = (u64::from_be_bytes(a.0[..].try_into().unwrap())
+            ^ u64::from_be_bytes(b.0[..].try_into().unwrap()))
+        .count_ones();
+        let weighted = weighted_distance(&a, &b);
+        assert!(hamming > 0);
+        assert!(weighted < hamming as f64, "{} {}", weighted, hamming);
+        assert_eq!(weighted, weighted_distance(&b, &a));
+
+        // A disagreement on a tied bit does not count.
+        let tied = similarity_hash_confidence(&[1, 0]);
+        let unset = similarity_hash_confidence(&[0, 0]);
+        assert_eq!(weighted_distance(&tied, &unset), 0.0);
+        let set = similarity_hash_confidence(&[1, 1]);
+        assert_eq!(weighted_distance(&set, &unset), 1.0);
+    }
+
+    #[test]
+    fn test_similarity_hash_bits() {
+        let hash_digests: Vec<u64> = (1..20u64).map(|i| xxhash64(&i.to_le_bytes())).collect();
+        let full = similarity_hash(hash_digests.clone());
+        for bits in (8..=64).step_by(8) {
+            let truncated = similarity_hash_bits(&hash_digests, bits).unwrap();
+            assert_eq!(truncated, full[..bits as usize / 8].to_vec());
+        }
+        // The first byte holds the votes on the most significant input bits
+        let digests = vec![0xFF00_0000_0000_0001];
+        assert_eq!(similarity_hash_bits(&digests, 8).unwrap(), vec![0xFF]);
+        assert_eq!(similarity_hash(digests)[7], 0x01);
+        for &bits in [0, 4, 65, 72].iter() {
+            assert!(similarity_hash_bits(&hash_digests, bits).is_err());
+        }
+        assert!(matches!(
+            similarity_hash_bits(&[], 64),
+            Err(IsccError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_minhash_bytes_round_trip() {
+        // xorshift64, so the sketches are random but reproducible
+        let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for i in 0..200 {
+            let len = if i < 100 {
+                64
+            } else {
+                (random() % 300) as usize
+            };
+            let shift = random() % 32;
+            let sketch: Vec<u32> = (0..len)
+                .map(|_| (random() >> (32 + shift)) as u32)
+                .collect();
+
+            let fixed = minhash_to_bytes(&sketch);
+            assert_eq!(fixed.len(), 4 + 4 * len);
+            assert_eq!(minhash_from_bytes(&fixed).unwrap(), sketch);
+            let varint = minhash_to_bytes_with(&sketch, SketchEncoding::Varint);
+            assert_eq!(minhash_from_bytes(&varint).unwrap(), sketch);
+        }
+
+        let sketch = minimum_hash(1..20000);
+        let varint = minhash_to_bytes_with(&sketch, SketchEncoding::Varint);
+        assert!(varint.len() < minhash_to_bytes(&sketch).len());
+        assert_eq!(minhash_from_bytes(&varint).unwrap(), sketch);
+    }
+
+    #[test]
+    fn test_minhash_bytes_layout() {
+        let bytes = minhash_to_bytes(&[1, 0x0403_0201]);
+        assert_eq!(bytes, [1, 0, 2, 0, 1, 0, 0, 0, 1, 2, 3, 4]);
+        let bytes = minhash_to_bytes_with(&[1, 300], SketchEncoding::Varint);
+        assert_eq!(bytes, [1, 1, 2, 0, 1, 0xac, 0x02]);
+        assert_eq!(
+            minhash_from_bytes(&[1, 0, 0, 0]).unwrap(),
+            Vec::<u32>::new()
+        );
+    }
+
+    #[test]
+    fn test_minhash_bytes_invalid() {
+        let sketch = minimum_hash(1..100);
+        for &encoding in [SketchEncoding::Fixed, SketchEncoding::Varint].iter() {
+            let bytes = minhash_to_bytes_with(&sketch, encoding);
+            for n in 0..bytes.len() {
+                match minhash_from_bytes(&bytes[..n]) {
+                    Err(IsccError::InvalidSketch(reason)) => assert_eq!(reason, "truncated"),
+                    other => panic!("unexpected result {:?}", other),
+                }
+            }
+            let mut trailing = bytes.clone();
+            trailing.push(0);
+            match minhash_from_bytes(&trailing) {
+                Err(IsccError::InvalidSketch(reason)) => assert_eq!(reason, "trailing bytes"),
+                other => panic!("unexpected result {:?}", other),
+            }
+            let mut version = bytes.clone();
+            version[0] = 2;
+            match minhash_from_bytes(&version) {
+                Err(IsccError::InvalidSketch(reason)) => {
+                    assert_eq!(reason, "unsupported version 2")
+                }
+                other => panic!("unexpected result {:?}", other),
+            }
+        }
+        match minhash_from_bytes(&[1, 2, 0, 0]) {
+            Err(IsccError::InvalidSketch(reason)) => assert_eq!(reason, "unknown encoding 2"),
+            other => panic!("unexpected result {:?}", other),
+        }
+        match minhash_from_bytes(&[1, 1, 1, 0, 0xff, 0xff, 0xff, 0xff, 0x7f]) {
+            Err(IsccError::InvalidSketch(reason)) => assert_eq!(reason, "value out of range"),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_similarity_hash_with_order() {
+        // Cross-checked with the `similarity_hash` of the Python reference
+        // implementation given `d.to_bytes(8, "big")` (current) and
+        // `d.to_bytes(8, "little")` (reference) of the digests.
+        let digests = [
+            0x0123_4567_89ab_cdef,
+            0x0f1e_2d3c_4b5a_6978,
+            0xfedc_ba98_7654_3210,
+            0x8000_0000_0000_0001,
+            0x1357_9bdf_0246_8ace,
+        ];
+        let current = similarity_hash_with_order(&digests, BitOrder::Current);
+        assert_eq!(current, [0x03, 0x16, 0x09, 0x1c, 0x02, 0x42, 0x08, 0x48]);
+        assert_eq!(current, similarity_hash(digests.to_vec()));
+        let reference = similarity_hash_with_order(&digests, BitOrder::Reference);
+        assert_eq!(reference, [0x48, 0x08, 0x42, 0x02, 0x1c, 0x09, 0x16, 0x03]);
+
+        // The vote on bit 0 is the lowest bit of the last or the first byte.
+        let digests = [1];
+        assert_eq!(
+            similarity_hash_with_order(&digests, BitOrder::Current)[7],
+            1
+        );
+        assert_eq!(
+            similarity_hash_with_order(&digests, BitOrder::Reference)[0],
+            1
+        );
+        assert_eq!(BitOrder::default(), BitOrder::Current);
+    }
+
+    #[allow(clippy::unreadable_literal)]
+    #[test]
+    fn test_xxhash128() {
+        assert_eq!(xxhash128(b""), 0x99aa06d3014798d86001c324468d497f);
+        assert_eq!(xxhash128(b"a"), 0xa96faf705af16834e6c632b61e964e1f);
+        assert_eq!(xxhash128(b"abc"), 0x06b05ab6733a618578af5f94892f3950);
+
+        // The sanity test vectors of the xxHash reference implementation
+        let mut buffer = vec![0u8; 2367];
+        let mut byte_gen: u64 = 2654435761;
+        for byte in buffer.iter_mut() {
+            *byte = (byte_gen >> 56) as u8;
+            byte_gen = byte_gen.wrapping_mul(11400714785074694797);
+        }
+        let vectors: [(usize, u128); 17] = [
+            (0, 0x99aa06d3014798d86001c324468d497f),
+            (1, 0xa6cd5e9392000f6ac44bdff4074eecdb),
+            (6, 0x082afe0b8162d12a3e7039bdda43cfc6),
+            (12, 0x6e3efd8fc7802b18061a192713f69ad9),
+            (16, 0xc68c368ecf8a9c05562980258a998629),
+            (17, 0x955fa78643ed3669abbc12d11973d7db),
+            (24, 0x0ce966e4678d37611e7044d28b1b901d),
+            (48, 0xa002ac4e5478227ef942219aed80f67b),
+            (80, 0xfdf2cefde9eaac8a454ae6bf7a8a532d),
+            (128, 0x39992220e045260aebb15e34a7fb5ab1),
+            (129, 0x03815fc91f1b30b686c9e3bc8f0a3b5c),
+            (195, 0x7729543a26b207ee3fb593c086a66075),
+            (240, 0xaa4202daa2769dc85c9aae94c8ebe5a0),
+            (241, 0x99a80ecf0ecfc647c5a639ecd2030e5e),
+            (1025, 0xfd3ee4fe7f2954c6d870c0fa13211c6a),
+            (2240, 0xccb134fbfa7ce49d6e73a90539cf2948),
+            (2367, 0xe89c0f6ff369b427cb37aeb9e5d361ed),
+        ];
+        for &(len, expected) in vectors.iter() {
+            assert_eq!(xxhash128(&buffer[..len]), expected, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_similarity_hash_u128() {
+        // Bit 127 and bit 0 are set in two, bit 1 in one of the three digests.
+        let digests = [1 << 127 | 1, 1 << 127 | 2, 1];
+        let mut expected = vec![0u8; 16];
+        expected[0] = 0x80;
+        expected[15] = 0x01;
+        assert_eq!(similarity_hash_u128(&digests), expected);
+
+        // With an even number of digests a tie sets the bit.
+        let digests = [0x0F << 64, 0xF0];
+        let mut expected = vec![0u8; 16];
+        expected[7] = 0x0F;
+        expected[15] = 0xF0;
+        assert_eq!(similarity_hash_u128(&digests), expected);
+    }
+
+    /// Features of a log file like text: few distinct lines repeated often.
+    fn log_features() -> Vec<u32> {
+        let log: String = (0..2000)
+            .map(|i| format!("INFO request {} served in {} ms\n", i % 50, i % 7))
+            .collect();
+        text_features(&log, 13).collect()
+    }
+
+    #[test]
+    fn test_min_hasher_exact() {
+        let features = log_features();
+        let mut hasher = MinHasher::new();
+        let mut plain = MinHasher::new();
+        for &f in &features {
+            hasher.push_unique(f);
+            plain.push(f);
+        }
+        let distinct: HashSet<u32> = features.iter().copied().collect();
+        assert_eq!(hasher.skipped() as usize, features.len() - distinct.len());
+        assert!(hasher.skipped() as usize > features.len() * 9 / 10);
+        let expected = minimum_hash(features);
+        assert_eq!(hasher.finish().unwrap(), expected);
+        assert_eq!(plain.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_min_hasher_bloom() {
+        let features = log_features();
+        let distinct: HashSet<u32> = features.iter().copied().collect();
+        let mut hasher = MinHasher::with_dedup(FeatureDedup::Bloom {
+            bits: distinct.len() * 16,
+        })
+        .unwrap();
+        for &f in &features {
+            hasher.push_unique(f);
+        }
+        // Repeated features are always skipped, new ones sometimes.
+        assert!(hasher.skipped() as usize >= features.len() - distinct.len());
+        let sketch = hasher.finish().unwrap();
+        assert!(minhash_similarity(&sketch, &minimum_hash(features)) > 0.9);
+
+        match MinHasher::with_dedup(FeatureDedup::Bloom { bits: 0 }) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match MinHasher::new().finish() {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_feature_containment() {
+        assert_eq!(
+            feature_containment(&[1, 2, 2, 3, 4], &[4, 3, 9]).unwrap(),
+            0.5
+        );
+        assert_eq!(feature_containment(&[7, 7, 7], &[7]).unwrap(), 1.0);
+        assert_eq!(feature_containment(&[1], &[]).unwrap(), 0.0);
+        match feature_containment(&[], &[1]) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_sliding_window_units() {
+        // 1-, 2-, 3- and 4-byte characters, 11 bytes and 5 characters.
+        let seq = "aé€😀b";
+        assert_eq!(
+            sliding_window_units(seq, 4, WidthUnit::Chars),
+            vec!["aé€😀", "é€😀b"]
+        );
+        assert_eq!(
+            sliding_window_units(seq, 4, WidthUnit::Bytes),
+            vec!["aé", "é", "€", "😀", "b"]
+        );
+        assert_eq!(
+            sliding_window_units(seq, 6, WidthUnit::Bytes),
+            vec!["aé€", "é€", "€", "😀b"]
+        );
+        // Characters wider than the window are windows of their own.
+        assert_eq!(
+            sliding_window_units(seq, 2, WidthUnit::Bytes),
+            vec!["a", "é", "€", "😀", "b"]
+        );
+        assert_eq!(
+            sliding_window_units("ab😀", 2, WidthUnit::Bytes),
+            vec!["ab", "b", "😀"]
+        );
+        assert_eq!(sliding_window_units(seq, 11, WidthUnit::Bytes), vec![seq]);
+        assert_eq!(sliding_window_units("", 4, WidthUnit::Bytes), vec![""]);
+
+        // On ASCII both units are the same.
+        let ascii = "The quick brown fox";
+        for &width in [2, 4, 13, 19, 30].iter() {
+            assert_eq!(
+                sliding_window_units(ascii, width, WidthUnit::Bytes),
+                sliding_window_units(ascii, width, WidthUnit::Chars)
+            );
+        }
+
+        let mixed = "Ünïcödé テキスト 😀🎉 mixed";
+        for &width in [2, 3, 5, 13].iter() {
+            let chars = sliding_window_units(mixed, width, WidthUnit::Chars);
+            assert_eq!(chars, sliding_window(mixed, width));
+            assert_eq!(chars.len(), mixed.chars().count() - width + 1);
+            let bytes = sliding_window_units(mixed, width, WidthUnit::Bytes);
+            let starts = mixed
+                .char_indices()
+                .filter(|&(i, _)| i <= mixed.len() - width)
+                .count();
+            assert!(bytes.len() == starts || bytes.len() == starts + 1);
+            assert!(mixed.ends_with(bytes.last().unwrap().as_str()));
+            for window in &bytes {
+                assert!(window.len() <= width || window.chars().count() == 1);
+                assert!(mixed.contains(window.as_str()));
+            }
+        }
+    }
+
 }
@@ -0,0 +1,216 @@
+//! Markup Stripping
+//!
+//! A small extractor for the visible text of HTML documents. It is not a full
+//! HTML parser but handles the constructs that matter for text fingerprints:
+//! Tags are replaced by whitespace, the contents of `script` and `style`
+//! elements, comments, doctypes and processing instructions are removed,
+//! CDATA sections are kept as text and character references are decoded.
+
+/// Extracts the visible text of an HTML document.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(['<', '&']) {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with('&') {
+            let (decoded, len) = decode_entity(rest);
+            text.push_str(&decoded);
+            rest = &rest[len..];
+        } else if rest.starts_with("<!--") {
+            rest = skip_past(rest, "-->");
+            text.push(' ');
+        } else if rest.starts_with("<![CDATA[") {
+            let content = &rest["<![CDATA[".len()..];
+            let end = content.find("]]>").unwrap_or(content.len());
+            text.push(' ');
+            text.push_str(&content[..end]);
+            text.push(' ');
+            rest = skip_past(content, "]]>");
+        } else if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = skip_past(rest, ">");
+            text.push(' ');
+        } else if starts_with_letter_or_slash(&rest[1..]) {
+            let tag_len = tag_length(rest);
+            let name = tag_name(&rest[1..tag_len]);
+            let self_closing = rest[..tag_len].ends_with("/>");
+            rest = &rest[tag_len..];
+            if (name == "script" || name == "style") && !self_closing {
+                rest = skip_raw_text(rest, &name);
+            }
+            text.push(' ');
+        } else {
+            // A lone `<` is text
+            text.push('<');
+            rest = &rest[1..];
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn starts_with_letter_or_slash(s: &str) -> bool {
+    s.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '/')
+}
+
+/// Returns the remainder of `s` after the first occurrence of `end`, or an
+/// empty string if `end` does not occur.
+fn skip_past<'a>(s: &'a str, end: &str) -> &'a str {
+    match s.find(end) {
+        Some(pos) => &s[pos + end.len()..],
+        None => "",
+    }
+}
+
+/// Length of the tag at the start of `s` up to and including the closing `>`,
+/// ignoring any `>` within quoted attribute values.
+fn tag_length(s: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return i + 1,
+            None => {}
+        }
+    }
+    s.len()
+}
+
+/// Lower case name of an opening tag, without attributes. Closing tags yield
+/// an empty name.
+fn tag_name(tag: &str) -> String {
+    if tag.starts_with('/') {
+        return String::new();
+    }
+    tag.chars()
+        .take_while(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Skips the raw text content of a `script` or `style` element including its
+/// closing tag.
+fn skip_raw_text<'a>(s: &'a str, name: &str) -> &'a str {
+    let closing = format!("</{}", name);
+    let lower = s.to_ascii_lowercase();
+    match lower.find(&closing) {
+        Some(pos) => {
+            let after = &s[pos..];
+            &after[tag_length(after)..]
+        }
+        None => "",
+    }
+}
+
+/// Decodes the character reference at the start of `s` and returns the
+/// decoded text and the number of bytes consumed. Unknown references are kept
+/// as they are.
+fn decode_entity(s: &str) -> (String, usize) {
+    let end = match s[1..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '#')) {
+        Some(pos) if s[1 + pos..].starts_with(';') => 1 + pos,
+        _ => return ("&".to_string(), 1),
+    };
+    let name = &s[1..end];
+    let hex = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X"));
+    let decoded = if let Some(hex) = hex {
+        u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+    } else if let Some(dec) = name.strip_prefix('#') {
+        dec.parse().ok().and_then(std::char::from_u32)
+    } else {
+        named_entity(name)
+    };
+    match decoded {
+        Some(c) => (c.to_string(), end + 1),
+        None => ("&".to_string(), 1),
+    }
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    let c = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => '\u{00A0}',
+        "shy" => '\u{00AD}',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "hellip" => '…',
+        "mdash" => '—',
+        "ndash" => '–',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "euro" => '€',
+        "auml" => 'ä',
+        "ouml" => 'ö',
+        "uuml" => 'ü',
+        "Auml" => 'Ä',
+        "Ouml" => 'Ö',
+        "Uuml" => 'Ü',
+        "szlig" => 'ß',
+        "eacute" => 'é',
+        "egrave" => 'è',
+        "aacute" => 'á',
+        "agrave" => 'à',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = "<html><head><title>T</title><style>p { color: red; }</style>\
+                    <script type=\"text/javascript\">if (a < b) { x = '</p>'; }</script></head>\
+                    <body><!-- a <b>comment</b> --><p class=\"x > y\">Fish &amp; Chips\
+                    &#8212;&#x263A;</p><![CDATA[raw <text>]]><SCRIPT>hidden</SCRIPT>a < b &bogus;</body></html>";
+        let text = html_to_text(html);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        assert_eq!(
+            words,
+            vec![
+                "T",
+                "Fish",
+                "&",
+                "Chips—☺",
+                "raw",
+                "<text>",
+                "a",
+                "<",
+                "b",
+                "&bogus;"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_html_to_text_nested() {
+        assert_eq!(
+            html_to_text("<div><p>One<b>Two</b></p></div>")
+                .split_whitespace()
+                .collect::<Vec<_>>(),
+            vec!["One", "Two"]
+        );
+        assert_eq!(html_to_text("no markup"), "no markup");
+        assert_eq!(html_to_text("<script>unterminated").trim(), "");
+        assert_eq!(
+            html_to_text("<script src=\"x.js\"/>visible").trim(),
+            "visible"
+        );
+    }
+}
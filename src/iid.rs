@@ -1,49 +1,584 @@
 //! Instance-ID
+use std::convert::TryInto;
+use std::fmt;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::Read;
 
 use hex;
-use ring::digest::{digest, SHA256};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 
 use crate::base58;
+use crate::checkpoint;
+use crate::code::{CodeKind, FullDigest};
+use crate::compare::ct_eq;
+use crate::error::IsccError;
+use crate::metrics::{Metrics, Stage, TimedReader};
+#[cfg(feature = "blake3")]
+use crate::multihash::MULTIHASH_BLAKE3;
+use crate::multihash::{base58btc_encode, multihash, MULTIHASH_MERKLE_SHA2_256D};
+use crate::sha256::sha256;
+#[cfg(feature = "parallel")]
+use crate::sha256::Sha256;
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
 
 const BUF_SIZE: usize = 64000;
 
 // Component Header
 const HEAD_IID: u8 = 0x30;
+#[cfg(feature = "blake3")]
+const HEAD_IID_BLAKE3: u8 = 0x31;
+#[cfg(feature = "parallel")]
+const HEAD_IID_TREE: u8 = 0x32;
+
+/// The leaf size of [`instance_id_tree`].
+#[cfg(feature = "parallel")]
+pub const TREE_LEAF_SIZE: usize = 1 << 20;
+/// The number of leaves [`instance_id_tree`] reads before hashing them in
+/// parallel.
+#[cfg(feature = "parallel")]
+const TREE_BATCH: usize = 64;
+
+/// The hash algorithm used to build the Instance-ID.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceAlgo {
+    /// The hash-tree over 64-kB chunks defined by the ISCC specification.
+    #[default]
+    Default,
+    /// A BLAKE3 hash over the whole data. **This is not part of the ISCC
+    /// specification.** Instance-IDs generated with BLAKE3 carry the header
+    /// `0x31` instead of `0x30` and do not match standard Instance-IDs. The
+    /// returned tophash is the full BLAKE3 hex digest.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
 
 /// The Instance-ID is built from the raw data of the media object to be
 /// identified and serves as checksum for the media object. The raw data of the
 /// media object is split into 64-kB data-chunks. Then we build a hash-tree from
 /// those chunks and use the truncated tophash (merkle root) as component body
 /// of the Instance-ID.
+///
+/// Empty data is hashed as a single empty leaf, so every Instance-ID function
+/// returns the code `CR4ATDsziWVwB` with the tophash `sha256d(0x00)` for it.
 pub fn instance_id(data_path: &str) -> std::io::Result<(String, String)> {
+    instance_id_with_algo(data_path, InstanceAlgo::Default)
+}
+
+/// Generates the Instance-ID like [`instance_id`] and returns it together with
+/// the raw digest.
+pub fn instance_id_result(data_path: &str) -> std::io::Result<InstanceResult> {
+    let file = File::open(data_path)?;
+    hash_reader(file, InstanceAlgo::Default)
+}
+
+/// Generates the Instance-ID of a file with the given hash algorithm.
+pub fn instance_id_with_algo(
+    data_path: &str,
+    algo: InstanceAlgo,
+) -> std::io::Result<(String, String)> {
+    let file = File::open(data_path)?;
+    instance_id_from_reader(file, algo)
+}
+
+/// Generates the Instance-ID from any reader with the given hash algorithm.
+pub fn instance_id_from_reader<R: Read>(
+    data: R,
+    algo: InstanceAlgo,
+) -> std::io::Result<(String, String)> {
+    Ok(hash_reader(data, algo)?.into())
+}
+
+/// Generates the Instance-ID from any reader and measures the generation, see
+/// [`Metrics`].
+pub fn instance_id_with_metrics<R: Read>(data: R) -> Result<(InstanceResult, Metrics), IsccError> {
+    let mut metrics = Metrics::default();
+    let mut reader = TimedReader::new(data);
+    let mut hasher = InstanceHasher::new();
+    let mut buffer = vec![0; BUF_SIZE];
+    loop {
+        let n = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        metrics.time(Stage::CryptoHashing, || hasher.update(&buffer[..n]));
+    }
+    let result = metrics.time(Stage::CryptoHashing, || hasher.finish());
+    metrics.input_bytes = reader.bytes;
+    metrics.add(Stage::Reading, reader.elapsed);
+    Ok((result, metrics))
+}
+
+/// Generates the 256-bit digest of the Instance-ID, the full tophash, see
+/// [`FullDigest`].
+pub fn instance_id_full(data_path: &str) -> std::io::Result<FullDigest> {
+    let file = File::open(data_path)?;
+    let (_, tophash) = feed_reader(file, InstanceAlgo::Default)?.finish_tophash();
+    Ok(FullDigest {
+        kind: CodeKind::Instance,
+        digest: tophash,
+    })
+}
+
+fn hash_reader<R: Read>(data: R, algo: InstanceAlgo) -> std::io::Result<InstanceResult> {
+    Ok(feed_reader(data, algo)?.finish())
+}
+
+fn feed_reader<R: Read>(mut data: R, algo: InstanceAlgo) -> std::io::Result<InstanceHasher> {
+    let mut hasher = InstanceHasher::with_algo(algo);
+    let mut buffer = vec![0; BUF_SIZE];
+    loop {
+        let n = match data.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher)
+}
+
+/// Generates a tree hashed Instance-ID of a file whose leaves are hashed in
+/// parallel, see [`instance_id_tree_from_reader`].
+#[cfg(feature = "parallel")]
+pub fn instance_id_tree(data_path: &str) -> std::io::Result<(String, String)> {
     let file = File::open(data_path)?;
-    let mut leaf_node_digests: Vec<[u8; 32]> = Vec::new();
-    let mut reader = BufReader::with_capacity(BUF_SIZE, file);
-    while let Ok(chunk) = reader.fill_buf() {
-        let n = chunk.len();
-        if n == 0 {
-            break;
+    instance_id_tree_from_reader(file)
+}
+
+/// Generates a tree hashed Instance-ID whose leaves are hashed in parallel.
+/// **This is not part of the ISCC specification.** The codes carry the header
+/// `0x32` and do not match standard Instance-IDs.
+///
+/// The data is split into leaves of [`TREE_LEAF_SIZE`] bytes, the last leaf
+/// may be shorter and empty data is a single empty leaf. The digest of a leaf
+/// is `sha256d(0x00 || leaf)` and the tophash is `sha256d(0x01 || digest_1 ||
+/// ... || digest_n)` over the leaf digests in order. The result thus does not
+/// depend on the number of threads. The code body is the first 8 bytes of the
+/// tophash, the tophash is returned hex encoded.
+#[cfg(feature = "parallel")]
+pub fn instance_id_tree_from_reader<R: Read>(mut data: R) -> std::io::Result<(String, String)> {
+    let mut top = Sha256::new();
+    top.update(&[1]);
+    let mut first = true;
+    let mut eof = false;
+    while !eof {
+        let mut leaves: Vec<Vec<u8>> = Vec::with_capacity(TREE_BATCH);
+        while !eof && leaves.len() < TREE_BATCH {
+            let mut leaf = Vec::with_capacity(TREE_LEAF_SIZE);
+            data.by_ref()
+                .take(TREE_LEAF_SIZE as u64)
+                .read_to_end(&mut leaf)?;
+            eof = leaf.len() < TREE_LEAF_SIZE;
+            if !leaf.is_empty() || first {
+                leaves.push(leaf);
+            }
+            first = false;
+        }
+        let digests: Vec<[u8; 32]> = leaves.par_iter().map(|l| hash_leaf_node(l)).collect();
+        for leaf_digest in digests.iter() {
+            top.update(leaf_digest);
+        }
+    }
+    let tophash = sha256(&top.finish());
+
+    let mut instance_id_digest = vec![HEAD_IID_TREE];
+    instance_id_digest.extend(&tophash[..8]);
+    Ok((base58::encode(&instance_id_digest), hex::encode(tophash)))
+}
+
+/// The Instance-ID and/or tophash a file is expected to match, see
+/// [`instance_verify`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceExpectation {
+    /// An Instance-ID like `CR6xpnrJkvQDH`.
+    pub code: Option<String>,
+    /// A hex encoded tophash.
+    pub tophash: Option<String>,
+}
+
+impl InstanceExpectation {
+    /// Expects the data to match an Instance-ID.
+    pub fn code(code: &str) -> InstanceExpectation {
+        InstanceExpectation {
+            code: Some(code.to_string()),
+            tophash: None,
+        }
+    }
+
+    /// Expects the data to match a hex encoded tophash.
+    pub fn tophash(tophash: &str) -> InstanceExpectation {
+        InstanceExpectation {
+            code: None,
+            tophash: Some(tophash.to_string()),
+        }
+    }
+
+    /// Expects the data to match both an Instance-ID and a tophash.
+    pub fn both(code: &str, tophash: &str) -> InstanceExpectation {
+        InstanceExpectation {
+            code: Some(code.to_string()),
+            tophash: Some(tophash.to_string()),
+        }
+    }
+}
+
+/// The result of [`instance_verify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyOutcome {
+    /// All provided expectations matched.
+    Match,
+    /// At least one provided expectation did not match. Holds whether the
+    /// code and the tophash matched, `None` if it was not provided.
+    Mismatch {
+        code: Option<bool>,
+        tophash: Option<bool>,
+    },
+}
+
+/// Parses a hex encoded 32-byte tophash. Upper, lower and mixed case are
+/// accepted, tophashes are always generated in lower case.
+pub fn parse_tophash(tophash: &str) -> Result<[u8; 32], IsccError> {
+    let bytes = hex::decode(tophash)
+        .map_err(|e| IsccError::InvalidArgument(format!("tophash is not hex encoded: {}", e)))?;
+    if bytes.len() != 32 {
+        return Err(IsccError::LengthMismatch {
+            expected: 32,
+            actual: bytes.len(),
+        });
+    }
+    let mut digest = [0; 32];
+    digest.copy_from_slice(&bytes);
+    Ok(digest)
+}
+
+/// Checks whether the data still matches a previously recorded Instance-ID
+/// and/or tophash. The data is read once. The hash algorithm is derived from
+/// the header of the expected code, the default algorithm is used if only a
+/// tophash is given. Digests are compared in constant time with [`ct_eq`].
+///
+/// Returns an error if the expectation is empty or malformed, i.e. the code is
+/// no valid Instance-ID or the tophash is no 32 byte hex string.
+pub fn instance_verify(
+    data: impl Read,
+    expected: &InstanceExpectation,
+) -> Result<VerifyOutcome, IsccError> {
+    let code_digest = match &expected.code {
+        Some(code) => Some(base58::try_decode_component(code)?),
+        None => None,
+    };
+    let tophash = match &expected.tophash {
+        Some(tophash) => Some(parse_tophash(tophash)?),
+        None => None,
+    };
+    let algo = match code_digest.as_ref().map(|digest| digest[0]) {
+        None | Some(HEAD_IID) => InstanceAlgo::Default,
+        #[cfg(feature = "blake3")]
+        Some(HEAD_IID_BLAKE3) => InstanceAlgo::Blake3,
+        Some(header) => {
+            return Err(IsccError::InvalidArgument(format!(
+                "header {:#04x} is not an Instance-ID header",
+                header
+            )))
         }
-        let zero = &[0];
-        let hash = sha256d(&[zero, chunk].concat());
-        leaf_node_digests.push(hash);
-        reader.consume(n);
+    };
+    if code_digest.is_none() && tophash.is_none() {
+        return Err(IsccError::InvalidArgument(
+            "expectation holds neither code nor tophash".to_string(),
+        ));
+    }
+
+    let result = hash_reader(data, algo)?;
+    let code = code_digest.map(|digest| ct_eq(&digest[1..], &result.digest));
+    let tophash = tophash.map(|tophash| {
+        let actual = hex::decode(&result.tophash).expect("tophash is valid hex");
+        ct_eq(&tophash, &actual)
+    });
+    if code.unwrap_or(true) && tophash.unwrap_or(true) {
+        Ok(VerifyOutcome::Match)
+    } else {
+        Ok(VerifyOutcome::Mismatch { code, tophash })
+    }
+}
+
+/// The Instance-ID together with the tophash it was built from. Displays as
+/// the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstanceResult {
+    pub code: String,
+    /// Hex encoded tophash
+    pub tophash: String,
+    pub algo: InstanceAlgo,
+    /// The raw Instance-ID body without the header byte, i.e. the truncated
+    /// tophash.
+    pub digest: Vec<u8>,
+    /// The version of the algorithm that generated the code.
+    pub version: AlgorithmVersion,
+}
+
+impl fmt::Display for InstanceResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.code)
+    }
+}
+
+impl InstanceResult {
+    /// The tophash as [multihash](https://github.com/multiformats/multihash).
+    /// The tophash of the default algorithm is the root of a Merkle tree of
+    /// double SHA2-256 digests, see [`top_hash`], and is tagged with the
+    /// private-use code [`MULTIHASH_MERKLE_SHA2_256D`]. BLAKE3 tophashes are
+    /// the `blake3` (`0x1e`) of the content.
+    pub fn tophash_multihash(&self) -> Vec<u8> {
+        let code = match self.algo {
+            InstanceAlgo::Default => MULTIHASH_MERKLE_SHA2_256D,
+            #[cfg(feature = "blake3")]
+            InstanceAlgo::Blake3 => MULTIHASH_BLAKE3,
+        };
+        let digest = hex::decode(&self.tophash).expect("tophash is valid hex");
+        multihash(code, &digest)
     }
 
-    let top_hash_digest = top_hash(&leaf_node_digests);
+    /// The hex encoded multihash of the tophash.
+    pub fn tophash_multihash_hex(&self) -> String {
+        hex::encode(self.tophash_multihash())
+    }
+
+    /// The base58btc encoded multihash of the tophash.
+    pub fn tophash_multihash_base58btc(&self) -> String {
+        base58btc_encode(&self.tophash_multihash())
+    }
+}
 
-    let mut instance_id_digest = vec![HEAD_IID];
-    instance_id_digest.extend(&top_hash_digest[..8]);
+impl From<InstanceResult> for (String, String) {
+    fn from(result: InstanceResult) -> (String, String) {
+        (result.code, result.tophash)
+    }
+}
 
-    let code = base58::encode(&instance_id_digest);
-    let hex_hash = hex::encode(top_hash_digest);
+/// Incremental Instance-ID generation for data that arrives in pieces. The
+/// result does not depend on how the data is split across `update` calls.
+///
+/// Completed leaves are merged into the hash-tree right away, so the state
+/// only holds the current leaf and one node per level of the tree. It can be
+/// saved with [`InstanceHasher::checkpoint`] and restored with
+/// [`InstanceHasher::resume`] to continue hashing in another process.
+pub struct InstanceHasher {
+    algo: InstanceAlgo,
+    state: HasherState,
+}
 
-    Ok((code, hex_hash))
+enum HasherState {
+    Default {
+        leaf: Vec<u8>,
+        /// The number of completed leaves.
+        leaves: u64,
+        /// The roots of the perfect subtrees over the completed leaves, one
+        /// for every set bit of `leaves`, the biggest subtree first.
+        subtrees: Vec<[u8; 32]>,
+    },
+    #[cfg(feature = "blake3")]
+    Blake3(Box<blake3::Hasher>),
 }
 
+impl InstanceHasher {
+    pub fn new() -> InstanceHasher {
+        InstanceHasher::with_algo(InstanceAlgo::Default)
+    }
+
+    pub fn with_algo(algo: InstanceAlgo) -> InstanceHasher {
+        let state = match algo {
+            InstanceAlgo::Default => HasherState::Default {
+                leaf: Vec::with_capacity(BUF_SIZE),
+                leaves: 0,
+                subtrees: Vec::new(),
+            },
+            #[cfg(feature = "blake3")]
+            InstanceAlgo::Blake3 => HasherState::Blake3(Box::new(blake3::Hasher::new())),
+        };
+        InstanceHasher { algo, state }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        match &mut self.state {
+            HasherState::Default {
+                leaf,
+                leaves,
+                subtrees,
+            } => {
+                while !data.is_empty() {
+                    let n = (BUF_SIZE - leaf.len()).min(data.len());
+                    leaf.extend(&data[..n]);
+                    data = &data[n..];
+                    if leaf.len() == BUF_SIZE {
+                        push_leaf(leaves, subtrees, hash_leaf_node(leaf));
+                        leaf.clear();
+                    }
+                }
+            }
+            #[cfg(feature = "blake3")]
+            HasherState::Blake3(hasher) => {
+                hasher.update_rayon(data);
+            }
+        }
+    }
+
+    /// Returns the Instance-ID and the hex encoded tophash.
+    pub fn finish(self) -> InstanceResult {
+        let algo = self.algo;
+        let (header, top_hash_digest) = self.finish_tophash();
+
+        let mut instance_id_digest = vec![header];
+        instance_id_digest.extend(&top_hash_digest[..8]);
+
+        InstanceResult {
+            code: base58::encode(&instance_id_digest),
+            tophash: hex::encode(top_hash_digest),
+            algo,
+            digest: top_hash_digest[..8].to_vec(),
+            version: ALGORITHM_VERSION,
+        }
+    }
+
+    /// Returns the header and the full tophash.
+    fn finish_tophash(self) -> (u8, [u8; 32]) {
+        match self.state {
+            HasherState::Default {
+                leaf,
+                mut leaves,
+                mut subtrees,
+            } => {
+                // Empty data is hashed as a single empty leaf.
+                if !leaf.is_empty() || leaves == 0 {
+                    push_leaf(&mut leaves, &mut subtrees, hash_leaf_node(&leaf));
+                }
+                (HEAD_IID, merge_subtrees(leaves, subtrees))
+            }
+            #[cfg(feature = "blake3")]
+            HasherState::Blake3(hasher) => (HEAD_IID_BLAKE3, *hasher.finalize().as_bytes()),
+        }
+    }
+}
+
+const INSTANCE_CHECKPOINT_MAGIC: &[u8; 4] = b"ISIH";
+
+impl InstanceHasher {
+    /// Serializes the state of the hasher. The checkpoint consists of
+    ///
+    /// * the magic `ISIH` and the format version `1` (1 byte),
+    /// * the algorithm (1 byte, `0` for the default algorithm),
+    /// * the number of completed 64-kB leaves (`u64`),
+    /// * the 32-byte roots of the perfect subtrees over the completed leaves,
+    ///   one for every set bit of the number of leaves, the biggest first,
+    /// * the length (`u32`) and the bytes of the current leaf,
+    /// * the `xxhash64` of all preceding bytes (`u64`).
+    ///
+    /// All integers are little endian. BLAKE3 hashers do not expose their
+    /// state and return an error.
+    pub fn checkpoint(&self) -> Result<Vec<u8>, IsccError> {
+        match &self.state {
+            HasherState::Default {
+                leaf,
+                leaves,
+                subtrees,
+            } => {
+                let mut writer = checkpoint::Writer::new(INSTANCE_CHECKPOINT_MAGIC);
+                writer.u8(0);
+                writer.u64(*leaves);
+                for subtree in subtrees.iter() {
+                    writer.raw(subtree);
+                }
+                writer.bytes(leaf);
+                Ok(writer.finish())
+            }
+            #[cfg(feature = "blake3")]
+            HasherState::Blake3(_) => Err(IsccError::InvalidArgument(
+                "BLAKE3 hashers can not be checkpointed".to_string(),
+            )),
+        }
+    }
+
+    /// Restores a hasher from an [`InstanceHasher::checkpoint`]. Returns
+    /// [`IsccError::InvalidCheckpoint`] if the checkpoint is corrupted.
+    pub fn resume(bytes: &[u8]) -> Result<InstanceHasher, IsccError> {
+        let mut reader = checkpoint::Reader::new(bytes, INSTANCE_CHECKPOINT_MAGIC)?;
+        if reader.u8()? != 0 {
+            return Err(checkpoint::invalid("unknown algorithm"));
+        }
+        let leaves = reader.u64()?;
+        let mut subtrees = Vec::new();
+        for _ in 0..leaves.count_ones() {
+            subtrees.push(reader.raw(32)?.try_into().unwrap());
+        }
+        let leaf = reader.bytes()?;
+        reader.finish()?;
+        if leaf.len() >= BUF_SIZE {
+            return Err(checkpoint::invalid("current leaf exceeds the leaf size"));
+        }
+
+        let mut current = Vec::with_capacity(BUF_SIZE);
+        current.extend(leaf);
+        Ok(InstanceHasher {
+            algo: InstanceAlgo::Default,
+            state: HasherState::Default {
+                leaf: current,
+                leaves,
+                subtrees,
+            },
+        })
+    }
+}
+
+impl Default for InstanceHasher {
+    fn default() -> InstanceHasher {
+        InstanceHasher::new()
+    }
+}
+
+/// Adds a leaf to the perfect subtrees, merging subtrees of equal size.
+fn push_leaf(leaves: &mut u64, subtrees: &mut Vec<[u8; 32]>, digest: [u8; 32]) {
+    let mut node = digest;
+    let mut count = *leaves;
+    while count & 1 == 1 {
+        let left = subtrees.pop().expect("one subtree per set bit");
+        node = hash_inner_nodes(&left, &node);
+        count >>= 1;
+    }
+    subtrees.push(node);
+    *leaves += 1;
+}
+
+/// Computes the same tophash as [`top_hash`] over all leaves. Like there, a
+/// node without a sibling is hashed with itself.
+fn merge_subtrees(leaves: u64, mut subtrees: Vec<[u8; 32]>) -> [u8; 32] {
+    let mut levels = (0..64).filter(|level| (leaves >> level) & 1 == 1);
+    let mut node = subtrees.pop().expect("at least one leaf");
+    let mut level = levels.next().unwrap();
+    while let Some(left) = subtrees.pop() {
+        let left_level = levels.next().unwrap();
+        while level < left_level {
+            node = hash_inner_nodes(&node, &node);
+            level += 1;
+        }
+        node = hash_inner_nodes(&left, &node);
+        level += 1;
+    }
+    node
+}
+
+fn hash_leaf_node(chunk: &[u8]) -> [u8; 32] {
+    let zero = &[0];
+    sha256d(&[zero, chunk].concat())
+}
+
+/// The merkle root over the leaf digests `hashes`. No digests are treated like
+/// the single empty leaf of empty data.
 pub fn top_hash(hashes: &[[u8; 32]]) -> [u8; 32] {
+    if hashes.is_empty() {
+        return hash_leaf_node(&[]);
+    }
     if hashes.len() == 1 {
         return hashes[0];
     }
@@ -64,9 +599,262 @@ fn hash_inner_nodes(a: &[u8], b: &[u8]) -> [u8; 32] {
 }
 
 pub fn sha256d(data: &[u8]) -> [u8; 32] {
-    let hash1 = digest(&SHA256, data);
-    let hash2 = digest(&SHA256, hash1.as_ref());
-    let mut arr: [u8; 32] = Default::default();
-    arr.copy_from_slice(hash2.as_ref());
-    arr
+    sha256(&sha256(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use crate::multihash::parse_multihash;
+
+    fn incremental(data: &[u8], piece: usize, algo: InstanceAlgo) -> (String, String) {
+        let mut hasher = InstanceHasher::with_algo(algo);
+        for chunk in data.chunks(piece) {
+            hasher.update(chunk);
+        }
+        hasher.finish().into()
+    }
+
+    #[test]
+    fn test_instance_id_with_metrics() {
+        let path = "tests/test_data/lenna.jpg";
+        let (result, metrics) = instance_id_with_metrics(File::open(path).unwrap()).unwrap();
+        assert_eq!(result, instance_id_result(path).unwrap());
+        assert_eq!(metrics.input_bytes, fs::metadata(path).unwrap().len());
+        assert_eq!((metrics.features, metrics.chunks), (0, 0));
+        let stages: Vec<Stage> = metrics.stages.iter().map(|(s, _)| *s).collect();
+        assert_eq!(stages, vec![Stage::Reading, Stage::CryptoHashing]);
+    }
+
+    #[test]
+    fn test_instance_paths_agree() {
+        let path = "tests/test_data/test-70k.txt";
+        let data = fs::read(path).unwrap();
+        let expected = instance_id(path).unwrap();
+        assert_eq!(
+            instance_id_from_reader(&data[..], InstanceAlgo::Default).unwrap(),
+            expected
+        );
+        for &piece in [1, 999, 64000, 100_000].iter() {
+            assert_eq!(incremental(&data, piece, InstanceAlgo::Default), expected);
+        }
+    }
+
+    #[test]
+    fn test_instance_verify() {
+        let data = fs::read("tests/test_data/cat.png").unwrap();
+        let code = "CR6xpnrJkvQDH";
+        let tophash = "23a5b78a044143b612a0d033384a5ebf95343ec812c3e62caff393852d0328a0";
+        let both = InstanceExpectation::both(code, tophash);
+        assert_eq!(
+            instance_verify(&data[..], &both).unwrap(),
+            VerifyOutcome::Match
+        );
+        assert_eq!(
+            instance_verify(&data[..], &InstanceExpectation::code(code)).unwrap(),
+            VerifyOutcome::Match
+        );
+        let upper = InstanceExpectation::tophash(&tophash.to_uppercase());
+        assert_eq!(
+            instance_verify(&data[..], &upper).unwrap(),
+            VerifyOutcome::Match
+        );
+        assert_eq!(
+            parse_tophash(&tophash.to_uppercase()).unwrap(),
+            parse_tophash(tophash).unwrap()
+        );
+
+        let mut flipped = data.clone();
+        flipped[1000] ^= 1;
+        assert_eq!(
+            instance_verify(&flipped[..], &both).unwrap(),
+            VerifyOutcome::Mismatch {
+                code: Some(false),
+                tophash: Some(false)
+            }
+        );
+        assert_eq!(
+            instance_verify(&flipped[..], &InstanceExpectation::code(code)).unwrap(),
+            VerifyOutcome::Mismatch {
+                code: Some(false),
+                tophash: None
+            }
+        );
+        let other = InstanceExpectation::both(code, &"00".repeat(32));
+        assert_eq!(
+            instance_verify(&data[..], &other).unwrap(),
+            VerifyOutcome::Mismatch {
+                code: Some(true),
+                tophash: Some(false)
+            }
+        );
+    }
+
+    #[test]
+    fn test_instance_verify_malformed() {
+        let data = b"data";
+        let short = InstanceExpectation::tophash("23a5b78a044143b6");
+        match instance_verify(&data[..], &short) {
+            Err(IsccError::LengthMismatch { expected, actual }) => {
+                assert_eq!((expected, actual), (32, 8))
+            }
+            other => panic!("{:?}", other),
+        }
+        let not_hex = InstanceExpectation::tophash(&"zz".repeat(32));
+        assert!(instance_verify(&data[..], &not_hex).is_err());
+        let meta_id = InstanceExpectation::code("CCAKevDpE1eEL");
+        assert!(instance_verify(&data[..], &meta_id).is_err());
+        let invalid = InstanceExpectation::code("CR6xpnrJkvQD0");
+        assert!(instance_verify(&data[..], &invalid).is_err());
+        assert!(instance_verify(&data[..], &InstanceExpectation::default()).is_err());
+    }
+
+    #[test]
+    fn test_merge_subtrees() {
+        for n in 1..40 {
+            let digests: Vec<[u8; 32]> = (0..n).map(|i| sha256d(&[i as u8])).collect();
+            let (mut leaves, mut subtrees) = (0, Vec::new());
+            for digest in digests.iter() {
+                push_leaf(&mut leaves, &mut subtrees, *digest);
+            }
+            assert_eq!(subtrees.len(), leaves.count_ones() as usize);
+            assert_eq!(
+                merge_subtrees(leaves, subtrees),
+                top_hash(&digests),
+                "{}",
+                n
+            );
+        }
+        let empty = instance_id_from_reader(&b""[..], InstanceAlgo::Default).unwrap();
+        assert_eq!(empty.1, hex::encode(hash_leaf_node(&[])));
+    }
+
+    #[test]
+    fn test_instance_checkpoint() {
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let expected = instance_id_from_reader(&data[..], InstanceAlgo::Default).unwrap();
+        for &step in [7_777, 63_999, 64_000, 100_003].iter() {
+            let mut hasher = InstanceHasher::new();
+            for chunk in data.chunks(step) {
+                hasher.update(chunk);
+                hasher = InstanceHasher::resume(&hasher.checkpoint().unwrap()).unwrap();
+            }
+            let result: (String, String) = hasher.finish().into();
+            assert_eq!(result, expected);
+        }
+
+        let mut hasher = InstanceHasher::new();
+        hasher.update(&data[..200_000]);
+        let checkpoint = hasher.checkpoint().unwrap();
+        let positions = (0..200).chain((200..checkpoint.len()).step_by(499));
+        for i in positions {
+            let mut corrupted = checkpoint.clone();
+            corrupted[i] ^= 0x10;
+            assert!(InstanceHasher::resume(&corrupted).is_err(), "byte {}", i);
+        }
+        assert!(InstanceHasher::resume(&checkpoint[..100]).is_err());
+        let data_checkpoint = crate::did::DataHasher::new().checkpoint();
+        assert!(InstanceHasher::resume(&data_checkpoint).is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_instance_id_tree() {
+        // sha256d(0x01 || sha256d(0x00 || data)) for data below one leaf
+        let (code, tophash) = instance_id_tree("tests/test_data/lenna.jpg").unwrap();
+        assert_eq!(code, "Cs9NKxPK6WzW9");
+        assert_eq!(
+            tophash,
+            "33498bf9e297535601224c6e0306cc63a154a1e6effeb1790d83fd360239b33d"
+        );
+        let data = fs::read("tests/test_data/lenna.jpg").unwrap();
+        let expected = sha256d(&[&[1], &hash_leaf_node(&data)[..]].concat());
+        assert_eq!(tophash, hex::encode(expected));
+        assert_eq!(base58::decode(&code)[0], HEAD_IID_TREE);
+
+        let (_, empty) = instance_id_tree_from_reader(&b""[..]).unwrap();
+        let expected = sha256d(&[&[1], &hash_leaf_node(&[])[..]].concat());
+        assert_eq!(empty, hex::encode(expected));
+
+        // More than one batch of leaves with a partial last leaf
+        let data: Vec<u8> = (0..(TREE_BATCH + 2) * TREE_LEAF_SIZE + 5)
+            .map(|i| (i % 253) as u8)
+            .collect();
+        let mut results = Vec::new();
+        for &threads in [1, 2, 8].iter() {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            results.push(pool.install(|| instance_id_tree_from_reader(&data[..]).unwrap()));
+        }
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0], results[2]);
+        assert_eq!(
+            results[0].1,
+            "d2e655235f4972f7b1a1d4b835e30388c98a73d3f46771597f0c3661483c3808"
+        );
+
+        let exact = &data[..2 * TREE_LEAF_SIZE];
+        let digests: Vec<u8> = exact
+            .chunks(TREE_LEAF_SIZE)
+            .flat_map(|leaf| hash_leaf_node(leaf).to_vec())
+            .collect();
+        let expected = sha256d(&[&[1], &digests[..]].concat());
+        assert_eq!(
+            instance_id_tree_from_reader(exact).unwrap().1,
+            hex::encode(expected)
+        );
+    }
+
+    #[test]
+    fn test_tophash_multihash() {
+        let data = fs::read("tests/test_data/cat.jpg").unwrap();
+        let mut hasher = InstanceHasher::new();
+        hasher.update(&data);
+        let result = hasher.finish();
+        let tophash = "f8e5e94f953709ae8930220da8bada303a370a46157a5cdd50ad2476a7f51e42";
+        assert_eq!(
+            result.tophash_multihash_hex(),
+            format!("8080c00120{}", tophash)
+        );
+        assert_eq!(
+            result.tophash_multihash_base58btc(),
+            "5JnzKyJaMwW5uChLR1dXyPGsEyyxEmp2NLVAh6vS1P8KTaoWPjX"
+        );
+        let (code, digest) = parse_multihash(&result.tophash_multihash()).unwrap();
+        assert_eq!(code, MULTIHASH_MERKLE_SHA2_256D);
+        assert_eq!(hex::encode(digest), tophash);
+    }
+
+    #[cfg(feature = "blake3")]
+    #[test]
+    fn test_instance_blake3() {
+        let (code, tophash) = incremental(b"abc", 1, InstanceAlgo::Blake3);
+        assert_eq!(
+            tophash,
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert!(code.starts_with("Cq"));
+        let mut hasher = InstanceHasher::with_algo(InstanceAlgo::Blake3);
+        hasher.update(b"abc");
+        assert_eq!(
+            hasher.finish().tophash_multihash_hex(),
+            format!("1e20{}", tophash)
+        );
+
+        let path = "tests/test_data/test-70k.txt";
+        let data = fs::read(path).unwrap();
+        let expected = instance_id_with_algo(path, InstanceAlgo::Blake3).unwrap();
+        assert_eq!(expected.1, hex::encode(blake3::hash(&data).as_bytes()));
+        assert_eq!(
+            instance_id_from_reader(&data[..], InstanceAlgo::Blake3).unwrap(),
+            expected
+        );
+        assert_eq!(incremental(&data, 4096, InstanceAlgo::Blake3), expected);
+        assert_ne!(expected, instance_id(path).unwrap());
+    }
 }
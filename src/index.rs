@@ -0,0 +1,448 @@
+//! Similarity Indexes
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::error::IsccError;
+use crate::hashes::{minhash_from_bytes, minhash_similarity, minhash_to_bytes, xxhash64};
+use crate::tables::MINHASH_PERMUTATIONS;
+
+/// An index for MinHash sketches (see [`minimum_hash`](crate::hashes::minimum_hash))
+/// based on locality sensitive hashing with the banding technique.
+///
+/// Every sketch is split into `bands` bands of `rows` consecutive values. Two
+/// sketches become candidates for each other if all values of at least one
+/// band are equal. For sketches with Jaccard similarity `s` this happens with
+/// probability `1 - (1 - s^rows)^bands`.
+pub struct MinHashIndex {
+    rows: usize,
+    tables: Vec<HashMap<u64, Vec<u64>>>,
+    sketches: HashMap<u64, Vec<u32>>,
+}
+
+impl MinHashIndex {
+    /// Creates an empty index. `bands * rows` must equal the sketch length of
+    /// 64.
+    pub fn new(bands: usize, rows: usize) -> Result<MinHashIndex, IsccError> {
+        if bands.checked_mul(rows) != Some(MINHASH_PERMUTATIONS.len()) {
+            return Err(IsccError::InvalidArgument(format!(
+                "bands * rows must be {}, not {} * {}",
+                MINHASH_PERMUTATIONS.len(),
+                bands,
+                rows
+            )));
+        }
+        Ok(MinHashIndex {
+            rows,
+            tables: vec![HashMap::new(); bands],
+            sketches: HashMap::new(),
+        })
+    }
+
+    /// Adds a sketch to the index. Inserting an existing id again replaces
+    /// its sketch.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sketch` does not have 64 values.
+    pub fn insert(&mut self, id: u64, sketch: &[u32]) {
+        self.check_length(sketch);
+        if let Some(old) = self.sketches.remove(&id) {
+            for (table, band) in self.tables.iter_mut().zip(band_hashes(&old, self.rows)) {
+                if let Some(ids) = table.get_mut(&band) {
+                    ids.retain(|&other| other != id);
+                }
+            }
+        }
+        for (table, band) in self.tables.iter_mut().zip(band_hashes(sketch, self.rows)) {
+            table.entry(band).or_insert_with(Vec::new).push(id);
+        }
+        self.sketches.insert(id, sketch.to_vec());
+    }
+
+    /// Returns the ids of all sketches that share at least one band with
+    /// `sketch`, in ascending order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sketch` does not have 64 values.
+    pub fn query(&self, sketch: &[u32]) -> Vec<u64> {
+        self.check_length(sketch);
+        let mut candidates: HashSet<u64> = HashSet::new();
+        for (table, band) in self.tables.iter().zip(band_hashes(sketch, self.rows)) {
+            if let Some(ids) = table.get(&band) {
+                candidates.extend(ids);
+            }
+        }
+        let mut candidates: Vec<u64> = candidates.into_iter().collect();
+        candidates.sort_unstable();
+        candidates
+    }
+
+    /// Returns the candidates of [`MinHashIndex::query`] with their estimated
+    /// Jaccard similarity if it is at least `min_jaccard`, most similar first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sketch` does not have 64 values.
+    pub fn query_above(&self, sketch: &[u32], min_jaccard: f64) -> Vec<(u64, f64)> {
+        let mut results: Vec<(u64, f64)> = self
+            .query(sketch)
+            .into_iter()
+            .map(|id| (id, minhash_similarity(sketch, &self.sketches[&id])))
+            .filter(|&(_, similarity)| similarity >= min_jaccard)
+            .collect();
+        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        results
+    }
+
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    fn check_length(&self, sketch: &[u32]) {
+        assert_eq!(
+            sketch.len(),
+            self.tables.len() * self.rows,
+            "Sketch must have {} values.",
+            self.tables.len() * self.rows
+        );
+    }
+}
+
+/// A byte-oriented key-value store for MinHash sketches, see
+/// [`insert_sketch`] and [`query_similar`]. All keys and values are owned
+/// bytes, so the trait can be implemented outside of this crate for any
+/// key-value database with prefix scans, e.g. RocksDB or sled. Errors of the
+/// database are reported as [`IsccError`], e.g. as [`IsccError::Io`].
+///
+/// The keys written by this crate are
+///
+/// * `s` and the id (`u64`) for the sketch of an id, the value is the sketch
+///   serialized with [`minhash_to_bytes`],
+/// * `b`, the band index (1 byte), the band hash (`u64`) and the id (`u64`)
+///   for every band of a sketch, the value is empty.
+///
+/// All integers are big endian, so the keys of the candidates of a band are
+/// adjacent in stores that are ordered by key.
+pub trait SketchStore {
+    /// Stores `value` under `key`, replacing a previous value.
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), IsccError>;
+    /// Returns the value stored under `key`.
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, IsccError>;
+    /// Returns all entries whose key starts with `prefix`, in any order.
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<StoreEntry>, IsccError>;
+}
+
+/// A key of a [`SketchStore`] and its value.
+pub type StoreEntry = (Vec<u8>, Vec<u8>);
+
+/// The number of values per band of the sketches in a [`SketchStore`]. With
+/// `32` bands of `2` values, sketches with a Jaccard similarity of `0.3` are
+/// candidates for each other with a probability of 95%, sketches with a
+/// similarity of `0.5` with a probability of 99.99%.
+pub const STORE_BAND_ROWS: usize = 2;
+
+/// Adds the sketch of `id` to the store. Inserting an existing id again
+/// replaces its sketch, the bands of the old sketch remain in the store and
+/// only cost an extra candidate in [`query_similar`]. Returns an error if the
+/// sketch does not have 64 values.
+pub fn insert_sketch<S: SketchStore + ?Sized>(
+    store: &mut S,
+    id: u64,
+    sketch: &[u32],
+) -> Result<(), IsccError> {
+    check_sketch(sketch)?;
+    for (band, hash) in band_hashes(sketch, STORE_BAND_ROWS).enumerate() {
+        let mut key = band_prefix(band, hash);
+        key.extend(&id.to_be_bytes());
+        store.put(&key, &[])?;
+    }
+    store.put(&sketch_key(id), &minhash_to_bytes(sketch))
+}
+
+/// Returns the ids of the stored sketches whose estimated Jaccard similarity
+/// with `sketch` is at least `min_jaccard`, most similar first.
+///
+/// The candidates are the sketches that share at least one band with `sketch`
+/// (see [`STORE_BAND_ROWS`]) and are ranked by [`minhash_similarity`] of the
+/// full sketches. Returns an error if the sketch does not have 64 values or a
+/// stored sketch is corrupted.
+pub fn query_similar<S: SketchStore + ?Sized>(
+    store: &S,
+    sketch: &[u32],
+    min_jaccard: f64,
+) -> Result<Vec<(u64, f64)>, IsccError> {
+    check_sketch(sketch)?;
+    let mut candidates: HashSet<u64> = HashSet::new();
+    for (band, hash) in band_hashes(sketch, STORE_BAND_ROWS).enumerate() {
+        let prefix = band_prefix(band, hash);
+        for (key, _) in store.scan(&prefix)? {
+            candidates.insert(id_suffix(&key)?);
+        }
+    }
+    let mut results = Vec::new();
+    for id in candidates {
+        let stored = match store.get(&sketch_key(id))? {
+            Some(bytes) => minhash_from_bytes(&bytes)?,
+            None => continue,
+        };
+        if stored.len() != sketch.len() {
+            return Err(IsccError::InvalidSketch(format!(
+                "sketch of id {} has {} values",
+                id,
+                stored.len()
+            )));
+        }
+        let similarity = minhash_similarity(sketch, &stored);
+        if similarity >= min_jaccard {
+            results.push((id, similarity));
+        }
+    }
+    results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    Ok(results)
+}
+
+fn check_sketch(sketch: &[u32]) -> Result<(), IsccError> {
+    if sketch.len() != MINHASH_PERMUTATIONS.len() {
+        return Err(IsccError::InvalidArgument(format!(
+            "a sketch must have {} values, not {}",
+            MINHASH_PERMUTATIONS.len(),
+            sketch.len()
+        )));
+    }
+    Ok(())
+}
+
+fn sketch_key(id: u64) -> Vec<u8> {
+    let mut key = vec![b's'];
+    key.extend(&id.to_be_bytes());
+    key
+}
+
+fn band_prefix(band: usize, hash: u64) -> Vec<u8> {
+    let mut key = vec![b'b', band as u8];
+    key.extend(&hash.to_be_bytes());
+    key
+}
+
+/// The id at the end of a band key.
+fn id_suffix(key: &[u8]) -> Result<u64, IsccError> {
+    if key.len() != 18 {
+        return Err(IsccError::InvalidSketch(format!(
+            "band key of {} bytes",
+            key.len()
+        )));
+    }
+    let mut id = [0; 8];
+    id.copy_from_slice(&key[10..]);
+    Ok(u64::from_be_bytes(id))
+}
+
+/// A [`SketchStore`] in memory, ordered by key.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySketchStore {
+    entries: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MemorySketchStore {
+    pub fn new() -> MemorySketchStore {
+        MemorySketchStore::default()
+    }
+
+    /// The number of entries, i.e. keys.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl SketchStore for MemorySketchStore {
+    fn put(&mut self, key: &[u8], value: &[u8]) -> Result<(), IsccError> {
+        self.entries.insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, IsccError> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<StoreEntry>, IsccError> {
+        Ok(self
+            .entries
+            .range(prefix.to_vec()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect())
+    }
+}
+
+/// The `xxhash64` of the little endian bytes of the values of every band.
+fn band_hashes(sketch: &[u32], rows: usize) -> impl Iterator<Item = u64> + '_ {
+    sketch.chunks(rows).map(|band| {
+        let bytes: Vec<u8> = band.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+        xxhash64(&bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hashes::{minimum_hash, xxhash32};
+
+    fn features(seed: u32, n: u32) -> Vec<u32> {
+        (0..n)
+            .map(|i| xxhash32(&[seed.to_le_bytes(), i.to_le_bytes()].concat()))
+            .collect()
+    }
+
+    #[test]
+    fn test_minhash_index() {
+        for &(bands, rows) in [(16, 4), (32, 2), (8, 8)].iter() {
+            let mut index = MinHashIndex::new(bands, rows).unwrap();
+            for id in 0..50 {
+                index.insert(id, &minimum_hash(features(id as u32, 500)));
+            }
+            assert_eq!(index.len(), 50);
+
+            // 95% of the features of document 7
+            let mut near = features(7, 475);
+            near.extend(features(1000, 25));
+            let near = minimum_hash(near);
+            assert!(index.query(&near).contains(&7));
+            let above = index.query_above(&near, 0.6);
+            assert_eq!(above.len(), 1);
+            assert_eq!(above[0].0, 7);
+
+            let unrelated = minimum_hash(features(2000, 500));
+            assert!(index.query(&unrelated).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_minhash_index_reinsert() {
+        let mut index = MinHashIndex::new(16, 4).unwrap();
+        let a = minimum_hash(features(1, 100));
+        let b = minimum_hash(features(2, 100));
+        index.insert(1, &a);
+        index.insert(1, &b);
+        assert_eq!(index.len(), 1);
+        assert!(index.query(&a).is_empty());
+        assert_eq!(index.query(&b), vec![1]);
+    }
+
+    #[test]
+    fn test_minhash_index_invalid_banding() {
+        assert!(MinHashIndex::new(10, 6).is_err());
+        // The product overflows to 64
+        assert!(MinHashIndex::new(usize::MAX / 2 + 33, 2).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "Sketch must have 64 values.")]
+    fn test_minhash_index_invalid_sketch() {
+        MinHashIndex::new(16, 4).unwrap().query(&[0; 32]);
+    }
+
+    /// A random sketch and copies of it in which `64 - equal` random
+    /// positions are replaced, i.e. with an estimated similarity of
+    /// `equal / 64`.
+    struct Sketches(u64);
+
+    impl Sketches {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn random(&mut self) -> Vec<u32> {
+            (0..64).map(|_| self.next() as u32).collect()
+        }
+
+        fn near(&mut self, sketch: &[u32], equal: usize) -> Vec<u32> {
+            let mut near = sketch.to_vec();
+            let mut positions: Vec<usize> = (0..64).collect();
+            for i in 0..64 - equal {
+                let j = i + self.next() as usize % (64 - i);
+                positions.swap(i, j);
+                near[positions[i]] = !near[positions[i]];
+            }
+            near
+        }
+    }
+
+    #[test]
+    fn test_query_similar() {
+        let mut sketches = Sketches(42);
+        let mut store = MemorySketchStore::new();
+        for id in 0..20_000 {
+            insert_sketch(&mut store, id, &sketches.random()).unwrap();
+        }
+        // 100 queries with 4 planted near duplicates each
+        let similarities: [f64; 4] = [0.4, 0.6, 0.8, 0.95];
+        let mut queries = Vec::new();
+        for q in 0..100 {
+            let query = sketches.random();
+            for (n, &s) in similarities.iter().enumerate() {
+                let id = 1_000_000 + q * 10 + n as u64;
+                let near = sketches.near(&query, (s * 64.0) as usize);
+                insert_sketch(&mut store, id, &near).unwrap();
+            }
+            queries.push((q, query));
+        }
+
+        for &min_jaccard in [0.3, 0.5, 0.75, 0.9].iter() {
+            let (mut planted, mut found) = (0, 0);
+            for (q, query) in queries.iter() {
+                let results = query_similar(&store, query, min_jaccard).unwrap();
+                assert!(results.iter().all(|&(_, s)| s >= min_jaccard));
+                assert!(results.windows(2).all(|w| w[0].1 >= w[1].1));
+                for (n, &s) in similarities.iter().enumerate() {
+                    if (s * 64.0).floor() / 64.0 >= min_jaccard {
+                        planted += 1;
+                        let id = 1_000_000 + q * 10 + n as u64;
+                        found += results.iter().filter(|r| r.0 == id).count();
+                    }
+                }
+                // None of the random sketches is similar.
+                assert!(results.iter().all(|&(id, _)| id >= 1_000_000));
+            }
+            let recall = found as f64 / planted as f64;
+            assert!(recall >= 0.95, "recall {} at {}", recall, min_jaccard);
+        }
+    }
+
+    #[test]
+    fn test_sketch_store_layout() {
+        let mut store = MemorySketchStore::new();
+        let sketch = minimum_hash(features(1, 100));
+        insert_sketch(&mut store, 7, &sketch).unwrap();
+        assert_eq!(store.len(), 64 / STORE_BAND_ROWS + 1);
+        let mut key = vec![b's'];
+        key.extend(&7u64.to_be_bytes());
+        let stored = store.get(&key).unwrap().unwrap();
+        assert_eq!(minhash_from_bytes(&stored).unwrap(), sketch);
+        assert_eq!(store.scan(b"b").unwrap().len(), 64 / STORE_BAND_ROWS);
+
+        // Reinserting replaces the sketch.
+        let other = minimum_hash(features(2, 100));
+        insert_sketch(&mut store, 7, &other).unwrap();
+        let dyn_store: &dyn SketchStore = &store;
+        assert!(query_similar(dyn_store, &sketch, 0.5).unwrap().is_empty());
+        assert_eq!(
+            query_similar(dyn_store, &other, 1.0).unwrap(),
+            vec![(7, 1.0)]
+        );
+
+        assert!(insert_sketch(&mut store, 8, &sketch[..10]).is_err());
+        assert!(query_similar(&store, &sketch[..10], 0.5).is_err());
+    }
+}
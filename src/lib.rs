@@ -3,21 +3,59 @@ extern crate doc_comment;
 
 doctest!("../README.md");
 
+#[cfg(feature = "image")]
 pub use crate::cid_image::content_id_image;
 pub use crate::cid_mixed::content_id_mixed;
 pub use crate::cid_text::content_id_text;
-pub use crate::did::data_id;
-pub use crate::iid::instance_id;
-pub use crate::mid::meta_id;
+pub use crate::code::{Code, CodeKind, FullDigest};
+pub use crate::compare::hamming_distance_u64;
+pub use crate::did::{data_id, DataHasher};
+pub use crate::error::IsccError;
+pub use crate::hashes::{minimum_hash, similarity_hash, sliding_window, xxhash32, xxhash64};
+pub use crate::iid::{instance_id, InstanceHasher, InstanceResult};
+pub use crate::mid::{meta_id, MetaResult};
+pub use crate::normalization::{text_normalize, text_trim};
+pub use crate::options::IsccOptions;
+pub use crate::result::CodeResult;
 
 pub mod base58;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod bloom;
+#[cfg(feature = "cache")]
+pub mod cache;
+mod checkpoint;
+#[cfg(feature = "image")]
 pub mod cid_image;
 pub mod cid_mixed;
 pub mod cid_text;
+pub mod code;
+pub mod compare;
 #[doc(hidden)]
 pub mod constants;
 pub mod did;
+pub mod error;
+#[cfg(feature = "extract")]
+pub mod extract;
+pub mod file;
 pub mod hashes;
+#[cfg(feature = "html")]
+pub mod html;
 pub mod iid;
+pub mod index;
+pub mod metrics;
 pub mod mid;
+pub mod multihash;
 pub mod normalization;
+pub mod options;
+pub mod prelude;
+pub mod result;
+pub mod script;
+mod sha256;
+mod tables;
+#[cfg(feature = "testkit")]
+pub mod testing;
+pub mod trace;
+pub mod tree;
+pub mod version;
+mod xxh3;
@@ -0,0 +1,155 @@
+//! Generation Metrics
+//!
+//! Counters and per-stage wall-clock durations of a code generation, returned
+//! by the `*_with_metrics` variants of the generating functions. The plain
+//! functions do not measure anything.
+use std::io::{self, Read};
+use std::time::{Duration, Instant};
+
+/// A stage of a code generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    /// Reading the input.
+    Reading,
+    /// Decoding an image.
+    Decoding,
+    /// Text or image normalization.
+    Normalization,
+    /// Building the n-grams or windows of a text.
+    Windowing,
+    /// Content defined chunking of data.
+    Chunking,
+    /// Hashing the features with `xxhash`.
+    FeatureHashing,
+    /// Reducing the features with `minimum_hash`.
+    MinHash,
+    /// Reducing the features with `similarity_hash`.
+    SimHash,
+    /// The discrete cosine transform of an image.
+    Dct,
+    /// Cryptographic hashing of the Instance-ID.
+    CryptoHashing,
+}
+
+/// The metrics of one or more code generations, see [`Metrics::merge`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The number of input bytes.
+    pub input_bytes: u64,
+    /// The number of input characters, `0` for binary inputs.
+    pub input_chars: u64,
+    /// The number of features, e.g. n-grams or chunks, that were hashed.
+    pub features: u64,
+    /// The number of data chunks.
+    pub chunks: u64,
+    /// The summed duration of every stage that ran, ordered by stage.
+    pub stages: Vec<(Stage, Duration)>,
+}
+
+impl Metrics {
+    /// The duration of `stage`, zero if it did not run.
+    pub fn duration(&self, stage: Stage) -> Duration {
+        self.stages
+            .iter()
+            .filter(|(s, _)| *s == stage)
+            .map(|(_, d)| *d)
+            .sum()
+    }
+
+    /// The summed duration of all stages.
+    pub fn total(&self) -> Duration {
+        self.stages.iter().map(|(_, d)| *d).sum()
+    }
+
+    /// Adds the counters and durations of `other`, e.g. to aggregate the
+    /// metrics of all components of an ISCC.
+    pub fn merge(&mut self, other: &Metrics) {
+        self.input_bytes += other.input_bytes;
+        self.input_chars += other.input_chars;
+        self.features += other.features;
+        self.chunks += other.chunks;
+        for &(stage, duration) in &other.stages {
+            self.add(stage, duration);
+        }
+    }
+
+    /// Runs `f` and adds its duration to `stage`.
+    pub(crate) fn time<T>(&mut self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        self.add(stage, start.elapsed());
+        result
+    }
+
+    pub(crate) fn add(&mut self, stage: Stage, duration: Duration) {
+        match self.stages.binary_search_by_key(&stage, |(s, _)| *s) {
+            Ok(i) => self.stages[i].1 += duration,
+            Err(i) => self.stages.insert(i, (stage, duration)),
+        }
+    }
+}
+
+/// Measures the time spent reading and counts the bytes read.
+pub(crate) struct TimedReader<R: Read> {
+    pub(crate) inner: R,
+    pub(crate) bytes: u64,
+    pub(crate) elapsed: Duration,
+}
+
+impl<R: Read> TimedReader<R> {
+    pub(crate) fn new(inner: R) -> TimedReader<R> {
+        TimedReader {
+            inner,
+            bytes: 0,
+            elapsed: Duration::default(),
+        }
+    }
+}
+
+impl<R: Read> Read for TimedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let result = self.inner.read(buf);
+        self.elapsed += start.elapsed();
+        if let Ok(n) = result {
+            self.bytes += n as u64;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_merge() {
+        let mut a = Metrics {
+            input_bytes: 10,
+            features: 3,
+            ..Metrics::default()
+        };
+        a.add(Stage::MinHash, Duration::from_millis(2));
+        a.add(Stage::Normalization, Duration::from_millis(1));
+        let mut b = Metrics {
+            input_bytes: 5,
+            chunks: 2,
+            ..Metrics::default()
+        };
+        b.add(Stage::MinHash, Duration::from_millis(3));
+        b.add(Stage::Chunking, Duration::from_millis(4));
+        a.merge(&b);
+        assert_eq!((a.input_bytes, a.features, a.chunks), (15, 3, 2));
+        assert_eq!(
+            a.stages,
+            vec![
+                (Stage::Normalization, Duration::from_millis(1)),
+                (Stage::Chunking, Duration::from_millis(4)),
+                (Stage::MinHash, Duration::from_millis(5)),
+            ]
+        );
+        assert_eq!(a.duration(Stage::MinHash), Duration::from_millis(5));
+        assert_eq!(a.duration(Stage::Dct), Duration::from_secs(0));
+        assert_eq!(a.total(), Duration::from_millis(10));
+    }
+}
@@ -1,11 +1,21 @@
 //! Meta-ID
+use std::collections::BTreeMap;
+use std::fmt;
+
 use crate::base58::encode;
-use crate::hashes::{similarity_hash, sliding_window, xxhash64};
-use crate::normalization::text_normalize;
+use crate::code::{CodeKind, FullDigest};
+use crate::error::IsccError;
+use crate::hashes::{
+    similarity_hash, sliding_window, xxhash64, xxhash64_namespaced, xxhash64_with_seed,
+};
+use crate::metrics::{Metrics, Stage};
+use crate::normalization::{self, meta_normalize, text_normalize, text_normalize_traced};
+use crate::options::IsccOptions;
+use crate::trace::Trace;
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
 
 const WINDOW_SIZE_MID: usize = 4;
 const HEAD_MID: u8 = 0x00;
-const INPUT_TRIM: usize = 128;
 
 /// The Meta-ID component starts with a 1-byte header `00000000`. The first
 /// nibble `0000` indicates that this is a Meta-ID component type. The second
@@ -15,61 +25,327 @@ const INPUT_TRIM: usize = 128;
 /// n-grams of the basic metadata of the content to be identified.  The basic
 /// metadata supplied to the Meta-ID generating function is assumed to be UTF-8
 /// encoded.
+/// Both inputs are cleaned with [`meta_normalize`] and [`text_normalize`]
+/// before they are trimmed.
 ///
 /// * `title` - The title of an intangible creation.
 /// * `extra` - An optional short statement that distinguishes this intangible
 ///   creation from another one for the purpose of forced Meta-ID uniqueness.
 pub fn meta_id(title: &str, extra: &str) -> (String, String, String) {
-    let title_norm = text_normalize(title, true);
-    let extra_norm = text_normalize(extra, true);
+    let result = meta_id_result(title, extra);
+    (result.code, result.title, result.extra)
+}
+
+/// Generates the Meta-ID like [`meta_id`] and returns it together with the
+/// raw digest.
+pub fn meta_id_result(title: &str, extra: &str) -> MetaResult {
+    let (n_grams, title_trimmed, extra_trimmed) = meta_n_grams(title, extra);
+
+    let hash_digests: Vec<u64> = n_grams.iter().map(|n| xxhash64(n.as_bytes())).collect();
+
+    let simhash_digest = similarity_hash(hash_digests);
+
+    meta_result(simhash_digest, title_trimmed, extra_trimmed)
+}
 
-    let title_trimmed = text_trim(&title_norm);
-    let extra_trimmed = text_trim(&extra_norm);
+/// Generates the Meta-ID like [`meta_id_result`] with the given options, see
+/// [`IsccOptions`]. The default options produce the standard code.
+pub fn meta_id_with_options(title: &str, extra: &str, options: &IsccOptions) -> MetaResult {
+    let namespace = match &options.namespace {
+        None => return meta_id_result(title, extra),
+        Some(namespace) => namespace,
+    };
+    let (n_grams, title_trimmed, extra_trimmed) = meta_n_grams(title, extra);
 
+    let hash_digests: Vec<u64> = n_grams
+        .iter()
+        .map(|n| xxhash64_namespaced(namespace, n.as_bytes()))
+        .collect();
+
+    let mut result = meta_result(similarity_hash(hash_digests), title_trimmed, extra_trimmed);
+    result.namespaced = true;
+    result
+}
+
+/// Generates the Meta-ID like [`meta_id_result`] and measures the generation,
+/// see [`Metrics`]. The features are the n-grams.
+pub fn meta_id_with_metrics(title: &str, extra: &str) -> (MetaResult, Metrics) {
+    let mut metrics = Metrics {
+        input_bytes: (title.len() + extra.len()) as u64,
+        input_chars: (title.chars().count() + extra.chars().count()) as u64,
+        ..Metrics::default()
+    };
+    let (title_trimmed, extra_trimmed) =
+        metrics.time(Stage::Normalization, || meta_trim(title, extra));
+    let n_grams = metrics.time(Stage::Windowing, || {
+        meta_windows(&title_trimmed, &extra_trimmed)
+    });
+    metrics.features = n_grams.len() as u64;
+    let hash_digests: Vec<u64> = metrics.time(Stage::FeatureHashing, || {
+        n_grams.iter().map(|n| xxhash64(n.as_bytes())).collect()
+    });
+    let simhash_digest = metrics.time(Stage::SimHash, || similarity_hash(hash_digests));
+
+    (
+        meta_result(simhash_digest, title_trimmed, extra_trimmed),
+        metrics,
+    )
+}
+
+/// Generates the Meta-ID like [`meta_id_result`] and traces the normalization
+/// of `title` and `extra`, see [`Trace`]. The previews of the steps hold up to
+/// `preview_chars` characters.
+pub fn meta_id_with_trace(title: &str, extra: &str, preview_chars: usize) -> (MetaResult, Trace) {
+    let mut trace = Trace::new(preview_chars);
+    let title_trimmed = meta_trim_traced(title, "title", Some(&mut trace));
+    let extra_trimmed = meta_trim_traced(extra, "extra", Some(&mut trace));
+    let n_grams = meta_windows(&title_trimmed, &extra_trimmed);
+    let hash_digests: Vec<u64> = n_grams.iter().map(|n| xxhash64(n.as_bytes())).collect();
+
+    (
+        meta_result(similarity_hash(hash_digests), title_trimmed, extra_trimmed),
+        trace,
+    )
+}
+
+fn meta_result(simhash_digest: Vec<u8>, title: String, extra: String) -> MetaResult {
+    let mut meta_id_digest = vec![HEAD_MID];
+    meta_id_digest.extend(&simhash_digest);
+
+    MetaResult {
+        code: encode(&meta_id_digest),
+        title,
+        extra,
+        digest: simhash_digest,
+        namespaced: false,
+        version: ALGORITHM_VERSION,
+    }
+}
+
+/// Generates the 256-bit digest of the Meta-ID. The n-grams are hashed with
+/// `xxhash64` seeded with `0` to `3` and the similarity hash of each seed
+/// becomes one 64-bit word of the digest, see [`FullDigest`].
+pub fn meta_id_full(title: &str, extra: &str) -> FullDigest {
+    let (n_grams, _, _) = meta_n_grams(title, extra);
+
+    let mut digest = [0; 32];
+    for (seed, word) in digest.chunks_mut(8).enumerate() {
+        let hash_digests: Vec<u64> = n_grams
+            .iter()
+            .map(|n| xxhash64_with_seed(n.as_bytes(), seed as u64))
+            .collect();
+        word.copy_from_slice(&similarity_hash(hash_digests));
+    }
+    FullDigest {
+        kind: CodeKind::Meta,
+        digest,
+    }
+}
+
+/// Normalizes and trims the metadata and returns its n-grams together with the
+/// trimmed title and extra.
+pub(crate) fn meta_n_grams(title: &str, extra: &str) -> (Vec<String>, String, String) {
+    let (title_trimmed, extra_trimmed) = meta_trim(title, extra);
+    let n_grams = meta_windows(&title_trimmed, &extra_trimmed);
+    (n_grams, title_trimmed, extra_trimmed)
+}
+
+fn meta_trim(title: &str, extra: &str) -> (String, String) {
+    (
+        meta_trim_traced(title, "title", None),
+        meta_trim_traced(extra, "extra", None),
+    )
+}
+
+/// Normalizes and trims one input like [`meta_trim`] and records every step
+/// in `trace`, if any.
+fn meta_trim_traced(text: &str, input: &'static str, mut trace: Option<&mut Trace>) -> String {
+    let repaired = meta_normalize(text);
+    if let Some(trace) = trace.as_mut() {
+        trace.chars(input, "repair typography", text, &repaired);
+    }
+    let normalized = text_normalize_traced(&repaired, true, input, trace.as_deref_mut());
+    let trimmed = normalization::text_trim(&normalized);
+    if let Some(trace) = trace {
+        trace.bytes(input, "trim to 128 bytes", &normalized, &trimmed);
+    }
+    trimmed
+}
+
+fn meta_windows(title_trimmed: &str, extra_trimmed: &str) -> Vec<String> {
     let concat = format!("{} {}", title_trimmed, extra_trimmed);
     // trim in case `title` or `extra` is an empty string
     let concat = concat.trim();
 
-    let n_grams = sliding_window(concat, WINDOW_SIZE_MID);
+    sliding_window(concat, WINDOW_SIZE_MID)
+}
 
-    let hash_digests: Vec<u64> = n_grams.iter().map(|n| xxhash64(n.as_bytes())).collect();
+/// The Meta-ID together with the normalized and trimmed metadata it was
+/// built from. Displays as the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaResult {
+    pub code: String,
+    pub title: String,
+    pub extra: String,
+    /// The raw Meta-ID body without the header byte.
+    pub digest: Vec<u8>,
+    /// `true` if the n-grams were hashed with a namespace, see
+    /// [`IsccOptions::namespace`]. Such codes are not standard ISCC codes.
+    pub namespaced: bool,
+    /// The version of the algorithm that generated the code.
+    pub version: AlgorithmVersion,
+}
 
-    let simhash_digest = similarity_hash(hash_digests);
+impl fmt::Display for MetaResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.code)
+    }
+}
 
-    let mut meta_id_digest = vec![HEAD_MID];
-    meta_id_digest.extend(simhash_digest);
+/// Generates the Meta-ID from a map of Dublin Core like metadata.
+///
+/// * `title` - The value of `title`, or of `name` if there is no `title`.
+/// * `extra` - The values of `creator` and `description` joined by a space.
+///
+/// Keys are case sensitive, all other keys are ignored. Returns
+/// [`IsccError::MissingTitle`] if the map contains no title that is
+/// non-empty after normalization.
+pub fn meta_id_from_map(meta: &BTreeMap<String, String>) -> Result<MetaResult, IsccError> {
+    let title = ["title", "name"]
+        .iter()
+        .filter_map(|key| meta.get(*key))
+        .find(|value| !text_normalize(value, true).is_empty())
+        .ok_or(IsccError::MissingTitle)?;
+    let extra: Vec<&str> = ["creator", "description"]
+        .iter()
+        .filter_map(|key| meta.get(*key))
+        .map(|value| value.as_str())
+        .collect();
 
-    let meta_id = encode(&meta_id_digest);
-    (meta_id, title_trimmed, extra_trimmed)
+    Ok(meta_id_result(title, &extra.join(" ")))
 }
 
-/// Trim text such that its UTF-8 encoded byte representation does not exceed
-/// 128-bytes each. Remove leading and trailing whitespace.
+/// Moved to [`normalization::text_trim`].
+#[deprecated(since = "0.2.1", note = "moved to `normalization::text_trim`")]
 pub fn text_trim(text: &str) -> String {
-    let input_trim = text.len().min(INPUT_TRIM);
-    String::from_utf8_lossy(&text.as_bytes()[..input_trim])
-        .replace("�", "")
-        .trim()
-        .to_string()
+    normalization::text_trim(text)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn map(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_meta_id_with_metrics() {
+        let (result, metrics) = meta_id_with_metrics(" Die Unendliche, Geschichte", "");
+        assert_eq!(result, meta_id_result(" Die Unendliche, Geschichte", ""));
+        assert_eq!((metrics.input_bytes, metrics.input_chars), (27, 27));
+        // The 25 characters of "die unendliche geschichte" have 22 windows.
+        assert_eq!(metrics.features, 22);
+        assert_eq!(metrics.chunks, 0);
+        let stages: Vec<Stage> = metrics.stages.iter().map(|(s, _)| *s).collect();
+        assert_eq!(
+            stages,
+            vec![
+                Stage::Normalization,
+                Stage::Windowing,
+                Stage::FeatureHashing,
+                Stage::SimHash
+            ]
+        );
+    }
+
+    #[test]
+    fn test_meta_id_pdf_title() {
+        let pairs = [
+            (
+                "The Official Guide to Office Workflows",
+                "The O\u{FB03}cial Guide to O\u{FB03}ce Work\u{FB02}ows",
+            ),
+            (
+                "Don't Panic - A Field Guide",
+                "Don\u{2019}t Pan\u{00AD}ic \u{2014} A \u{FB01}eld Guide",
+            ),
+        ];
+        for &(clean, pdf) in pairs.iter() {
+            assert_eq!(meta_id(pdf, "").0, meta_id(clean, "").0, "{:?}", pdf);
+            assert_eq!(meta_id(pdf, "").1, meta_id(clean, "").1, "{:?}", pdf);
+        }
+    }
+
+    #[test]
+    fn test_meta_id_minus_sign() {
+        // The minus sign is a math symbol, which the standard normalization keeps.
+        let (code, title, _) = meta_id("Temperatures of \u{2212}40 Degrees", "");
+        assert_eq!(title, "temperatures of \u{2212}40 degrees");
+        assert_eq!(code, "CCGcBxf9Thg8c");
+        assert_ne!(code, meta_id("Temperatures of -40 Degrees", "").0);
+    }
+
+    #[test]
+    fn test_meta_id_result_reproducible() {
+        // 126 bytes after normalization, the fox crosses the 128 byte limit.
+        let words = "the quick brown fox jumps over the lazy dog ".repeat(3);
+        let words = &words[..126];
+        let messy = format!("  {}\t\n 🦊 🦊", words.to_uppercase().replace(' ', "   "));
+        assert!(messy.len() > 128);
+
+        let result = meta_id_result(&messy, "  Michael\u{00A0}ENDE ");
+        assert_eq!(result.title, words.trim());
+        assert_eq!(result.extra, "michael ende");
+        assert_eq!(result.digest.len(), 8);
+
+        let again = meta_id_result(&result.title, &result.extra);
+        assert_eq!(again, result);
+
+        let result = meta_id_result(&messy, "");
+        assert_eq!(result.extra, "");
+        assert_eq!(meta_id_result(&result.title, "").code, result.code);
+    }
+
+    #[test]
+    fn test_meta_id_from_map() {
+        let result = meta_id_from_map(&map(&[("title", "Die Unendliche Geschichte")])).unwrap();
+        let (code, title, extra) = meta_id("Die Unendliche Geschichte", "");
+        assert_eq!(
+            (result.code, result.title, result.extra),
+            (code, title, extra)
+        );
+
+        let result = meta_id_from_map(&map(&[
+            ("name", "Momo"),
+            ("title", "Die Unendliche Geschichte"),
+            ("creator", "Michael Ende"),
+            ("description", "Roman"),
+            ("publisher", "Thienemann"),
+        ]))
+        .unwrap();
+        assert_eq!(result.title, "die unendliche geschichte");
+        assert_eq!(result.extra, "michael ende roman");
+        assert_eq!(
+            result.code,
+            meta_id("Die Unendliche Geschichte", "Michael Ende Roman").0
+        );
+
+        let result = meta_id_from_map(&map(&[("title", " ? "), ("name", "Momo")])).unwrap();
+        assert_eq!(result.title, "momo");
+
+        let result = meta_id_from_map(&map(&[("title", &"驩".repeat(100))])).unwrap();
+        assert_eq!(result.title, "驩".repeat(42));
+    }
+
     #[test]
-    fn test_trim_text() {
-        let multibyte_2 = "ü".repeat(128);
-        let trimmed = text_trim(&multibyte_2);
-        assert_eq!(trimmed.chars().count(), 64);
-        assert_eq!(trimmed.len(), 128);
-        let multibyte_3 = "驩".repeat(128);
-        let trimmed2 = text_trim(&multibyte_3);
-        assert_eq!(trimmed2.chars().count(), 42);
-        assert_eq!(trimmed2.len(), 126);
-        let mixed = "Iñtërnâtiônàlizætiøn☃💩".repeat(6);
-        let trimmed3 = text_trim(&mixed);
-        assert_eq!(trimmed3.chars().count(), 85);
-        assert_eq!(trimmed3.len(), 128);
+    fn test_meta_id_from_map_missing_title() {
+        match meta_id_from_map(&map(&[("creator", "Michael Ende"), ("Title", "Momo")])) {
+            Err(IsccError::MissingTitle) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
     }
 }
@@ -0,0 +1,140 @@
+//! Multihash
+//!
+//! Self-describing hash digests as specified by
+//! [multiformats](https://github.com/multiformats/multihash): an unsigned
+//! varint with the code of the hash function, an unsigned varint with the
+//! digest length and the digest bytes.
+use crate::error::IsccError;
+
+/// Private-use multicodec code of the Instance-ID tophash, the root of a
+/// Merkle tree with the leaves `sha256d(0x00 || chunk)` and the inner nodes
+/// `sha256d(0x01 || left || right)`. It is not the `dbl-sha2-256` (`0x56`) of
+/// the content.
+pub const MULTIHASH_MERKLE_SHA2_256D: u64 = 0x30_0000;
+/// Multicodec code of BLAKE3.
+pub const MULTIHASH_BLAKE3: u64 = 0x1e;
+
+const BASE58BTC_SYMBOLS: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Frames `digest` as multihash for the hash function with the given code.
+pub fn multihash(code: u64, digest: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(digest.len() + 4);
+    encode_varint(code, &mut result);
+    encode_varint(digest.len() as u64, &mut result);
+    result.extend(digest);
+    result
+}
+
+/// Parses a multihash and returns the code of the hash function and the digest.
+pub fn parse_multihash(bytes: &[u8]) -> Result<(u64, Vec<u8>), IsccError> {
+    let (code, n_code) = decode_varint(bytes)?;
+    let (length, n_length) = decode_varint(&bytes[n_code..])?;
+    let digest = &bytes[n_code + n_length..];
+    if length != digest.len() as u64 {
+        return Err(IsccError::LengthMismatch {
+            expected: length as usize,
+            actual: digest.len(),
+        });
+    }
+    Ok((code, digest.to_vec()))
+}
+
+/// Encodes `bytes` with the base58btc alphabet, as used for multihashes.
+pub fn base58btc_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    // Little endian base58 digits
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in &bytes[zeros..] {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut result = "1".repeat(zeros);
+    result.extend(
+        digits
+            .iter()
+            .rev()
+            .map(|&d| BASE58BTC_SYMBOLS[d as usize] as char),
+    );
+    result
+}
+
+/// Appends `value` as unsigned LEB128 varint to `out`.
+pub(crate) fn encode_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Decodes an unsigned LEB128 varint from the start of `bytes` and returns the
+/// value and the number of bytes read.
+pub(crate) fn decode_varint(bytes: &[u8]) -> Result<(u64, usize), IsccError> {
+    let mut value: u64 = 0;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        let low = u64::from(byte & 0x7f);
+        if i == 9 && low > 1 {
+            return Err(IsccError::InvalidVarint);
+        }
+        value |= low << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(IsccError::InvalidVarint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint() {
+        for &value in [0, 1, 127, 128, 300, 0xb220, u64::MAX].iter() {
+            let mut bytes = Vec::new();
+            encode_varint(value, &mut bytes);
+            assert_eq!(decode_varint(&bytes).unwrap(), (value, bytes.len()));
+        }
+        let mut bytes = Vec::new();
+        encode_varint(0xb220, &mut bytes);
+        assert_eq!(bytes, vec![0xa0, 0xe4, 0x02]);
+        assert!(decode_varint(&[0x80, 0x80]).is_err());
+        assert!(decode_varint(&[0xff; 10]).is_err());
+    }
+
+    #[test]
+    fn test_parse_multihash() {
+        let bytes = multihash(0xb220, &[7; 32]);
+        assert_eq!(parse_multihash(&bytes).unwrap(), (0xb220, vec![7; 32]));
+        match parse_multihash(&bytes[..bytes.len() - 1]) {
+            Err(IsccError::LengthMismatch {
+                expected: 32,
+                actual: 31,
+            }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match parse_multihash(&[0xa0, 0xe4]) {
+            Err(IsccError::InvalidVarint) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match parse_multihash(&[0x56]) {
+            Err(IsccError::InvalidVarint) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_base58btc_encode() {
+        assert_eq!(base58btc_encode(b""), "");
+        assert_eq!(base58btc_encode(&[0, 0, 1]), "112");
+        assert_eq!(base58btc_encode(b"hello world"), "StV1DL6CwTryKyV");
+    }
+}
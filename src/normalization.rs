@@ -2,6 +2,10 @@
 use unic_ucd_category::GeneralCategory;
 use unicode_normalization::UnicodeNormalization;
 
+use crate::trace::Trace;
+
+const INPUT_TRIM: usize = 128;
+
 /// We define a text normalization function that is specific to our application.
 /// It takes text and an optional boolean `keep_ws` parameter as an input and
 /// returns *normalized* Unicode text for further algorithmic processing. The
@@ -9,37 +13,279 @@ use unicode_normalization::UnicodeNormalization;
 /// order while each step works with the results of the previous operation:
 ///
 /// 1. Decode to native Unicode if text is a byte string
-/// 2. Remove leading and trailing whitespace
-/// 3. Transform text to lower case
-/// 4. Decompose the lower case text by applying [Unicode Normalization Form D
+/// 2. Clean whitespace and control characters with [`text_clean`]
+/// 3. Remove leading and trailing whitespace
+/// 4. Transform text to lower case
+/// 5. Decompose the lower case text by applying [Unicode Normalization Form D
 ///    (NFD)](http://www.unicode.org/reports/tr15/#Norm_Forms).
-/// 5. Filter out all characters that are neither alphanumeric, whitespace
+/// 6. Filter out all characters that are neither alphanumeric, whitespace
 ///    or of unicode category symbol.
-/// 6. Keep or remove whitespace depending on `keep_ws` parameter
-/// 7. Re-Combine the text by applying `Unicode Normalization Form KC (NFKC)`.
+/// 7. Keep or remove whitespace depending on `keep_ws` parameter
+/// 8. Re-Combine the text by applying `Unicode Normalization Form KC (NFKC)`.
 pub fn text_normalize(text: &str, keep_ws: bool) -> String {
-    let text_filtered: String = text
-        .trim()
-        .to_lowercase()
-        .nfd()
-        .collect::<String>()
+    text_normalize_traced(text, keep_ws, "text", None)
+}
+
+/// Normalizes text like [`text_normalize`] and records every step of `input`
+/// in `trace`, if any.
+pub(crate) fn text_normalize_traced(
+    text: &str,
+    keep_ws: bool,
+    input: &'static str,
+    mut trace: Option<&mut Trace>,
+) -> String {
+    let mut record = |step: &'static str, before: &str, after: &str| {
+        if let Some(trace) = trace.as_mut() {
+            trace.chars(input, step, before, after);
+        }
+    };
+    let cleaned = text_clean(text);
+    record("clean whitespace and control characters", text, &cleaned);
+    let trimmed = cleaned.trim();
+    record("trim whitespace", &cleaned, trimmed);
+    let lower = trimmed.to_lowercase();
+    record("lower case", trimmed, &lower);
+    let decomposed: String = lower.nfd().collect();
+    record("NFD", &lower, &decomposed);
+    let filtered: String = decomposed
         .chars()
         .filter(|&ch| {
             ch.is_alphanumeric() || ch.is_whitespace() || GeneralCategory::of(ch).is_symbol()
         })
-        .nfkc()
         .collect();
+    record(
+        "remove characters other than letters, numbers, symbols and whitespace",
+        &decomposed,
+        &filtered,
+    );
+    let composed: String = filtered.nfkc().collect();
+    record("NFKC", &filtered, &composed);
     let ws_char = if keep_ws { " " } else { "" };
-    text_filtered
+    let result = composed
         .split_whitespace()
         .collect::<Vec<&str>>()
-        .join(ws_char)
+        .join(ws_char);
+    let step = if keep_ws {
+        "whitespace collapse"
+    } else {
+        "whitespace removal"
+    };
+    record(step, &composed, &result);
+    result
+}
+
+/// Normalizes text like [`text_normalize`] and then replaces every run of
+/// decimal digits with a single `0`, such that documents which only differ in
+/// amounts, dates and reference numbers normalize to the same result. If
+/// `strip_dates` is `true`, dates like `2021-03-15`, `2021/3/15`,
+/// `15.03.2021` or `15/03/2021`, optionally followed by a time like
+/// `T10:30` or ` 10:30:00`, are removed before the normalization.
+///
+/// **Codes generated from robustly normalized text are not standard ISCC
+/// codes.**
+pub fn text_normalize_robust(text: &str, keep_ws: bool, strip_dates: bool) -> String {
+    let normalized = if strip_dates {
+        text_normalize(&remove_dates(text), keep_ws)
+    } else {
+        text_normalize(text, keep_ws)
+    };
+    collapse_digits(&normalized)
+}
+
+/// Replaces every run of decimal digits with a single `0`.
+pub(crate) fn collapse_digits(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut in_digits = false;
+    for ch in text.chars() {
+        if GeneralCategory::of(ch) == GeneralCategory::DecimalNumber {
+            if !in_digits {
+                result.push('0');
+            }
+            in_digits = true;
+        } else {
+            result.push(ch);
+            in_digits = false;
+        }
+    }
+    result
+}
+
+/// Replaces every date, see [`text_normalize_robust`], with a space.
+pub(crate) fn remove_dates(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let starts_number = i == 0 || !chars[i - 1].is_ascii_digit();
+        match date_len(&chars[i..]).filter(|_| starts_number) {
+            Some(n) => {
+                result.push(' ');
+                i += n;
+            }
+            None => {
+                result.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    result
+}
+
+/// The length of the date at the start of `chars`, if any.
+fn date_len(chars: &[char]) -> Option<usize> {
+    let digits = |at: usize, min: usize, max: usize| {
+        let n = chars[at.min(chars.len())..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if n >= min && n <= max {
+            Some(n)
+        } else {
+            None
+        }
+    };
+    let separator = |at: usize| chars.get(at).filter(|c| matches!(c, '-' | '/' | '.'));
+
+    // Year first or year last, the same separator between all parts.
+    let first = digits(0, 1, 4)?;
+    let sep = *separator(first)?;
+    let second = digits(first + 1, 1, 2)?;
+    if chars.get(first + 1 + second) != Some(&sep) {
+        return None;
+    }
+    let third_at = first + 1 + second + 1;
+    let third = digits(third_at, 1, 4)?;
+    let year_first = first == 4 && third <= 2;
+    let year_last = first <= 2 && third == 4;
+    if !year_first && !year_last {
+        return None;
+    }
+    let mut len = third_at + third;
+
+    // An optional time of hours and minutes with optional seconds.
+    if matches!(chars.get(len), Some('T') | Some(' ')) && digits(len + 1, 2, 2).is_some() {
+        let mut time = len + 3;
+        let mut parts = 0;
+        while parts < 2 && chars.get(time) == Some(&':') && digits(time + 1, 2, 2).is_some() {
+            time += 3;
+            parts += 1;
+        }
+        if parts > 0 {
+            len = time;
+        }
+    }
+    Some(len)
+}
+
+/// Replaces every whitespace character with a single space and strips control
+/// and format characters such that visually identical texts normalize to the
+/// same result:
+///
+/// * A leading byte order mark is dropped.
+/// * Characters of the unicode categories Zs, Zl and Zp as well as tab, line
+///   feed, vertical tab, form feed, carriage return and next line (`U+0085`)
+///   become a space. Consecutive spaces are collapsed later by
+///   [`text_normalize`].
+/// * All other characters of the unicode categories Cc and Cf are removed,
+///   including zero-width spaces and joiners, soft hyphens and byte order
+///   marks within the text.
+pub fn text_clean(text: &str) -> String {
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
+    text.chars()
+        .filter_map(|ch| {
+            let category = GeneralCategory::of(ch);
+            if category.is_separator() || ch.is_whitespace() {
+                Some(' ')
+            } else if category == GeneralCategory::Control || category == GeneralCategory::Format {
+                None
+            } else {
+                Some(ch)
+            }
+        })
+        .collect()
+}
+
+/// Repairs typographic artifacts of metadata copied from PDFs and other
+/// typeset sources before it is passed to [`text_normalize`]:
+///
+/// * The Latin ligatures of the Alphabetic Presentation Forms block (`U+FB00`
+///   to `U+FB06`, e.g. `ﬁ`, `ﬂ`, `ﬃ`) are replaced by their letter sequences.
+/// * Soft hyphens (`U+00AD`) are removed.
+/// * Typographic single and double quotes are replaced by `'` and `"`, and
+///   hyphens and dashes by `-`.
+///
+/// All other characters, including letters like `æ`, `œ` or `ß` and symbols
+/// like the minus sign `U+2212`, are passed through unchanged.
+pub fn meta_normalize(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\u{FB00}' => result.push_str("ff"),
+            '\u{FB01}' => result.push_str("fi"),
+            '\u{FB02}' => result.push_str("fl"),
+            '\u{FB03}' => result.push_str("ffi"),
+            '\u{FB04}' => result.push_str("ffl"),
+            '\u{FB05}' | '\u{FB06}' => result.push_str("st"),
+            '\u{00AD}' => {}
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' => result.push('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' => result.push('"'),
+            '\u{2010}'..='\u{2015}' => result.push('-'),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+/// Trim text such that its UTF-8 encoded byte representation does not exceed
+/// 128-bytes each. Remove leading and trailing whitespace.
+pub fn text_trim(text: &str) -> String {
+    let input_trim = text.len().min(INPUT_TRIM);
+    String::from_utf8_lossy(&text.as_bytes()[..input_trim])
+        .replace("�", "")
+        .trim()
+        .to_string()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_text_normalize_traced() {
+        let texts = [
+            "",
+            "  \u{FEFF}Hello\u{200B} World!\t\r\n ﬁne Ǆ ① ²",
+            "Ünïcödé\u{00A0}tëxt — with   «quotes» and 12.5%",
+            "\u{1F98A} 漢字 ＡＢＣ e\u{0301}",
+        ];
+        for text in texts.iter() {
+            for &keep_ws in [true, false].iter() {
+                let mut trace = Trace::new(10);
+                let traced = text_normalize_traced(text, keep_ws, "text", Some(&mut trace));
+                assert_eq!(traced, text_normalize(text, keep_ws));
+                assert_eq!(trace.steps.len(), 7);
+                assert_eq!(trace.steps[0].before, text.chars().count());
+                assert_eq!(trace.steps[6].after, traced.chars().count());
+            }
+        }
+    }
+
+    #[test]
+    fn test_trim_text() {
+        let multibyte_2 = "ü".repeat(128);
+        let trimmed = text_trim(&multibyte_2);
+        assert_eq!(trimmed.chars().count(), 64);
+        assert_eq!(trimmed.len(), 128);
+        let multibyte_3 = "驩".repeat(128);
+        let trimmed2 = text_trim(&multibyte_3);
+        assert_eq!(trimmed2.chars().count(), 42);
+        assert_eq!(trimmed2.len(), 126);
+        let mixed = "Iñtërnâtiônàlizætiøn☃💩".repeat(6);
+        let trimmed3 = text_trim(&mixed);
+        assert_eq!(trimmed3.chars().count(), 85);
+        assert_eq!(trimmed3.len(), 128);
+    }
+
     #[test]
     fn test_text_normalize() {
         assert_eq!(text_normalize(" ", false), "");
@@ -56,4 +302,103 @@ mod tests {
             "internation alizætiøn☃💩 is a tric ky thing"
         );
     }
+
+    #[test]
+    fn test_text_normalize_robust() {
+        assert_eq!(
+            text_normalize_robust("Invoice 2021-00481: 59.88 EUR", true, false),
+            "invoice 0 0 eur"
+        );
+        assert_eq!(
+            text_normalize_robust("Invoice 2021-00481: 59.88 EUR", false, false),
+            "invoice0eur"
+        );
+        assert_eq!(
+            text_normalize_robust("Order of 2021-03-09, due 14.04.2021.", true, true),
+            "order of due"
+        );
+        assert_eq!(
+            text_normalize_robust("Order of 2021-03-09, due 14.04.2021.", true, false),
+            "order of 0 due 0"
+        );
+        // Arabic-Indic and fullwidth digits are decimal digits too.
+        assert_eq!(text_normalize_robust("a ٣٤ b １２", true, false), "a 0 b 0");
+        let robust = text_normalize_robust("Total 159.02 EUR", false, false);
+        assert_eq!(text_normalize_robust(&robust, false, false), robust);
+    }
+
+    #[test]
+    fn test_remove_dates() {
+        assert_eq!(remove_dates("on 2021-03-15."), "on  .");
+        assert_eq!(remove_dates("on 2021/3/5"), "on  ");
+        assert_eq!(remove_dates("on 15.03.2021 at"), "on   at");
+        assert_eq!(remove_dates("at 2021-03-15T10:30:00Z"), "at  Z");
+        assert_eq!(remove_dates("at 15/03/2021 10:30 h"), "at   h");
+        assert_eq!(remove_dates("at 15/03/2021 10 h"), "at   10 h");
+        // Mixed separators, versions and phone numbers are not dates.
+        assert_eq!(remove_dates("2021-03/15"), "2021-03/15");
+        assert_eq!(remove_dates("v1.2.3"), "v1.2.3");
+        assert_eq!(remove_dates("12.34.56"), "12.34.56");
+        assert_eq!(remove_dates("12021-03-15"), "12021-03-15");
+    }
+
+    #[test]
+    fn test_meta_normalize() {
+        assert_eq!(
+            meta_normalize(
+                "The \u{FB01}ve \u{FB02}oors of e\u{FB00}ort, \u{FB03} \u{FB04} \u{FB06}"
+            ),
+            "The five floors of effort, ffi ffl st"
+        );
+        assert_eq!(
+            meta_normalize("Ex\u{00AD}tra\u{00AD}ordinary"),
+            "Extraordinary"
+        );
+        assert_eq!(
+            meta_normalize("\u{201C}Don\u{2019}t\u{201D} \u{2013} 1\u{2014}2 \u{2212}3"),
+            "\"Don't\" - 1-2 \u{2212}3"
+        );
+        for &text in [
+            "Die Unendliche Geschichte",
+            "Ærø og Œuvre, Straße",
+            "Πόλεμος και Ειρήνη",
+            "Война и мир",
+            "はてしない物語",
+            "Iñtërnâtiônàlizætiøn☃💩 - 'quoted' \"text\"",
+        ]
+        .iter()
+        {
+            assert_eq!(meta_normalize(text), text);
+        }
+    }
+
+    #[test]
+    fn test_text_clean() {
+        let cases = [
+            ("non\u{00A0}breaking", "non breaking", "non breaking"),
+            ("zero\u{200B}width", "zerowidth", "zerowidth"),
+            (
+                "zero\u{200C}width non\u{200D}joiner",
+                "zerowidth nonjoiner",
+                "zerowidth nonjoiner",
+            ),
+            ("\u{FEFF}bom", "bom", "bom"),
+            ("vertical\u{000B}tab", "vertical tab", "vertical tab"),
+            (
+                "line\u{2028}para\u{2029}graph",
+                "line para graph",
+                "line para graph",
+            ),
+            ("soft\u{00AD}hyphen\u{0007}", "softhyphen", "softhyphen"),
+            (
+                "\u{3000}ideographic\u{3000}space\u{3000}",
+                " ideographic space ",
+                "ideographic space",
+            ),
+        ];
+        for &(dirty, cleaned, normalized) in cases.iter() {
+            assert_eq!(text_clean(dirty), cleaned, "{:?}", dirty);
+            assert_eq!(text_normalize(dirty, true), normalized, "{:?}", dirty);
+        }
+    }
 }
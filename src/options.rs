@@ -0,0 +1,63 @@
+//! Generation Options
+//!
+//! Options for the `*_with_options` variants of the generating functions. The
+//! default options produce the standard codes of the ISCC specification.
+
+/// Options for [`meta_id_with_options`](crate::mid::meta_id_with_options),
+/// [`content_id_text_with_options`](crate::cid_text::content_id_text_with_options)
+/// and [`data_id_with_options`](crate::did::data_id_with_options).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IsccOptions {
+    /// A salt that is prepended to every n-gram, window or chunk before it is
+    /// hashed with `xxhash`. **Codes generated with a namespace are not
+    /// standard ISCC codes** and are marked as `namespaced` in their result.
+    /// They match the codes of the same inputs in the same namespace, but are
+    /// uncorrelated with standard codes and with the codes of any other
+    /// namespace, e.g. to deduplicate the documents of every tenant without
+    /// making them comparable across tenants.
+    ///
+    /// `xxhash` is not a keyed cryptographic hash: the salt keeps codes of
+    /// different namespaces apart, but it does not hide the inputs from
+    /// someone who knows the salt.
+    pub namespace: Option<[u8; 16]>,
+    /// Normalizes the text of a Content-ID-Text with
+    /// [`text_normalize_robust`](crate::normalization::text_normalize_robust),
+    /// which replaces every run of digits with a single placeholder, such that
+    /// documents that only differ in amounts, dates and reference numbers get
+    /// the same code. **Such codes are not standard ISCC codes** and are
+    /// marked as `robust` in their result. Ignored by the other codes.
+    pub ignore_numbers: bool,
+    /// Removes dates like `2021-03-15` or `15.03.2021` from the text of a
+    /// Content-ID-Text before the normalization, see
+    /// [`text_normalize_robust`](crate::normalization::text_normalize_robust).
+    /// **Such codes are not standard ISCC codes** and are marked as `robust`
+    /// in their result. Ignored by the other codes.
+    pub strip_dates: bool,
+    /// Caps the number of windows of a Content-ID-Text. If the normalized text
+    /// has more windows, only every `n`-th window is hashed, starting with the
+    /// first and with the smallest `n` that keeps the count within the cap,
+    /// such that pathological inputs like minified code or base64 blobs
+    /// without any whitespace are fingerprinted in bounded time. A cap of `0`
+    /// is treated as `1`. **Sampled codes are not standard ISCC codes** and
+    /// are marked as `sampled` in their result, texts within the cap get the
+    /// standard code. Ignored by the other codes, `None` (the default) hashes
+    /// every window.
+    pub max_features: Option<usize>,
+}
+
+/// Options for [`extract_data_features`](crate::did::extract_data_features).
+/// The default options extract the features of the standard Data-ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChunkingOptions {
+    /// A salt that is prepended to every chunk before it is hashed, see
+    /// [`IsccOptions::namespace`].
+    pub namespace: Option<[u8; 16]>,
+}
+
+impl From<&IsccOptions> for ChunkingOptions {
+    fn from(options: &IsccOptions) -> ChunkingOptions {
+        ChunkingOptions {
+            namespace: options.namespace,
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Prelude
+//!
+//! The component generation functions, their result types and the stable
+//! hashing and comparison primitives for a glob import:
+//!
+//! ```
+//! use iscc::prelude::*;
+//!
+//! let code = content_id_text("some text", false);
+//! ```
+#[cfg(feature = "image")]
+pub use crate::cid_image::content_id_image;
+pub use crate::cid_mixed::content_id_mixed;
+pub use crate::cid_text::content_id_text;
+pub use crate::code::{Code, CodeKind, FullDigest};
+pub use crate::compare::hamming_distance_u64;
+pub use crate::did::{data_id, DataHasher};
+pub use crate::error::IsccError;
+pub use crate::hashes::{minimum_hash, similarity_hash, sliding_window, xxhash32, xxhash64};
+pub use crate::iid::{instance_id, InstanceHasher, InstanceResult};
+pub use crate::mid::{meta_id, MetaResult};
+pub use crate::normalization::{text_normalize, text_trim};
+pub use crate::options::IsccOptions;
+pub use crate::result::CodeResult;
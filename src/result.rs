@@ -0,0 +1,51 @@
+//! Result Types
+use std::fmt;
+
+use crate::base58;
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+/// A component code together with its raw body. Displays as the code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeResult {
+    /// The Base58-ISCC encoded component code.
+    pub code: String,
+    /// The raw component body without the header byte.
+    pub digest: Vec<u8>,
+    /// `true` if the features were hashed with a namespace, see
+    /// [`IsccOptions::namespace`](crate::options::IsccOptions::namespace).
+    /// Such codes are not standard ISCC codes.
+    pub namespaced: bool,
+    /// `true` if the text was normalized with
+    /// [`IsccOptions::ignore_numbers`](crate::options::IsccOptions::ignore_numbers)
+    /// or [`IsccOptions::strip_dates`](crate::options::IsccOptions::strip_dates).
+    /// Such codes are not standard ISCC codes.
+    pub robust: bool,
+    /// `true` if only a sample of the features was hashed because there were
+    /// more than [`IsccOptions::max_features`](crate::options::IsccOptions::max_features).
+    /// Such codes are not standard ISCC codes.
+    pub sampled: bool,
+    /// The version of the algorithm that generated the code.
+    pub version: AlgorithmVersion,
+}
+
+impl CodeResult {
+    /// Encodes the header and body to the component code.
+    pub(crate) fn new(header: u8, digest: Vec<u8>) -> CodeResult {
+        let mut component_digest = vec![header];
+        component_digest.extend(&digest);
+        CodeResult {
+            code: base58::encode(&component_digest),
+            digest,
+            namespaced: false,
+            robust: false,
+            sampled: false,
+            version: ALGORITHM_VERSION,
+        }
+    }
+}
+
+impl fmt::Display for CodeResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.code)
+    }
+}
@@ -0,0 +1,192 @@
+//! Unicode Scripts
+//!
+//! A lightweight detection of the writing systems of a text by the Unicode
+//! blocks of its letters, e.g. to choose matching thresholds per script. The
+//! blocks of the major scripts are approximated by ranges, so rare letters in
+//! other blocks are reported as [`Script::Other`].
+use std::cmp::Ordering;
+
+/// The writing system of a letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Thai,
+    Georgian,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Han,
+    /// A letter of any other script.
+    Other,
+}
+
+const SCRIPTS: [Script; 15] = [
+    Script::Latin,
+    Script::Greek,
+    Script::Cyrillic,
+    Script::Armenian,
+    Script::Hebrew,
+    Script::Arabic,
+    Script::Devanagari,
+    Script::Bengali,
+    Script::Thai,
+    Script::Georgian,
+    Script::Hangul,
+    Script::Hiragana,
+    Script::Katakana,
+    Script::Han,
+    Script::Other,
+];
+
+/// The code point ranges of the scripts, ordered by their start.
+const RANGES: &[(u32, u32, Script)] = &[
+    (0x0041, 0x005A, Script::Latin),
+    (0x0061, 0x007A, Script::Latin),
+    (0x00AA, 0x00AA, Script::Latin),
+    (0x00BA, 0x00BA, Script::Latin),
+    (0x00C0, 0x02AF, Script::Latin),
+    (0x0370, 0x03FF, Script::Greek),
+    (0x0400, 0x052F, Script::Cyrillic),
+    (0x0530, 0x058F, Script::Armenian),
+    (0x0590, 0x05FF, Script::Hebrew),
+    (0x0600, 0x06FF, Script::Arabic),
+    (0x0750, 0x077F, Script::Arabic),
+    (0x08A0, 0x08FF, Script::Arabic),
+    (0x0900, 0x097F, Script::Devanagari),
+    (0x0980, 0x09FF, Script::Bengali),
+    (0x0E00, 0x0E7F, Script::Thai),
+    (0x10A0, 0x10FF, Script::Georgian),
+    (0x1100, 0x11FF, Script::Hangul),
+    (0x1C80, 0x1C8F, Script::Cyrillic),
+    (0x1E00, 0x1EFF, Script::Latin),
+    (0x1F00, 0x1FFF, Script::Greek),
+    (0x2C60, 0x2C7F, Script::Latin),
+    (0x2DE0, 0x2DFF, Script::Cyrillic),
+    (0x2E80, 0x2FDF, Script::Han),
+    (0x3005, 0x3005, Script::Han),
+    (0x3007, 0x3007, Script::Han),
+    (0x3021, 0x3029, Script::Han),
+    (0x3040, 0x309F, Script::Hiragana),
+    (0x30A0, 0x30FF, Script::Katakana),
+    (0x3130, 0x318F, Script::Hangul),
+    (0x31F0, 0x31FF, Script::Katakana),
+    (0x3400, 0x4DBF, Script::Han),
+    (0x4E00, 0x9FFF, Script::Han),
+    (0xA640, 0xA69F, Script::Cyrillic),
+    (0xA720, 0xA7FF, Script::Latin),
+    (0xAB30, 0xAB6F, Script::Latin),
+    (0xAC00, 0xD7AF, Script::Hangul),
+    (0xF900, 0xFAFF, Script::Han),
+    (0xFB50, 0xFDFF, Script::Arabic),
+    (0xFE70, 0xFEFF, Script::Arabic),
+    (0xFF21, 0xFF3A, Script::Latin),
+    (0xFF41, 0xFF5A, Script::Latin),
+    (0xFF66, 0xFF9D, Script::Katakana),
+    (0x20000, 0x3134F, Script::Han),
+];
+
+/// Returns the script of a letter, or `None` for characters that are shared
+/// by all scripts like digits, punctuation, symbols and whitespace.
+pub fn script_of(c: char) -> Option<Script> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    let cp = c as u32;
+    let script = match RANGES.binary_search_by(|&(start, end, _)| {
+        if end < cp {
+            Ordering::Less
+        } else if start > cp {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }) {
+        Ok(i) => RANGES[i].2,
+        Err(_) => Script::Other,
+    };
+    Some(script)
+}
+
+/// Returns the share of every script in the letters of `text`, largest share
+/// first and scripts with equal shares in the order of [`Script`]. Scripts
+/// without letters are omitted, a text without letters has no scripts.
+pub fn script_ratios(text: &str) -> Vec<(Script, f64)> {
+    let mut counts = [0u64; SCRIPTS.len()];
+    for c in text.chars() {
+        if let Some(script) = script_of(c) {
+            counts[script as usize] += 1;
+        }
+    }
+    let letters: u64 = counts.iter().sum();
+    let mut ratios: Vec<(Script, f64)> = SCRIPTS
+        .iter()
+        .zip(counts.iter())
+        .filter(|&(_, &count)| count > 0)
+        .map(|(&script, &count)| (script, count as f64 / letters as f64))
+        .collect();
+    ratios.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+    ratios
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_of() {
+        let cases = [
+            ('a', Some(Script::Latin)),
+            ('é', Some(Script::Latin)),
+            ('ß', Some(Script::Latin)),
+            ('λ', Some(Script::Greek)),
+            ('ж', Some(Script::Cyrillic)),
+            ('ա', Some(Script::Armenian)),
+            ('ש', Some(Script::Hebrew)),
+            ('ع', Some(Script::Arabic)),
+            ('क', Some(Script::Devanagari)),
+            ('ক', Some(Script::Bengali)),
+            ('ก', Some(Script::Thai)),
+            ('ა', Some(Script::Georgian)),
+            ('한', Some(Script::Hangul)),
+            ('ひ', Some(Script::Hiragana)),
+            ('カ', Some(Script::Katakana)),
+            ('中', Some(Script::Han)),
+            ('𠀀', Some(Script::Han)),
+            ('ᚠ', Some(Script::Other)),
+            ('1', None),
+            (' ', None),
+            ('.', None),
+            ('☃', None),
+        ];
+        for &(c, script) in cases.iter() {
+            assert_eq!(script_of(c), script, "{:?}", c);
+        }
+        for window in RANGES.windows(2) {
+            assert!(window[0].1 < window[1].0);
+        }
+        for (i, &script) in SCRIPTS.iter().enumerate() {
+            assert_eq!(script as usize, i);
+        }
+    }
+
+    #[test]
+    fn test_script_ratios() {
+        assert!(script_ratios("").is_empty());
+        assert!(script_ratios("123 !?").is_empty());
+        assert_eq!(
+            script_ratios("abc жж 1"),
+            vec![(Script::Latin, 0.6), (Script::Cyrillic, 0.4)]
+        );
+        assert_eq!(
+            script_ratios("ab жж"),
+            vec![(Script::Latin, 0.5), (Script::Cyrillic, 0.5)]
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! SHA-256
+//!
+//! SHA-256 for the Instance-ID. `ring` does not build for WebAssembly, so on
+//! `wasm32` targets (e.g. `wasm32-wasip1`) the digests are computed by the
+//! `sha2` crate instead. Both produce identical digests.
+
+/// The SHA-256 of `data`.
+pub(crate) fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Incremental SHA-256.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct Sha256(ring::digest::Context);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Sha256 {
+    pub(crate) fn new() -> Sha256 {
+        Sha256(ring::digest::Context::new(&ring::digest::SHA256))
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    pub(crate) fn finish(self) -> [u8; 32] {
+        let mut digest = [0; 32];
+        digest.copy_from_slice(self.0.finish().as_ref());
+        digest
+    }
+}
+
+/// Incremental SHA-256.
+#[cfg(target_arch = "wasm32")]
+pub(crate) struct Sha256(sha2::Sha256);
+
+#[cfg(target_arch = "wasm32")]
+impl Sha256 {
+    pub(crate) fn new() -> Sha256 {
+        Sha256(sha2::Digest::new())
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(&mut self.0, data);
+    }
+
+    pub(crate) fn finish(self) -> [u8; 32] {
+        sha2::Digest::finalize(self.0).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha256_known_answers() {
+        for &(data, expected) in [
+            (
+                &b""[..],
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            ),
+            (
+                &b"abc"[..],
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                &b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"[..],
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(hex::encode(sha256(data)), expected);
+            let mut hasher = Sha256::new();
+            // Uneven updates to cross the block boundaries
+            for part in data.chunks(7) {
+                hasher.update(part);
+            }
+            assert_eq!(hex::encode(hasher.finish()), expected);
+        }
+    }
+}
@@ -0,0 +1,674 @@
+//! Differential Testing
+//!
+//! Hooks to compare this crate with a reference implementation. A
+//! [`ReferenceOracle`] returns the outputs of the reference implementation for
+//! a [`Case`] and [`differential_check`] runs this crate against every case of
+//! a generator and reports the cases where the outputs differ.
+//!
+//! Outputs of the reference implementation can be recorded in a JSON-lines
+//! fixture file and replayed with [`FixtureOracle`]. Every line is an object
+//! with the fields
+//!
+//! * `name` - A name for the case that is used in reports.
+//! * `function` - One of `meta_id`, `content_id_text`, `data_id` and
+//!   `instance_id`.
+//! * `inputs` - The arguments of the function: `[title, extra]`,
+//!   `[text, partial]` or `[file]` with the path of the file relative to the
+//!   fixture file.
+//! * `outputs` - The outputs of the function: `[code, title, extra]`, `code`,
+//!   `code` or `[code, tophash]`.
+//!
+//! ```text
+//! {"name": "basic", "function": "meta_id", "inputs": ["Die Unendliche Geschichte", ""], "outputs": ["CCAKevDpE1eEL", "die unendliche geschichte", ""]}
+//! ```
+//!
+//! The seeded generators [`gen_features`], [`gen_text`] and [`mutate_text`]
+//! produce reproducible inputs, e.g. to calibrate matching thresholds.
+//!
+//! Only available with the `testkit` feature.
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::cid_text::{content_features, content_id_text};
+use crate::code::Code;
+use crate::did::data_id_with_report;
+use crate::error::IsccError;
+use crate::iid::{instance_id_from_reader, InstanceAlgo};
+use crate::mid::{meta_id, meta_n_grams};
+use crate::normalization::text_normalize;
+
+/// The input of a single test case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Meta { title: String, extra: String },
+    Text { text: String, partial: bool },
+    Data(Vec<u8>),
+    Instance(Vec<u8>),
+}
+
+impl Input {
+    /// The name of the function of this crate the input is for.
+    pub fn function(&self) -> &'static str {
+        match self {
+            Input::Meta { .. } => "meta_id",
+            Input::Text { .. } => "content_id_text",
+            Input::Data(_) => "data_id",
+            Input::Instance(_) => "instance_id",
+        }
+    }
+}
+
+/// A named test case.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub input: Input,
+}
+
+/// The outputs of a reference implementation. Every method returns `None` if
+/// the oracle has no output for the input, such cases are skipped by
+/// [`differential_check`].
+pub trait ReferenceOracle {
+    /// The code, normalized title and normalized extra of the Meta-ID.
+    fn meta(&self, title: &str, extra: &str) -> Option<(String, String, String)>;
+    /// The Content-ID-Text.
+    fn text(&self, text: &str, partial: bool) -> Option<String>;
+    /// The Data-ID.
+    fn data(&self, data: &[u8]) -> Option<String>;
+    /// The code and hex encoded tophash of the Instance-ID.
+    fn instance(&self, data: &[u8]) -> Option<(String, String)>;
+}
+
+/// A recorded output of a reference implementation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixture {
+    pub case: Case,
+    /// The outputs in the order of the tuple returned by the function.
+    pub outputs: Vec<String>,
+}
+
+/// A [`ReferenceOracle`] that replays recorded outputs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixtureOracle {
+    pub fixtures: Vec<Fixture>,
+}
+
+impl FixtureOracle {
+    /// Loads a JSON-lines fixture file, paths of input files are relative to
+    /// the directory of the fixture file.
+    pub fn load(path: &Path) -> Result<FixtureOracle, IsccError> {
+        let content = fs::read_to_string(path)?;
+        FixtureOracle::parse(&content, path.parent().unwrap_or_else(|| Path::new("")))
+    }
+
+    /// Parses the content of a JSON-lines fixture file, paths of input files
+    /// are relative to `base`. Empty lines are ignored.
+    pub fn parse(content: &str, base: &Path) -> Result<FixtureOracle, IsccError> {
+        let mut fixtures = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let invalid = |reason: String| IsccError::InvalidFixture {
+                line: i + 1,
+                reason,
+            };
+            let value: Value = serde_json::from_str(line).map_err(|e| invalid(e.to_string()))?;
+            fixtures.push(parse_fixture(&value, base).map_err(invalid)?);
+        }
+        Ok(FixtureOracle { fixtures })
+    }
+
+    /// The cases of all fixtures.
+    pub fn cases(&self) -> Vec<Case> {
+        self.fixtures.iter().map(|f| f.case.clone()).collect()
+    }
+
+    fn outputs(&self, input: &Input) -> Option<&[String]> {
+        self.fixtures
+            .iter()
+            .find(|f| &f.case.input == input)
+            .map(|f| f.outputs.as_slice())
+    }
+}
+
+impl ReferenceOracle for FixtureOracle {
+    fn meta(&self, title: &str, extra: &str) -> Option<(String, String, String)> {
+        let input = Input::Meta {
+            title: title.to_string(),
+            extra: extra.to_string(),
+        };
+        match self.outputs(&input)? {
+            [code, title, extra] => Some((code.clone(), title.clone(), extra.clone())),
+            _ => None,
+        }
+    }
+
+    fn text(&self, text: &str, partial: bool) -> Option<String> {
+        let input = Input::Text {
+            text: text.to_string(),
+            partial,
+        };
+        self.outputs(&input)?.first().cloned()
+    }
+
+    fn data(&self, data: &[u8]) -> Option<String> {
+        self.outputs(&Input::Data(data.to_vec()))?.first().cloned()
+    }
+
+    fn instance(&self, data: &[u8]) -> Option<(String, String)> {
+        match self.outputs(&Input::Instance(data.to_vec()))? {
+            [code, tophash] => Some((code.clone(), tophash.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// A case where this crate and the oracle disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub name: String,
+    pub function: &'static str,
+    pub expected: Vec<String>,
+    pub actual: Vec<String>,
+    /// Intermediate values of this crate, e.g. the length of the normalized
+    /// text, the number of features and the hex encoded digest.
+    pub detail: String,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}): expected {:?}, got {:?} ({})",
+            self.name, self.function, self.expected, self.actual, self.detail
+        )
+    }
+}
+
+/// The result of [`differential_check`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// The number of cases that were compared.
+    pub checked: usize,
+    /// The names of the cases the oracle has no output for.
+    pub skipped: Vec<String>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl DiffReport {
+    /// `true` if there are no mismatches.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} cases checked, {} skipped, {} mismatches",
+            self.checked,
+            self.skipped.len(),
+            self.mismatches.len()
+        )?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n{}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs this crate against every case of `generator` and compares the
+/// outputs with the outputs of `oracle`. The generator can be the cases of a
+/// [`FixtureOracle`] or generated inputs for an oracle that calls the
+/// reference implementation directly.
+///
+/// Returns an error if this crate fails to process an input.
+pub fn differential_check<O, I>(oracle: &O, generator: I) -> Result<DiffReport, IsccError>
+where
+    O: ReferenceOracle + ?Sized,
+    I: IntoIterator<Item = Case>,
+{
+    let mut report = DiffReport::default();
+    for case in generator {
+        let expected = match &case.input {
+            Input::Meta { title, extra } => oracle
+                .meta(title, extra)
+                .map(|(code, title, extra)| vec![code, title, extra]),
+            Input::Text { text, partial } => oracle.text(text, *partial).map(|code| vec![code]),
+            Input::Data(data) => oracle.data(data).map(|code| vec![code]),
+            Input::Instance(data) => oracle
+                .instance(data)
+                .map(|(code, tophash)| vec![code, tophash]),
+        };
+        let expected = match expected {
+            Some(expected) => expected,
+            None => {
+                report.skipped.push(case.name);
+                continue;
+            }
+        };
+        report.checked += 1;
+
+        let (actual, detail) = run(&case.input)?;
+        if actual != expected {
+            report.mismatches.push(Mismatch {
+                name: case.name,
+                function: case.input.function(),
+                expected,
+                actual,
+                detail,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// The outputs of this crate for `input` and a description of the
+/// intermediate values.
+fn run(input: &Input) -> Result<(Vec<String>, String), IsccError> {
+    Ok(match input {
+        Input::Meta { title, extra } => {
+            let (n_grams, _, _) = meta_n_grams(title, extra);
+            let (code, title, extra) = meta_id(title, extra);
+            let detail = format!(
+                "normalized title {} chars, extra {} chars, {} n-grams, digest {}",
+                title.chars().count(),
+                extra.chars().count(),
+                n_grams.len(),
+                digest_hex(&code)
+            );
+            (vec![code, title, extra], detail)
+        }
+        Input::Text { text, partial } => {
+            let code = content_id_text(text, *partial);
+            let detail = format!(
+                "normalized text {} chars, {} features, digest {}",
+                text_normalize(text, false).chars().count(),
+                content_features(text).len(),
+                digest_hex(&code)
+            );
+            (vec![code], detail)
+        }
+        Input::Data(data) => {
+            let (code, report) = data_id_with_report(&data[..])?;
+            let detail = format!(
+                "{} bytes, {} chunks, digest {}",
+                data.len(),
+                report.chunk_count(),
+                digest_hex(&code)
+            );
+            (vec![code], detail)
+        }
+        Input::Instance(data) => {
+            let (code, tophash) = instance_id_from_reader(&data[..], InstanceAlgo::Default)?;
+            let detail = format!("{} bytes, digest {}", data.len(), digest_hex(&code));
+            (vec![code, tophash], detail)
+        }
+    })
+}
+
+fn digest_hex(code: &str) -> String {
+    match code.parse::<Code>() {
+        Ok(code) => hex::encode(code.body()),
+        Err(e) => format!("invalid ({})", e),
+    }
+}
+
+/// Generates `count` pseudo-random features, e.g. for
+/// [`content_id_text_from_features`](crate::cid_text::content_id_text_from_features).
+/// The same seed yields the same features on every platform.
+pub fn gen_features(seed: u64, count: usize) -> Vec<u32> {
+    let mut rng = SplitMix64(seed);
+    (0..count).map(|_| (rng.next_u64() >> 32) as u32).collect()
+}
+
+/// Generates `len` pseudo-random bytes, e.g. as data for
+/// [`data_id_with_options`](crate::did::data_id_with_options). The same seed
+/// yields the same bytes on every platform.
+pub fn gen_bytes(seed: u64, len: usize) -> Vec<u8> {
+    let mut rng = SplitMix64(seed);
+    let mut bytes = Vec::with_capacity(len + 8);
+    while bytes.len() < len {
+        bytes.extend_from_slice(&rng.next_u64().to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+/// Generates a pseudo-random text of exactly `chars` characters. The text
+/// consists of words in Latin (with diacritics), Cyrillic, Greek and CJK
+/// script separated by spaces and punctuation. The same seed yields the same
+/// text on every platform.
+pub fn gen_text(seed: u64, chars: usize) -> String {
+    let mut rng = SplitMix64(seed);
+    let mut text = String::with_capacity(chars * 2);
+    let mut count = 0;
+    while count < chars {
+        if count > 0 {
+            let separator = match rng.below(10) {
+                0 => ", ",
+                1 => ". ",
+                _ => " ",
+            };
+            for c in separator.chars().take(chars - count) {
+                text.push(c);
+                count += 1;
+            }
+        }
+        let script = rng.below(SCRIPTS.len() as u32) as usize;
+        let length = 2 + rng.below(9) as usize;
+        for _ in 0..length.min(chars - count) {
+            text.push(random_char(&mut rng, script));
+            count += 1;
+        }
+    }
+    text
+}
+
+/// Applies pseudo-random edits to `text`: every character is substituted,
+/// preceded by an inserted character or deleted with a total probability of
+/// `edit_ratio`, so a ratio of `0.01` edits about 1% of the characters. The
+/// same seed yields the same edits on every platform.
+///
+/// Returns an error if `edit_ratio` is not between `0.0` and `1.0`.
+pub fn mutate_text(text: &str, seed: u64, edit_ratio: f64) -> Result<String, IsccError> {
+    if !(0.0..=1.0).contains(&edit_ratio) {
+        return Err(IsccError::InvalidArgument(format!(
+            "edit ratio must be between 0 and 1, got {}",
+            edit_ratio
+        )));
+    }
+    let mut rng = SplitMix64(seed);
+    let mut mutated = String::with_capacity(text.len());
+    for c in text.chars() {
+        if !rng.chance(edit_ratio) {
+            mutated.push(c);
+            continue;
+        }
+        match rng.below(3) {
+            0 => mutated.push(random_char(&mut rng, 0)),
+            1 => {
+                mutated.push(random_char(&mut rng, 0));
+                mutated.push(c);
+            }
+            _ => {}
+        }
+    }
+    Ok(mutated)
+}
+
+/// Ranges of lowercase letters used by [`gen_text`], the first one is also
+/// used for the edits of [`mutate_text`]. Latin appears twice to make it the
+/// most frequent script.
+const SCRIPTS: &[&[(u32, u32)]] = &[
+    &[(0x61, 0x7a), (0xe0, 0xfc)],
+    &[(0x61, 0x7a)],
+    &[(0x430, 0x44f)],
+    &[(0x3b1, 0x3c9)],
+    &[(0x4e00, 0x4fff)],
+];
+
+fn random_char(rng: &mut SplitMix64, script: usize) -> char {
+    let ranges = SCRIPTS[script];
+    let (start, end) = ranges[rng.below(ranges.len() as u32) as usize];
+    let code = start + rng.below(end - start + 1);
+    // The Latin-1 range contains `÷` which is not a letter.
+    std::char::from_u32(if code == 0xf7 { 0xf8 } else { code }).unwrap()
+}
+
+/// The SplitMix64 generator, small and reproducible on every platform.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: u32) -> u32 {
+        (((self.next_u64() >> 32) * u64::from(n)) >> 32) as u32
+    }
+
+    /// `true` with probability `p`.
+    fn chance(&mut self, p: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < p
+    }
+}
+
+fn parse_fixture(value: &Value, base: &Path) -> Result<Fixture, String> {
+    let name = as_str(field(value, "name")?)?.to_string();
+    let function = as_str(field(value, "function")?)?;
+    let inputs = match field(value, "inputs")? {
+        Value::Array(values) => values.as_slice(),
+        _ => return Err("expected an array".to_string()),
+    };
+    let read_file = |value: &Value| -> Result<Vec<u8>, String> {
+        let path = base.join(as_str(value)?);
+        fs::read(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+    };
+    let input = match (function, inputs) {
+        ("meta_id", [title, extra]) => Input::Meta {
+            title: as_str(title)?.to_string(),
+            extra: as_str(extra)?.to_string(),
+        },
+        ("content_id_text", [text, partial]) => Input::Text {
+            text: as_str(text)?.to_string(),
+            partial: as_bool(partial)?,
+        },
+        ("data_id", [file]) => Input::Data(read_file(file)?),
+        ("instance_id", [file]) => Input::Instance(read_file(file)?),
+        ("meta_id", _) | ("content_id_text", _) | ("data_id", _) | ("instance_id", _) => {
+            return Err(format!("wrong number of inputs for {}", function))
+        }
+        _ => return Err(format!("unknown function {}", function)),
+    };
+    let outputs = match field(value, "outputs")? {
+        Value::Array(values) => values
+            .iter()
+            .map(|v| as_str(v).map(String::from))
+            .collect::<Result<_, _>>()?,
+        output => vec![as_str(output)?.to_string()],
+    };
+    Ok(Fixture {
+        case: Case { name, input },
+        outputs,
+    })
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, String> {
+    match value {
+        Value::Object(fields) => fields
+            .get(name)
+            .ok_or_else(|| format!("missing field {}", name)),
+        _ => Err("expected an object".to_string()),
+    }
+}
+
+fn as_str(value: &Value) -> Result<&str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| "expected a string".to_string())
+}
+
+fn as_bool(value: &Value) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| "expected a boolean".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_oracle_parse() {
+        let content = concat!(
+            r#"{"name": "t", "function": "content_id_text", "inputs": ["Some short text", false], "outputs": "CTtuZwbXemZkr"}"#,
+            "\n\n",
+            r#"{"name": "m", "function": "meta_id", "inputs": ["A", ""], "outputs": ["CC", "a", ""]}"#,
+        );
+        let oracle = FixtureOracle::parse(content, Path::new("")).unwrap();
+        assert_eq!(oracle.fixtures.len(), 2);
+        assert_eq!(
+            oracle.text("Some short text", false).as_deref(),
+            Some("CTtuZwbXemZkr")
+        );
+        assert_eq!(oracle.text("Some short text", true), None);
+        assert_eq!(
+            oracle.meta("A", ""),
+            Some(("CC".to_string(), "a".to_string(), "".to_string()))
+        );
+
+        let content =
+            "\n{\"name\": \"x\", \"function\": \"meta_id\", \"inputs\": [\"A\"], \"outputs\": []}";
+        match FixtureOracle::parse(content, Path::new("")) {
+            Err(IsccError::InvalidFixture { line: 2, .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+        match FixtureOracle::parse("{\"name\": \"x\"", Path::new("")) {
+            Err(IsccError::InvalidFixture { line: 1, .. }) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    /// An oracle that disagrees with everything.
+    struct Wrong;
+
+    impl ReferenceOracle for Wrong {
+        fn meta(&self, _: &str, _: &str) -> Option<(String, String, String)> {
+            Some(("CCh2TW8YiFhSN".to_string(), String::new(), String::new()))
+        }
+
+        fn text(&self, _: &str, _: bool) -> Option<String> {
+            Some("CT7A4zpmccuEv".to_string())
+        }
+
+        fn data(&self, _: &[u8]) -> Option<String> {
+            None
+        }
+
+        fn instance(&self, _: &[u8]) -> Option<(String, String)> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_differential_check_mismatch() {
+        let cases = vec![
+            Case {
+                name: "meta".to_string(),
+                input: Input::Meta {
+                    title: "Die Unendliche Geschichte".to_string(),
+                    extra: String::new(),
+                },
+            },
+            Case {
+                name: "text".to_string(),
+                input: Input::Text {
+                    text: "Some short text".to_string(),
+                    partial: false,
+                },
+            },
+            Case {
+                name: "data".to_string(),
+                input: Input::Data(b"data".to_vec()),
+            },
+        ];
+        let report = differential_check(&Wrong, cases).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.checked, 2);
+        assert_eq!(report.skipped, vec!["data".to_string()]);
+        assert_eq!(report.mismatches.len(), 2);
+
+        let meta = &report.mismatches[0];
+        assert_eq!(meta.function, "meta_id");
+        assert_eq!(meta.actual[0], "CCAKevDpE1eEL");
+        assert_eq!(
+            meta.detail,
+            format!(
+                "normalized title 25 chars, extra 0 chars, 22 n-grams, digest {}",
+                hex::encode(crate::mid::meta_id_result("Die Unendliche Geschichte", "").digest)
+            )
+        );
+        let text = &report.mismatches[1];
+        assert_eq!(text.expected, vec!["CT7A4zpmccuEv".to_string()]);
+        assert_eq!(text.actual, vec!["CTtuZwbXemZkr".to_string()]);
+        assert!(text
+            .detail
+            .starts_with("normalized text 13 chars, 1 features, digest "));
+    }
+
+    #[test]
+    fn test_generators_reproducible() {
+        assert_eq!(
+            gen_features(42, 4),
+            vec![3184996902, 686809907, 1196582743, 1478287871]
+        );
+        assert_eq!(
+            gen_text(42, 40),
+            "ιφυ νργ âñhöôøöë, nhrcnjea αωβπθψρ. 串亢万乾"
+        );
+        assert_eq!(gen_text(42, 1000).chars().count(), 1000);
+        assert_eq!(gen_text(7, 0), "");
+        let text = gen_text(1, 1000);
+        assert_eq!(mutate_text(&text, 2, 0.0).unwrap(), text);
+        assert_eq!(
+            mutate_text(&text, 2, 0.1).unwrap(),
+            mutate_text(&text, 2, 0.1).unwrap()
+        );
+        assert_ne!(mutate_text(&text, 2, 0.1).unwrap(), text);
+        match mutate_text(&text, 2, 1.5) {
+            Err(IsccError::InvalidArgument(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    fn distance(a: &str, b: &str) -> u32 {
+        let a: Code = a.parse().unwrap();
+        let b: Code = b.parse().unwrap();
+        crate::compare::hamming_distance_u64(a.body_u64().unwrap(), b.body_u64().unwrap())
+    }
+
+    #[test]
+    fn test_content_id_text_edit_distance() {
+        const SEEDS: u64 = 100;
+        const MAX_DISTANCE: u32 = 16;
+        let mut similar = 0;
+        let mut unrelated = 0;
+        for seed in 0..SEEDS {
+            let text = gen_text(seed, 2000);
+            let code = content_id_text(&text, false);
+            let edited = mutate_text(&text, seed + SEEDS, 0.01).unwrap();
+            if distance(&code, &content_id_text(&edited, false)) <= MAX_DISTANCE {
+                similar += 1;
+            }
+            let other = gen_text(seed + 2 * SEEDS, 2000);
+            if distance(&code, &content_id_text(&other, false)) > MAX_DISTANCE {
+                unrelated += 1;
+            }
+        }
+        // Texts with 1% edits are "the same" and unrelated texts are not
+        // in at least 95% of the cases.
+        assert!(
+            similar >= 95,
+            "{} of {} edited texts matched",
+            similar,
+            SEEDS
+        );
+        assert!(
+            unrelated >= 95,
+            "{} of {} unrelated texts did not match",
+            unrelated,
+            SEEDS
+        );
+    }
+}
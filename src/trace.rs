@@ -0,0 +1,127 @@
+//! Normalization Traces
+//!
+//! The `*_with_trace` variants of the Meta-ID and Content-ID-Text functions
+//! additionally return a [`Trace`] of every normalization step that was
+//! applied to the inputs before they were hashed, e.g. to document why two
+//! works got similar codes. The codes are identical to the codes of the plain
+//! functions, which trace nothing.
+use std::fmt;
+
+/// The unit of the lengths of a [`TraceStep`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Chars,
+    Bytes,
+}
+
+/// A normalization step and its effect on the length of the input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The normalized input, e.g. `title`, `extra` or `text`.
+    pub input: &'static str,
+    /// The name of the step, e.g. `NFKC`.
+    pub step: &'static str,
+    /// The length before the step.
+    pub before: usize,
+    /// The length after the step.
+    pub after: usize,
+    pub unit: Unit,
+    /// The first characters of the result of the step, see [`Trace::new`].
+    pub preview: String,
+}
+
+impl fmt::Display for TraceStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match self.unit {
+            Unit::Chars => "chars",
+            Unit::Bytes => "bytes",
+        };
+        write!(
+            f,
+            "{}: {}: {} \u{2192} {} {}",
+            self.input, self.step, self.before, self.after, unit
+        )
+    }
+}
+
+/// The normalization steps of a code generation in the order they were
+/// applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+    /// The maximum number of characters of the previews.
+    pub preview_chars: usize,
+}
+
+impl Trace {
+    /// An empty trace whose steps keep the first `preview_chars` characters of
+    /// their results, `usize::MAX` keeps the whole results.
+    pub fn new(preview_chars: usize) -> Trace {
+        Trace {
+            steps: Vec::new(),
+            preview_chars,
+        }
+    }
+
+    /// The steps applied to `input`.
+    pub fn steps_of<'a>(&'a self, input: &'a str) -> impl Iterator<Item = &'a TraceStep> + 'a {
+        self.steps.iter().filter(move |step| step.input == input)
+    }
+
+    /// Records a step from `before` to `after`, measured in characters.
+    pub(crate) fn chars(
+        &mut self,
+        input: &'static str,
+        step: &'static str,
+        before: &str,
+        after: &str,
+    ) {
+        self.push(
+            input,
+            step,
+            before.chars().count(),
+            after.chars().count(),
+            Unit::Chars,
+            after,
+        );
+    }
+
+    /// Records a step from `before` to `after`, measured in bytes.
+    pub(crate) fn bytes(
+        &mut self,
+        input: &'static str,
+        step: &'static str,
+        before: &str,
+        after: &str,
+    ) {
+        self.push(input, step, before.len(), after.len(), Unit::Bytes, after);
+    }
+
+    fn push(
+        &mut self,
+        input: &'static str,
+        step: &'static str,
+        before: usize,
+        after: usize,
+        unit: Unit,
+        result: &str,
+    ) {
+        self.steps.push(TraceStep {
+            input,
+            step,
+            before,
+            after,
+            unit,
+            preview: result.chars().take(self.preview_chars).collect(),
+        });
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for step in &self.steps {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
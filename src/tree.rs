@@ -0,0 +1,294 @@
+//! Directory Trees
+//!
+//! Data-ID and Instance-ID like codes for a whole directory tree. **These
+//! codes are not part of the ISCC specification.**
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::did::{data_id_from_minhash, extract_data_features};
+use crate::error::IsccError;
+use crate::hashes::minimum_hash;
+use crate::iid::InstanceHasher;
+use crate::options::ChunkingOptions;
+use crate::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+/// Options for [`iscc_sum_tree_with`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeOptions {
+    /// Files and directories whose name matches any of these patterns are
+    /// skipped. `*` matches any number of characters, `?` a single one, e.g.
+    /// `.git` or `*.tmp`.
+    pub ignore: Vec<String>,
+}
+
+/// A file of a directory tree, see [`iscc_sum_tree`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// The path relative to the root with `/` as separator.
+    pub path: String,
+    pub size: u64,
+    /// The Data-ID of the file, `None` if the file is empty.
+    pub data_code: Option<String>,
+    pub instance_code: String,
+    /// The hex encoded tophash of the Instance-ID.
+    pub tophash: String,
+}
+
+/// The codes of a directory tree and the files they were built from, sorted
+/// by path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSum {
+    /// A Data-ID over the chunks of all files, `None` if all files are empty.
+    pub data_code: Option<String>,
+    /// An Instance-ID over the manifest of the tree.
+    pub instance_code: String,
+    /// The hex encoded tophash of the manifest.
+    pub tophash: String,
+    pub entries: Vec<TreeEntry>,
+    /// The version of the algorithm that generated the codes.
+    pub version: AlgorithmVersion,
+}
+
+/// Generates codes for all files below `root` with the default
+/// [`TreeOptions`], see [`iscc_sum_tree_with`].
+pub fn iscc_sum_tree(root: &Path) -> Result<TreeSum, IsccError> {
+    iscc_sum_tree_with(root, &TreeOptions::default())
+}
+
+/// Generates codes for all regular files below `root`. Symbolic links are not
+/// followed and skipped, file times and permissions are ignored.
+///
+/// Every file is read once to compute its Data-ID and Instance-ID. The data
+/// code of the tree is a Data-ID built from the MinHash of the chunk hashes
+/// of all files, so it does not depend on the names of the files. The
+/// instance code of the tree is the Instance-ID of the manifest, which holds
+/// for every file in the order of the byte-wise sorted paths
+///
+/// * the length of the path (`u32`, little endian) and the UTF-8 encoded
+///   path relative to `root` with `/` as separator,
+/// * the size of the file (`u64`, little endian),
+/// * the 32-byte tophash of the file.
+///
+/// Returns an error if a path is not valid UTF-8.
+pub fn iscc_sum_tree_with(root: &Path, options: &TreeOptions) -> Result<TreeSum, IsccError> {
+    let mut paths = Vec::new();
+    collect_files(root, "", options, &mut paths)?;
+    paths.sort();
+
+    let mut entries = Vec::with_capacity(paths.len());
+    // The elementwise minimum of the MinHash of every file, `None` while all
+    // files are empty.
+    let mut minhash: Option<[u32; 64]> = None;
+    let mut manifest = InstanceHasher::new();
+    for path in paths {
+        let mut reader = InstanceReader {
+            inner: File::open(root.join(&path))?,
+            hasher: InstanceHasher::new(),
+            size: 0,
+        };
+        let features = extract_data_features(&mut reader, &ChunkingOptions::default())?;
+        let file_minhash = if features.is_empty() {
+            None
+        } else {
+            Some(minimum_hash(features))
+        };
+        if let Some(file_minhash) = &file_minhash {
+            let tree_minhash = minhash.get_or_insert([u32::MAX; 64]);
+            for (min, &value) in tree_minhash.iter_mut().zip(file_minhash.iter()) {
+                *min = (*min).min(value);
+            }
+        }
+        let size = reader.size;
+        let instance = reader.hasher.finish();
+
+        manifest.update(&(path.len() as u32).to_le_bytes());
+        manifest.update(path.as_bytes());
+        manifest.update(&size.to_le_bytes());
+        manifest.update(&hex::decode(&instance.tophash).expect("tophash is hex encoded"));
+
+        entries.push(TreeEntry {
+            path,
+            size,
+            data_code: file_minhash.map(|m| data_id_from_minhash(&m).code),
+            instance_code: instance.code,
+            tophash: instance.tophash,
+        });
+    }
+
+    let instance = manifest.finish();
+    Ok(TreeSum {
+        data_code: minhash.map(|m| data_id_from_minhash(&m).code),
+        instance_code: instance.code,
+        tophash: instance.tophash,
+        entries,
+        version: ALGORITHM_VERSION,
+    })
+}
+
+/// Appends the relative paths of all files below `dir` to `paths`.
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    options: &TreeOptions,
+    paths: &mut Vec<String>,
+) -> Result<(), IsccError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| IsccError::InvalidArgument(format!("{:?} is not valid UTF-8", name)))?;
+        if options.ignore.iter().any(|p| glob_match(p, &name)) {
+            continue;
+        }
+        let path = format!("{}{}", prefix, name);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), &format!("{}/", path), options, paths)?;
+        } else if file_type.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Matches `name` against a pattern with the wildcards `*` and `?`.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Position after the last `*` in the pattern and the name
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            star = Some((p, n));
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p;
+            n = star_n + 1;
+            star = Some((star_p, n));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Feeds everything that is read to an [`InstanceHasher`].
+struct InstanceReader<R: Read> {
+    inner: R,
+    hasher: InstanceHasher,
+    size: u64,
+}
+
+impl<R: Read> Read for InstanceReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        self.size += n as u64;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    /// A fresh directory below the temporary directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("iscc-tree-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("a.txt"), "Some text that is not too short.").unwrap();
+        fs::write(
+            dir.join("sub/lenna.jpg"),
+            fs::read("tests/test_data/lenna.jpg").unwrap(),
+        )
+        .unwrap();
+        fs::write(dir.join("sub/empty"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_iscc_sum_tree() {
+        let dir = temp_dir("sum");
+        let sum = iscc_sum_tree(&dir).unwrap();
+        let paths: Vec<&str> = sum.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(paths, vec!["a.txt", "sub/empty", "sub/lenna.jpg"]);
+        let lenna = &sum.entries[2];
+        assert_eq!(
+            lenna.data_code.as_deref(),
+            Some(
+                crate::data_id("tests/test_data/lenna.jpg")
+                    .unwrap()
+                    .as_str()
+            )
+        );
+        let (code, tophash) = crate::instance_id("tests/test_data/lenna.jpg").unwrap();
+        assert_eq!((&lenna.instance_code, &lenna.tophash), (&code, &tophash));
+        assert_eq!(
+            lenna.size,
+            fs::metadata(dir.join("sub/lenna.jpg")).unwrap().len()
+        );
+        assert_eq!(sum.entries[1].data_code, None);
+
+        // Rewriting a file with the same content changes its times only.
+        fs::write(dir.join("a.txt"), "Some text that is not too short.").unwrap();
+        assert_eq!(iscc_sum_tree(&dir).unwrap(), sum);
+
+        // Renaming a file changes the manifest but not the data.
+        fs::rename(dir.join("a.txt"), dir.join("b.txt")).unwrap();
+        let renamed = iscc_sum_tree(&dir).unwrap();
+        assert_eq!(renamed.data_code, sum.data_code);
+        assert_ne!(renamed.instance_code, sum.instance_code);
+
+        // Adding a file changes both.
+        let data: Vec<u8> = (0..200_000u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        fs::write(dir.join("c.bin"), data).unwrap();
+        let added = iscc_sum_tree(&dir).unwrap();
+        assert_ne!(added.data_code, renamed.data_code);
+        assert_ne!(added.instance_code, renamed.instance_code);
+
+        let options = TreeOptions {
+            ignore: vec!["*.bin".to_string()],
+        };
+        assert_eq!(iscc_sum_tree_with(&dir, &options).unwrap(), renamed);
+        let options = TreeOptions {
+            ignore: vec!["su?".to_string(), "*.bin".to_string()],
+        };
+        let ignored = iscc_sum_tree_with(&dir, &options).unwrap();
+        assert_eq!(ignored.entries.len(), 1);
+
+        // A tree of empty files has no data code.
+        let options = TreeOptions {
+            ignore: vec!["*.*".to_string()],
+        };
+        let empty = iscc_sum_tree_with(&dir, &options).unwrap();
+        assert_eq!(empty.entries.len(), 1);
+        assert_eq!(empty.data_code, None);
+        assert_eq!(empty.entries[0].instance_code, "CR4ATDsziWVwB");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(".git", ".git"));
+        assert!(!glob_match(".git", ".gitignore"));
+        assert!(glob_match("*.tmp", "a.tmp"));
+        assert!(glob_match("*.tmp", ".tmp"));
+        assert!(!glob_match("*.tmp", "a.tmp.txt"));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("?", "驩"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("?", ""));
+    }
+}
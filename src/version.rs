@@ -0,0 +1,89 @@
+//! Algorithm Versions
+//!
+//! Every change to this crate that alters a generated code bumps
+//! [`ALGORITHM_VERSION`] and records the changed components, so that codes
+//! stored by different versions can be told apart with
+//! [`is_comparable`](crate::compare::is_comparable). The header of a code has
+//! no room for a version, so it is reported in the result structs instead and
+//! parsed codes are assumed to be of the current version, see
+//! [`Code::with_version`](crate::code::Code::with_version).
+use std::fmt;
+
+use crate::code::CodeKind;
+
+/// A version of the code generation algorithms of this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AlgorithmVersion(pub u32);
+
+/// The version of the algorithms of this build.
+pub const ALGORITHM_VERSION: AlgorithmVersion = AlgorithmVersion(1);
+
+/// For every version, the components whose codes changed in it. The partial
+/// content flag is ignored.
+const CHANGES: &[(u32, &[CodeKind])] = &[(
+    1,
+    &[
+        CodeKind::Meta,
+        CodeKind::ContentText { partial: false },
+        CodeKind::ContentImage { partial: false },
+        CodeKind::ContentMixed { partial: false },
+        CodeKind::Data,
+        CodeKind::Instance,
+    ],
+)];
+
+impl AlgorithmVersion {
+    /// The last version up to this one in which the codes of `kind` changed.
+    /// Codes of two versions with the same construction are comparable.
+    /// Unknown future versions are their own construction.
+    pub fn construction_of(self, kind: CodeKind) -> AlgorithmVersion {
+        if self > ALGORITHM_VERSION {
+            return self;
+        }
+        CHANGES
+            .iter()
+            .rev()
+            .filter(|(version, _)| *version <= self.0)
+            .find(|(_, kinds)| kinds.iter().any(|k| k.header() >> 1 == kind.header() >> 1))
+            .map(|&(version, _)| AlgorithmVersion(version))
+            .unwrap_or(self)
+    }
+}
+
+impl Default for AlgorithmVersion {
+    fn default() -> AlgorithmVersion {
+        ALGORITHM_VERSION
+    }
+}
+
+impl fmt::Display for AlgorithmVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_construction_of() {
+        let kind = CodeKind::ContentText { partial: true };
+        assert_eq!(ALGORITHM_VERSION.construction_of(kind), AlgorithmVersion(1));
+        assert_eq!(
+            AlgorithmVersion(99).construction_of(kind),
+            AlgorithmVersion(99)
+        );
+        assert_eq!(
+            AlgorithmVersion(0).construction_of(kind),
+            AlgorithmVersion(0)
+        );
+        assert_eq!(ALGORITHM_VERSION.to_string(), "v1");
+    }
+
+    #[test]
+    fn test_changes_are_ordered() {
+        assert!(CHANGES.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(CHANGES.last().unwrap().0, ALGORITHM_VERSION.0);
+    }
+}
@@ -0,0 +1,284 @@
+//! XXH3-128
+//!
+//! A port of the 128-bit variant of the XXH3 hash function (xxHash 0.8) with
+//! the default secret and seed `0`.
+use std::convert::TryInto;
+
+const PRIME32_1: u64 = 0x9E37_79B1;
+const PRIME32_2: u64 = 0x85EB_CA77;
+const PRIME32_3: u64 = 0xC2B2_AE3D;
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+const PRIME_MX1: u64 = 0x1656_6791_9E37_79F9;
+const PRIME_MX2: u64 = 0x9FB2_1C65_1E98_DF25;
+
+const STRIPE_LEN: usize = 64;
+const SECRET_CONSUME_RATE: usize = 8;
+const SECRET_SIZE_MIN: usize = 136;
+const MIDSIZE_START_OFFSET: usize = 3;
+const MIDSIZE_LAST_OFFSET: usize = 17;
+const SECRET_LASTACC_START: usize = 7;
+const SECRET_MERGEACCS_START: usize = 11;
+
+const SECRET: [u8; 192] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+/// Returns the XXH3-128 hash of `data` with the high 64 bits of the hash in
+/// the most significant half.
+pub(crate) fn hash128(data: &[u8]) -> u128 {
+    let len = data.len();
+    let (low, high) = if len <= 16 {
+        hash_len_0to16(data)
+    } else if len <= 128 {
+        hash_len_17to128(data)
+    } else if len <= 240 {
+        hash_len_129to240(data)
+    } else {
+        hash_long(data)
+    };
+    (u128::from(high) << 64) | u128::from(low)
+}
+
+fn read32(data: &[u8], offset: usize) -> u64 {
+    u64::from(u32::from_le_bytes(
+        data[offset..offset + 4].try_into().unwrap(),
+    ))
+}
+
+fn read64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn mul128(a: u64, b: u64) -> (u64, u64) {
+    let product = u128::from(a) * u128::from(b);
+    (product as u64, (product >> 64) as u64)
+}
+
+fn mul128_fold64(a: u64, b: u64) -> u64 {
+    let (low, high) = mul128(a, b);
+    low ^ high
+}
+
+fn xxh64_avalanche(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME64_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME64_3);
+    h ^ (h >> 32)
+}
+
+fn avalanche(mut h: u64) -> u64 {
+    h ^= h >> 37;
+    h = h.wrapping_mul(PRIME_MX1);
+    h ^ (h >> 32)
+}
+
+fn hash_len_0to16(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    if len > 8 {
+        hash_len_9to16(data)
+    } else if len >= 4 {
+        hash_len_4to8(data)
+    } else if len > 0 {
+        hash_len_1to3(data)
+    } else {
+        (
+            xxh64_avalanche(read64(&SECRET, 64) ^ read64(&SECRET, 72)),
+            xxh64_avalanche(read64(&SECRET, 80) ^ read64(&SECRET, 88)),
+        )
+    }
+}
+
+fn hash_len_1to3(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    let c1 = u32::from(data[0]);
+    let c2 = u32::from(data[len >> 1]);
+    let c3 = u32::from(data[len - 1]);
+    let combined_low = (c1 << 16) | (c2 << 24) | c3 | ((len as u32) << 8);
+    let combined_high = combined_low.swap_bytes().rotate_left(13);
+    let bitflip_low = read32(&SECRET, 0) ^ read32(&SECRET, 4);
+    let bitflip_high = read32(&SECRET, 8) ^ read32(&SECRET, 12);
+    (
+        xxh64_avalanche(u64::from(combined_low) ^ bitflip_low),
+        xxh64_avalanche(u64::from(combined_high) ^ bitflip_high),
+    )
+}
+
+fn hash_len_4to8(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    let input = read32(data, 0) + (read32(data, len - 4) << 32);
+    let bitflip = read64(&SECRET, 16) ^ read64(&SECRET, 24);
+    let (mut low, mut high) = mul128(input ^ bitflip, PRIME64_1 + ((len as u64) << 2));
+    high = high.wrapping_add(low << 1);
+    low ^= high >> 3;
+    low ^= low >> 35;
+    low = low.wrapping_mul(PRIME_MX2);
+    low ^= low >> 28;
+    (low, avalanche(high))
+}
+
+fn hash_len_9to16(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    let bitflip_low = read64(&SECRET, 32) ^ read64(&SECRET, 40);
+    let bitflip_high = read64(&SECRET, 48) ^ read64(&SECRET, 56);
+    let input_low = read64(data, 0);
+    let input_high = read64(data, len - 8) ^ bitflip_high;
+    let (mut low, mut high) = mul128(input_low ^ read64(data, len - 8) ^ bitflip_low, PRIME64_1);
+    low = low.wrapping_add(((len - 1) as u64) << 54);
+    high = high
+        .wrapping_add(input_high)
+        .wrapping_add((input_high & 0xFFFF_FFFF).wrapping_mul(PRIME32_2 - 1));
+    low ^= high.swap_bytes();
+    let (h_low, h_high) = mul128(low, PRIME64_2);
+    let h_high = h_high.wrapping_add(high.wrapping_mul(PRIME64_2));
+    (avalanche(h_low), avalanche(h_high))
+}
+
+fn mix16(data: &[u8], offset: usize, secret_offset: usize) -> u64 {
+    mul128_fold64(
+        read64(data, offset) ^ read64(&SECRET, secret_offset),
+        read64(data, offset + 8) ^ read64(&SECRET, secret_offset + 8),
+    )
+}
+
+fn mix32(
+    acc: (u64, u64),
+    data: &[u8],
+    offset_1: usize,
+    offset_2: usize,
+    secret_offset: usize,
+) -> (u64, u64) {
+    let mut low = acc.0.wrapping_add(mix16(data, offset_1, secret_offset));
+    low ^= read64(data, offset_2).wrapping_add(read64(data, offset_2 + 8));
+    let mut high = acc
+        .1
+        .wrapping_add(mix16(data, offset_2, secret_offset + 16));
+    high ^= read64(data, offset_1).wrapping_add(read64(data, offset_1 + 8));
+    (low, high)
+}
+
+fn finalize_mid(acc: (u64, u64), len: usize) -> (u64, u64) {
+    let low = acc.0.wrapping_add(acc.1);
+    let high = acc
+        .0
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(acc.1.wrapping_mul(PRIME64_4))
+        .wrapping_add((len as u64).wrapping_mul(PRIME64_2));
+    (avalanche(low), 0u64.wrapping_sub(avalanche(high)))
+}
+
+fn hash_len_17to128(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    let mut acc = ((len as u64).wrapping_mul(PRIME64_1), 0);
+    if len > 32 {
+        if len > 64 {
+            if len > 96 {
+                acc = mix32(acc, data, 48, len - 64, 96);
+            }
+            acc = mix32(acc, data, 32, len - 48, 64);
+        }
+        acc = mix32(acc, data, 16, len - 32, 32);
+    }
+    acc = mix32(acc, data, 0, len - 16, 0);
+    finalize_mid(acc, len)
+}
+
+fn hash_len_129to240(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    let rounds = len / 32;
+    let mut acc = ((len as u64).wrapping_mul(PRIME64_1), 0);
+    for i in 0..4 {
+        acc = mix32(acc, data, 32 * i, 32 * i + 16, 32 * i);
+    }
+    acc = (avalanche(acc.0), avalanche(acc.1));
+    for i in 4..rounds {
+        let secret_offset = MIDSIZE_START_OFFSET + 32 * (i - 4);
+        acc = mix32(acc, data, 32 * i, 32 * i + 16, secret_offset);
+    }
+    let secret_offset = SECRET_SIZE_MIN - MIDSIZE_LAST_OFFSET - 16;
+    acc = mix32(acc, data, len - 16, len - 32, secret_offset);
+    finalize_mid(acc, len)
+}
+
+fn accumulate_512(acc: &mut [u64; 8], data: &[u8], offset: usize, secret_offset: usize) {
+    for i in 0..8 {
+        let data_val = read64(data, offset + 8 * i);
+        let data_key = data_val ^ read64(&SECRET, secret_offset + 8 * i);
+        acc[i ^ 1] = acc[i ^ 1].wrapping_add(data_val);
+        acc[i] = acc[i].wrapping_add((data_key & 0xFFFF_FFFF).wrapping_mul(data_key >> 32));
+    }
+}
+
+fn scramble(acc: &mut [u64; 8]) {
+    let secret_offset = SECRET.len() - STRIPE_LEN;
+    for (i, value) in acc.iter_mut().enumerate() {
+        let mut a = *value;
+        a ^= a >> 47;
+        a ^= read64(&SECRET, secret_offset + 8 * i);
+        *value = a.wrapping_mul(PRIME32_1);
+    }
+}
+
+fn merge_accs(acc: &[u64; 8], secret_offset: usize, start: u64) -> u64 {
+    let mut result = start;
+    for i in 0..4 {
+        result = result.wrapping_add(mul128_fold64(
+            acc[2 * i] ^ read64(&SECRET, secret_offset + 16 * i),
+            acc[2 * i + 1] ^ read64(&SECRET, secret_offset + 16 * i + 8),
+        ));
+    }
+    avalanche(result)
+}
+
+fn hash_long(data: &[u8]) -> (u64, u64) {
+    let len = data.len();
+    let mut acc = [
+        PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1,
+    ];
+    let stripes_per_block = (SECRET.len() - STRIPE_LEN) / SECRET_CONSUME_RATE;
+    let block_len = STRIPE_LEN * stripes_per_block;
+    let blocks = (len - 1) / block_len;
+
+    for block in 0..blocks {
+        for stripe in 0..stripes_per_block {
+            let offset = block * block_len + stripe * STRIPE_LEN;
+            accumulate_512(&mut acc, data, offset, stripe * SECRET_CONSUME_RATE);
+        }
+        scramble(&mut acc);
+    }
+
+    let stripes = ((len - 1) - block_len * blocks) / STRIPE_LEN;
+    for stripe in 0..stripes {
+        let offset = blocks * block_len + stripe * STRIPE_LEN;
+        accumulate_512(&mut acc, data, offset, stripe * SECRET_CONSUME_RATE);
+    }
+    let secret_offset = SECRET.len() - STRIPE_LEN - SECRET_LASTACC_START;
+    accumulate_512(&mut acc, data, len - STRIPE_LEN, secret_offset);
+
+    let low = merge_accs(
+        &acc,
+        SECRET_MERGEACCS_START,
+        (len as u64).wrapping_mul(PRIME64_1),
+    );
+    let high = merge_accs(
+        &acc,
+        SECRET.len() - STRIPE_LEN - SECRET_MERGEACCS_START,
+        !(len as u64).wrapping_mul(PRIME64_2),
+    );
+    (low, high)
+}
@@ -0,0 +1,157 @@
+//! Guards the algorithm version: regenerates the codes and full digests of all
+//! inputs of `test_data.json` and compares a digest of them per component
+//! against `PINNED`. Any change of these outputs must bump
+//! `ALGORITHM_VERSION` and record the changed components in `version.rs`.
+use std::collections::BTreeMap;
+use std::fs;
+
+use serde_json as json;
+
+#[cfg(feature = "image")]
+use iscc::cid_image::{content_id_image, content_id_image_full};
+use iscc::cid_mixed::content_id_mixed;
+use iscc::cid_text::{content_id_text, content_id_text_full};
+use iscc::code::{CodeKind, FullDigest};
+use iscc::did::{data_id, data_id_full};
+use iscc::hashes::xxhash64;
+use iscc::iid::{instance_id, instance_id_full};
+use iscc::mid::{meta_id, meta_id_full};
+use iscc::version::{AlgorithmVersion, ALGORITHM_VERSION};
+
+/// The `xxhash64` of the outputs of every component and the version they were
+/// generated with.
+const PINNED: &[(&str, u32, u64)] = &[
+    ("meta_id", 1, 0x5b98_ff76_cd26_39de),
+    ("content_id_text", 1, 0x012c_40a3_0234_5540),
+    ("content_id_image", 1, 0xb91d_01f4_5e73_b478),
+    ("content_id_mixed", 1, 0xe653_1b47_6687_b305),
+    ("data_id", 1, 0x58e5_739e_82d6_f58a),
+    ("instance_id", 1, 0x53e9_4153_80d7_1c5f),
+];
+
+fn path(filename: &str) -> String {
+    format!("tests/test_data/{}", filename)
+}
+
+fn hex(full: &FullDigest) -> String {
+    full.digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The outputs of every component for the inputs of `test_data.json`.
+fn outputs() -> BTreeMap<&'static str, Vec<String>> {
+    let content = fs::read_to_string(path("test_data.json")).unwrap();
+    let test_data: BTreeMap<String, BTreeMap<String, json::Value>> =
+        json::from_str(&content).unwrap();
+    let inputs = |function: &str| -> Vec<json::Value> {
+        test_data[function]
+            .iter()
+            .filter(|(name, _)| *name != "required")
+            .map(|(_, case)| case["inputs"].clone())
+            .collect()
+    };
+
+    let mut outputs: BTreeMap<&'static str, Vec<String>> = BTreeMap::new();
+    for input in inputs("meta_id") {
+        let (title, extra) = (input[0].as_str().unwrap(), input[1].as_str().unwrap());
+        let (code, title_trimmed, extra_trimmed) = meta_id(title, extra);
+        let full = hex(&meta_id_full(title, extra));
+        outputs.entry("meta_id").or_default().extend(vec![
+            code,
+            title_trimmed,
+            extra_trimmed,
+            full,
+        ]);
+    }
+    for input in inputs("content_id_text") {
+        let (text, partial) = (input[0].as_str().unwrap(), input[1].as_bool().unwrap());
+        let full = hex(&content_id_text_full(text, partial));
+        outputs
+            .entry("content_id_text")
+            .or_default()
+            .extend(vec![content_id_text(text, partial), full]);
+    }
+    #[cfg(feature = "image")]
+    for input in inputs("content_id_image") {
+        let (file, partial) = (input[0].as_str().unwrap(), input[1].as_bool().unwrap());
+        let full = hex(&content_id_image_full(&path(file), partial).unwrap());
+        let code = content_id_image(&path(file), partial).unwrap();
+        outputs
+            .entry("content_id_image")
+            .or_default()
+            .extend(vec![code, full]);
+    }
+    for input in inputs("content_id_mixed") {
+        let cids: Vec<&str> = input[0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap())
+            .collect();
+        let partial = input[1].as_bool().unwrap();
+        outputs
+            .entry("content_id_mixed")
+            .or_default()
+            .push(content_id_mixed(&cids, partial));
+    }
+    for input in inputs("data_id") {
+        let file = path(input[0].as_str().unwrap());
+        let full = hex(&data_id_full(&file).unwrap());
+        outputs
+            .entry("data_id")
+            .or_default()
+            .extend(vec![data_id(&file).unwrap(), full]);
+    }
+    for input in inputs("instance_id") {
+        let file = path(input[0].as_str().unwrap());
+        let (code, tophash) = instance_id(&file).unwrap();
+        let full = hex(&instance_id_full(&file).unwrap());
+        outputs
+            .entry("instance_id")
+            .or_default()
+            .extend(vec![code, tophash, full]);
+    }
+    outputs
+}
+
+fn kind(component: &str) -> CodeKind {
+    match component {
+        "meta_id" => CodeKind::Meta,
+        "content_id_text" => CodeKind::ContentText { partial: false },
+        "content_id_image" => CodeKind::ContentImage { partial: false },
+        "content_id_mixed" => CodeKind::ContentMixed { partial: false },
+        "data_id" => CodeKind::Data,
+        "instance_id" => CodeKind::Instance,
+        _ => panic!("unknown component {}", component),
+    }
+}
+
+#[test]
+fn test_outputs_match_algorithm_version() {
+    let outputs = outputs();
+    let mut failures = Vec::new();
+    for &(component, pinned_version, pinned_hash) in PINNED {
+        let values = match outputs.get(component) {
+            Some(values) => values,
+            // Components behind disabled features
+            None => continue,
+        };
+        let hash = xxhash64(values.join("\n").as_bytes());
+        let version = ALGORITHM_VERSION.construction_of(kind(component));
+        if hash == pinned_hash && version == AlgorithmVersion(pinned_version) {
+            continue;
+        }
+        if version == AlgorithmVersion(pinned_version) {
+            failures.push(format!(
+                "The outputs of {} changed without a version bump. Bump ALGORITHM_VERSION, \
+                 record the change in version.rs and pin ({:?}, {}, {:#x}).",
+                component, component, ALGORITHM_VERSION.0, hash
+            ));
+        } else {
+            failures.push(format!(
+                "The construction of {} changed in {}, pin ({:?}, {}, {:#x}).",
+                component, version, component, version.0, hash
+            ));
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -1,4 +1,47 @@
-use iscc::{content_id_image, content_id_mixed, content_id_text, data_id, instance_id, meta_id};
+use std::convert::TryFrom;
+
+use iscc::base58;
+#[cfg(feature = "image")]
+use iscc::cid_image::{
+    content_id_image_animated, content_id_image_full, content_id_image_pixels,
+    content_id_image_with_algo, content_id_image_with_metrics, image_preview,
+    image_preview_from_bytes, FrameSampling, ImageAlgo,
+};
+use iscc::cid_mixed::{content_id_mixed_full, content_id_mixed_result};
+use iscc::cid_text::{
+    content_id_text_from_features, content_id_text_full, content_id_text_result,
+    content_id_text_with_options, content_id_text_with_scripts, content_id_text_with_trace,
+    extract_text_features, soft_hash_text_features,
+};
+use iscc::code::Code;
+use iscc::compare::hamming_distance_u64;
+#[cfg(feature = "image")]
+use iscc::content_id_image;
+use iscc::did::{
+    data_id_full, data_id_result, data_id_with_metrics, data_id_with_options, data_id_with_report,
+    data_sketch, extract_data_features,
+};
+use iscc::iid::{
+    instance_id_from_reader, instance_id_full, instance_id_result, instance_id_with_metrics,
+    InstanceAlgo,
+};
+use iscc::metrics::{Metrics, Stage};
+use iscc::mid::{meta_id_full, meta_id_result, meta_id_with_options, meta_id_with_trace};
+use iscc::options::{ChunkingOptions, IsccOptions};
+use iscc::script::Script;
+use iscc::{
+    content_id_mixed, content_id_text, data_id, instance_id, meta_id, minimum_hash,
+    similarity_hash, sliding_window, xxhash32, xxhash64, DataHasher, InstanceHasher,
+};
+
+/// Number of differing bits between the bodies of two component codes.
+fn hamming_distance(a: &str, b: &str) -> u32 {
+    base58::decode(a)[1..]
+        .iter()
+        .zip(base58::decode(b)[1..].iter())
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum()
+}
 
 #[test]
 fn test_meta_id() {
@@ -29,6 +72,15 @@ fn test_content_id_text() {
     assert_eq!(cid_t_p, "Ct7A4zpmccuEv");
 }
 
+#[test]
+fn test_invisible_characters() {
+    let clean = "The Neverending Story by Michael Ende";
+    let dirty = "\u{FEFF}The\u{00A0}Never\u{200B}ending\u{00AD} Story\u{2028}by \u{200D}Michael\u{000B}Ende\u{0000}";
+    assert_eq!(content_id_text(dirty, false), content_id_text(clean, false));
+    assert_eq!(meta_id(dirty, dirty).0, meta_id(clean, clean).0);
+}
+
+#[cfg(feature = "image")]
 #[test]
 fn test_content_id_image() {
     let cid_i = content_id_image("tests/test_data/lenna.jpg", false).unwrap();
@@ -38,6 +90,211 @@ fn test_content_id_image() {
     assert_eq!(cid_i, "CimLoqBRgV32u");
 }
 
+#[cfg(feature = "image")]
+#[test]
+fn test_content_id_image_with_metrics() {
+    let path = "tests/test_data/lenna.jpg";
+    let (result, metrics) = content_id_image_with_metrics(path, false).unwrap();
+    assert_eq!(result.code, "CYmLoqBRgV32u");
+    assert_eq!(metrics.input_bytes, std::fs::metadata(path).unwrap().len());
+    let stages: Vec<Stage> = metrics.stages.iter().map(|(s, _)| *s).collect();
+    assert_eq!(
+        stages,
+        vec![
+            Stage::Reading,
+            Stage::Decoding,
+            Stage::Normalization,
+            Stage::Dct
+        ]
+    );
+}
+
+#[test]
+fn test_namespace() {
+    let text = "The quick brown fox jumps over the lazy dog. Meanwhile the cat watches \
+                from the window sill and wonders why anyone would jump over a dog at all.";
+    let edited = text.replace("window sill", "garden wall");
+    let path = "tests/test_data/lenna.jpg";
+    let file = || std::fs::File::open(path).unwrap();
+
+    // Without a namespace the codes are the standard ones.
+    let standard = IsccOptions::default();
+    let meta = meta_id_with_options(text, "", &standard);
+    assert_eq!(meta, meta_id_result(text, ""));
+    assert!(!meta.namespaced);
+    let content = content_id_text_with_options(text, false, &standard);
+    assert_eq!(content, content_id_text_result(text, false));
+    assert!(!content.namespaced);
+    let data = data_id_with_options(file(), &standard).unwrap();
+    assert_eq!(data, data_id_result(path).unwrap());
+    assert!(!data.namespaced);
+
+    let tenant = |salt: u8| IsccOptions {
+        namespace: Some([salt; 16]),
+        ..IsccOptions::default()
+    };
+    let codes = |options: &IsccOptions| {
+        vec![
+            meta_id_with_options(text, "", options).code,
+            content_id_text_with_options(text, false, options).code,
+            data_id_with_options(file(), options).unwrap().code,
+        ]
+    };
+    let (a, b) = (codes(&tenant(1)), codes(&tenant(2)));
+    assert_eq!(a, codes(&tenant(1)));
+    let standard = codes(&standard);
+    for i in 0..3 {
+        // Unrelated 64-bit codes differ in 32 +- 4 bits.
+        for other in [&b[i], &standard[i]].iter() {
+            let distance = hamming_distance(&a[i], other);
+            assert!((16..=48).contains(&distance), "distance {}", distance);
+        }
+    }
+    assert!(content_id_text_with_options(text, false, &tenant(1)).namespaced);
+    assert!(data_id_with_options(file(), &tenant(1)).unwrap().namespaced);
+
+    // Similar texts are still similar within a namespace.
+    let content = |text: &str| content_id_text_with_options(text, false, &tenant(1)).code;
+    assert!(hamming_distance(&content(text), &content(&edited)) <= 12);
+}
+
+#[test]
+fn test_metrics_aggregate() {
+    let path = "tests/test_data/lenna.jpg";
+    let file = || std::fs::File::open(path).unwrap();
+    let (data, data_metrics) = data_id_with_metrics(file()).unwrap();
+    let (instance, instance_metrics) = instance_id_with_metrics(file()).unwrap();
+    assert_eq!(data.code, data_id(path).unwrap());
+    assert_eq!(instance.code, instance_id(path).unwrap().0);
+
+    let mut total = Metrics::default();
+    total.merge(&data_metrics);
+    total.merge(&instance_metrics);
+    assert_eq!(
+        total.input_bytes,
+        2 * std::fs::metadata(path).unwrap().len()
+    );
+    assert_eq!(total.chunks, data_metrics.chunks);
+    assert_eq!(
+        total.duration(Stage::Reading),
+        data_metrics.duration(Stage::Reading) + instance_metrics.duration(Stage::Reading)
+    );
+    assert_eq!(
+        total.total(),
+        data_metrics.total() + instance_metrics.total()
+    );
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_content_id_image_dct() {
+    let cid = |path| content_id_image_with_algo(path, false, ImageAlgo::Dct).unwrap();
+    let lenna = cid("tests/test_data/lenna.jpg");
+    let recompressed = cid("tests/test_data/lenna_q30.jpg");
+    let cat = cid("tests/test_data/cat.jpg");
+    assert!(hamming_distance(&lenna, &recompressed) <= 4);
+    assert!(hamming_distance(&lenna, &cat) >= 16);
+    assert_ne!(
+        lenna,
+        content_id_image("tests/test_data/lenna.jpg", false).unwrap()
+    );
+    assert_eq!(
+        content_id_image_with_algo("tests/test_data/lenna.jpg", false, ImageAlgo::Default).unwrap(),
+        "CYmLoqBRgV32u"
+    );
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_content_id_image_wavelet() {
+    let cid = |path| content_id_image_with_algo(path, false, ImageAlgo::Wavelet).unwrap();
+    let lenna = cid("tests/test_data/lenna.jpg");
+    let scaled = cid("tests/test_data/lenna_small.png");
+    let cat = cid("tests/test_data/cat.jpg");
+    assert!(hamming_distance(&lenna, &scaled) <= 4);
+    assert!(hamming_distance(&lenna, &cat) >= 16);
+    assert_eq!(
+        content_id_image("tests/test_data/lenna_small.png", false).unwrap(),
+        content_id_image_with_algo("tests/test_data/lenna_small.png", false, ImageAlgo::Default)
+            .unwrap()
+    );
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_image_preview() {
+    let path = "tests/test_data/lenna.jpg";
+    let preview = image_preview(path).unwrap();
+    assert_eq!(preview.pixels.len(), 1024);
+    assert_eq!(
+        content_id_image_pixels(&preview.pixels, false).unwrap(),
+        content_id_image(path, false).unwrap()
+    );
+
+    let png = preview.to_png_bytes().unwrap();
+    assert_eq!(image_preview_from_bytes(&png).unwrap(), preview);
+
+    let art = preview.to_ascii_art();
+    assert_eq!(art.lines().count(), 32);
+    assert!(art.lines().all(|line| line.chars().count() == 32));
+
+    assert!(content_id_image_pixels(&preview.pixels[1..], false).is_err());
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_content_id_image_animated() {
+    let sampling = FrameSampling::default();
+    for still in &["tests/test_data/cat.png", "tests/test_data/cat.gif"] {
+        assert_eq!(
+            content_id_image_animated(still, false, sampling).unwrap(),
+            content_id_image(still, false).unwrap()
+        );
+    }
+
+    let anim =
+        content_id_image_animated("tests/test_data/lenna_anim.gif", false, sampling).unwrap();
+    let reencoded =
+        content_id_image_animated("tests/test_data/lenna_anim_reencoded.gif", false, sampling)
+            .unwrap();
+    assert!(hamming_distance(&anim, &reencoded) <= 6);
+    assert_ne!(
+        anim,
+        content_id_image("tests/test_data/lenna_anim.gif", false).unwrap()
+    );
+
+    let sampled = FrameSampling {
+        step: 3,
+        max_frames: 2,
+    };
+    let anim_sampled =
+        content_id_image_animated("tests/test_data/lenna_anim.gif", false, sampled).unwrap();
+    assert!(hamming_distance(&anim, &anim_sampled) <= 12);
+    assert!(content_id_image_animated(
+        "tests/test_data/lenna_anim.gif",
+        false,
+        FrameSampling {
+            step: 0,
+            max_frames: 2
+        }
+    )
+    .is_err());
+
+    // An APNG with an animation control chunk is not hashed as its first frame
+    let png = std::fs::read("tests/test_data/cat.png").unwrap();
+    let mut apng = png[..33].to_vec();
+    apng.extend(b"\x00\x00\x00\x08acTL\x00\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00");
+    apng.extend(&png[33..]);
+    let path = std::env::temp_dir().join(format!("iscc-anim-{}.png", std::process::id()));
+    std::fs::write(&path, &apng).unwrap();
+    let result = content_id_image_animated(path.to_str().unwrap(), false, sampling);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        result,
+        Err(iscc::IsccError::UnsupportedAnimation(_))
+    ));
+}
+
 #[test]
 fn test_content_id_mixed() {
     let cid_t_1 = content_id_text("Some Text", false);
@@ -49,9 +306,12 @@ fn test_content_id_mixed() {
     let cid_m = content_id_mixed(&[&cid_t_1, &cid_t_2], false);
     assert_eq!(cid_m, "CM3kHkNRGvnhB".to_string());
 
-    let cid_i = content_id_image("tests/test_data/lenna.jpg", false).unwrap();
-    let cid_m = content_id_mixed(&[&cid_t_1, &cid_t_2, &cid_i], false);
-    assert_eq!(cid_m, "CM3hswzATv9d3".to_string());
+    #[cfg(feature = "image")]
+    {
+        let cid_i = content_id_image("tests/test_data/lenna.jpg", false).unwrap();
+        let cid_m = content_id_mixed(&[&cid_t_1, &cid_t_2, &cid_i], false);
+        assert_eq!(cid_m, "CM3hswzATv9d3".to_string());
+    }
 }
 
 #[test]
@@ -102,3 +362,513 @@ fn test_instance_id() {
         )
     );
 }
+
+#[test]
+fn test_results_carry_digest() {
+    fn assert_digest(code: &str, header: u8, digest: &[u8]) {
+        assert_eq!(digest.len(), 8);
+        let mut expected = vec![header];
+        expected.extend(digest);
+        assert_eq!(base58::decode(code), expected);
+    }
+
+    let mid = meta_id_result("Die Unendliche Geschichte", "");
+    assert_eq!(mid.code, "CCAKevDpE1eEL");
+    assert_eq!(mid.to_string(), mid.code);
+    assert_digest(&mid.code, 0x00, &mid.digest);
+
+    let cid_t = content_id_text_result("", true);
+    assert_eq!(cid_t.code, content_id_text("", true));
+    assert_digest(&cid_t.code, 0x11, &cid_t.digest);
+
+    #[cfg(feature = "image")]
+    {
+        let cid_i =
+            iscc::cid_image::content_id_image_result("tests/test_data/lenna.jpg", false).unwrap();
+        assert_eq!(cid_i.code, "CYmLoqBRgV32u");
+        assert_digest(&cid_i.code, 0x12, &cid_i.digest);
+    }
+
+    let cid_t_1 = content_id_text("Some Text", false);
+    let cid_t_2 = content_id_text("Another Text", false);
+    let cid_m = content_id_mixed_result(&[&cid_t_1, &cid_t_2], false);
+    assert_eq!(cid_m.code, "CM3kHkNRGvnhB");
+    assert_digest(&cid_m.code, 0x18, &cid_m.digest);
+
+    let did = data_id_result("tests/test_data/lenna.jpg").unwrap();
+    assert_eq!(did.code, data_id("tests/test_data/lenna.jpg").unwrap());
+    assert_eq!(did.to_string(), did.code);
+    assert_digest(&did.code, 0x20, &did.digest);
+
+    let iid = instance_id_result("tests/test_data/cat.png").unwrap();
+    assert_eq!(iid.code, "CR6xpnrJkvQDH");
+    assert_eq!(iid.tophash[..16], hex_encode(&iid.digest));
+    assert_digest(&iid.code, 0x30, &iid.digest);
+}
+
+#[test]
+fn test_full_digest_prefix() {
+    let mid = meta_id_full("Die Unendliche Geschichte", "Michael Ende");
+    assert_eq!(
+        mid.to_code(64).unwrap().to_string(),
+        meta_id("Die Unendliche Geschichte", "Michael Ende").0
+    );
+
+    let text = "Hello\n\tWorld! Some more words to fill the windows.";
+    let cid_t = content_id_text_full(text, true);
+    assert_eq!(
+        cid_t.to_code(64).unwrap().to_string(),
+        content_id_text(text, true)
+    );
+
+    let cid_t_2 = content_id_text_full("Another Text", false);
+    let cid_m = content_id_mixed_full(&[cid_t, cid_t_2], false);
+    assert_eq!(
+        cid_m.to_code(64).unwrap().to_string(),
+        content_id_mixed(
+            &[
+                &content_id_text(text, true),
+                &content_id_text("Another Text", false)
+            ],
+            false
+        )
+    );
+
+    #[cfg(feature = "image")]
+    {
+        let cid_i = content_id_image_full("tests/test_data/lenna.jpg", false).unwrap();
+        assert_eq!(cid_i.to_code(64).unwrap().to_string(), "CYmLoqBRgV32u");
+    }
+
+    let did = data_id_full("tests/test_data/lenna.jpg").unwrap();
+    assert_eq!(
+        did.to_code(64).unwrap().to_string(),
+        data_id("tests/test_data/lenna.jpg").unwrap()
+    );
+
+    let iid = instance_id_full("tests/test_data/cat.png").unwrap();
+    assert_eq!(iid.to_code(64).unwrap().to_string(), "CR6xpnrJkvQDH");
+    let (_, tophash) = instance_id("tests/test_data/cat.png").unwrap();
+    assert_eq!(tophash, hex_encode(&iid.digest));
+    let long = iid.to_code(128).unwrap();
+    assert_eq!(long.body(), &iid.digest[..16]);
+    assert!(long.to_string().starts_with("CR6xpnrJkvQDH"));
+}
+
+/// A code of every component kind.
+fn component_codes() -> Vec<String> {
+    let cid_t_1 = content_id_text("Some Text", false);
+    let cid_t_2 = content_id_text("Another Text", true);
+    #[cfg_attr(not(feature = "image"), allow(unused_mut))]
+    let mut codes = vec![
+        meta_id("Die Unendliche Geschichte", "").0,
+        content_id_mixed(&[&cid_t_1, &cid_t_2], true),
+        cid_t_1,
+        cid_t_2,
+        data_id("tests/test_data/lenna.jpg").unwrap(),
+        instance_id("tests/test_data/cat.png").unwrap().0,
+    ];
+    #[cfg(feature = "image")]
+    codes.push(content_id_image("tests/test_data/lenna.jpg", false).unwrap());
+    codes
+}
+
+#[test]
+fn test_code_body_u64_round_trip() {
+    let codes = component_codes();
+    for code in &codes {
+        let parsed: Code = code.parse().unwrap();
+        let body = parsed.body_u64().unwrap();
+        let rebuilt = Code::from_parts(parsed.kind(), 64, body).unwrap();
+        assert_eq!(rebuilt.to_string(), *code);
+    }
+
+    let a: Code = content_id_text("Some Text", false).parse().unwrap();
+    let b: Code = content_id_text("Some Text!", false).parse().unwrap();
+    assert_eq!(
+        hamming_distance_u64(a.body_u64().unwrap(), b.body_u64().unwrap()),
+        hamming_distance(&a.to_string(), &b.to_string())
+    );
+}
+
+#[test]
+fn test_code_bytes() {
+    for code in component_codes() {
+        let parsed: Code = code.parse().unwrap();
+        let bytes = parsed.to_bytes();
+        assert_eq!(base58::encode(&bytes), parsed.to_string());
+        assert_eq!(base58::encode(&bytes), code);
+        assert_eq!(Code::try_from(&bytes[..]).unwrap(), parsed);
+        assert_eq!(Vec::from(&parsed), bytes);
+    }
+    let full = meta_id_full("Die Unendliche Geschichte", "");
+    for &bits in [64, 128, 192, 256].iter() {
+        let code = full.to_code(bits).unwrap();
+        let bytes = code.to_bytes();
+        assert_eq!(bytes.len(), 1 + bits as usize / 8);
+        assert_eq!(base58::encode(&bytes), code.to_string());
+        assert_eq!(Code::try_from(&bytes[..]).unwrap(), code);
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn test_robust_text() {
+    let invoice = std::fs::read_to_string("tests/test_data/invoice.txt").unwrap();
+    // The same invoice with every digit changed.
+    let renumbered: String = invoice
+        .chars()
+        .map(|c| match c.to_digit(10) {
+            Some(d) => std::char::from_digit((d + 3) % 10, 10).unwrap(),
+            None => c,
+        })
+        .collect();
+    let robust = IsccOptions {
+        ignore_numbers: true,
+        strip_dates: true,
+        ..IsccOptions::default()
+    };
+    let standard = |text: &str| content_id_text_result(text, false).code;
+    let robust = |text: &str| content_id_text_with_options(text, false, &robust);
+
+    assert!(hamming_distance(&standard(&invoice), &standard(&renumbered)) > 8);
+    let (a, b) = (robust(&invoice), robust(&renumbered));
+    assert_eq!(a, b);
+    assert!(a.robust && !a.namespaced);
+    assert!(!content_id_text_result(&invoice, false).robust);
+
+    let other = "Minutes of the annual general meeting held on 2021-05-12 in Springfield. \
+                 Present were 14 members of the board, the auditor and 3 guests. The chair \
+                 opened the meeting at 10:00 and confirmed that it had been convened in due \
+                 time. Item 1 of the agenda, the report of the board, was accepted with 12 \
+                 votes in favour and 2 abstentions. Item 2, the budget for 2022, was \
+                 postponed to the next meeting because the figures were not complete.";
+    let distance = hamming_distance(&a.code, &robust(other).code);
+    assert!(distance >= 16, "distance {}", distance);
+}
+
+#[test]
+fn test_max_features() {
+    // 10 MB without whitespace, like minified code or a base64 blob
+    let mut blob = String::with_capacity(10_000_000);
+    let mut state: u32 = 1;
+    while blob.len() < 10_000_000 {
+        state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        blob.push(char::from(b'a' + (state >> 16) as u8 % 26));
+    }
+    let capped = IsccOptions {
+        max_features: Some(10_000),
+        ..IsccOptions::default()
+    };
+    let start = std::time::Instant::now();
+    let a = content_id_text_with_options(&blob, false, &capped);
+    assert!(start.elapsed().as_secs() < 60, "took {:?}", start.elapsed());
+    let b = content_id_text_with_options(&blob, false, &capped);
+    assert_eq!(a, b);
+    assert!(a.sampled && !a.robust && !a.namespaced);
+
+    let invoice = std::fs::read_to_string("tests/test_data/invoice.txt").unwrap();
+    for text in &[&invoice[..], "", "Short text"] {
+        let result = content_id_text_with_options(text, false, &capped);
+        assert_eq!(result, content_id_text_result(text, false));
+        assert!(!result.sampled);
+    }
+    let sampled = IsccOptions {
+        max_features: Some(100),
+        ..IsccOptions::default()
+    };
+    assert!(content_id_text_with_options(&invoice, false, &sampled).sampled);
+}
+
+#[test]
+fn test_extract_features() {
+    // The least significant bits of the 64 minimum hashes, first hash in the
+    // most significant bit.
+    let pack = |features: Vec<u32>| -> Vec<u8> {
+        minimum_hash(features)
+            .chunks(8)
+            .map(|lsb| lsb.iter().fold(0, |byte, x| byte << 1 | (x & 1) as u8))
+            .collect()
+    };
+
+    let invoice = std::fs::read_to_string("tests/test_data/invoice.txt").unwrap();
+    let options = [
+        IsccOptions::default(),
+        IsccOptions {
+            namespace: Some([7; 16]),
+            ..IsccOptions::default()
+        },
+        IsccOptions {
+            ignore_numbers: true,
+            strip_dates: true,
+            ..IsccOptions::default()
+        },
+        IsccOptions {
+            max_features: Some(100),
+            ..IsccOptions::default()
+        },
+    ];
+    for options in options.iter() {
+        for text in &[&invoice[..], "", "Short text"] {
+            let features = extract_text_features(text, options);
+            let code = content_id_text_with_options(text, false, options).code;
+            assert_eq!(pack(features.clone()), base58::decode(&code)[1..]);
+            assert_eq!(
+                soft_hash_text_features(&features).unwrap()[..],
+                base58::decode(&code)[1..]
+            );
+        }
+    }
+    let sampled = extract_text_features(&invoice, &options[3]).len();
+    assert!(sampled <= 100 && sampled < extract_text_features(&invoice, &options[0]).len());
+
+    let path = "tests/test_data/lenna.jpg";
+    let file = || std::fs::File::open(path).unwrap();
+    for options in options[..2].iter() {
+        let features = extract_data_features(file(), &ChunkingOptions::from(options)).unwrap();
+        assert_eq!(features.len(), 112);
+        let code = data_id_with_options(file(), options).unwrap().code;
+        assert_eq!(pack(features), base58::decode(&code)[1..]);
+    }
+    let features = extract_data_features(file(), &ChunkingOptions::default()).unwrap();
+    assert_eq!(pack(features), base58::decode(&data_id(path).unwrap())[1..]);
+    let empty = extract_data_features(&b""[..], &ChunkingOptions::default()).unwrap();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_scripts() {
+    let invoice = std::fs::read_to_string("tests/test_data/invoice.txt").unwrap();
+    let fixtures = [
+        (&invoice[..], Some(Script::Latin)),
+        (
+            "The Neverending Story is a fantasy novel by the German writer Michael Ende.",
+            Some(Script::Latin),
+        ),
+        (
+            "Бесконечная история — роман немецкого писателя Михаэля Энде.",
+            Some(Script::Cyrillic),
+        ),
+        (
+            "《永远讲不完的故事》是德国作家米切尔·恩德的奇幻小说。",
+            Some(Script::Han),
+        ),
+        (
+            "Die unendliche Geschichte (Бесконечная история, 永远讲不完的故事)",
+            Some(Script::Latin),
+        ),
+        ("", None),
+        ("2021-03-15 12:00", None),
+    ];
+    for &(text, script) in fixtures.iter() {
+        for &partial in [false, true].iter() {
+            let annotated = content_id_text_with_scripts(text, partial);
+            assert_eq!(annotated.result, content_id_text_result(text, partial));
+            assert_eq!(annotated.to_string(), content_id_text(text, partial));
+            assert_eq!(annotated.dominant_script, script, "{}", text);
+        }
+    }
+
+    let mixed = content_id_text_with_scripts(fixtures[4].0, false);
+    let scripts: Vec<Script> = mixed.scripts.iter().map(|&(s, _)| s).collect();
+    assert_eq!(scripts, vec![Script::Latin, Script::Cyrillic, Script::Han]);
+    let total: f64 = mixed.scripts.iter().map(|&(_, ratio)| ratio).sum();
+    assert!((total - 1.0).abs() < 1e-9);
+    // 23 Latin, 18 Cyrillic and 8 Han letters, punctuation is not counted.
+    assert_eq!(mixed.scripts[0].1, 23.0 / 49.0);
+    assert_eq!(mixed.scripts[2].1, 8.0 / 49.0);
+}
+
+#[test]
+fn test_trace() {
+    let text = std::fs::read_to_string("tests/test_data/invoice.txt").unwrap();
+    let (result, trace) = content_id_text_with_trace(&text, false, usize::MAX);
+    assert_eq!(result, content_id_text_result(&text, false));
+    let steps: Vec<&str> = trace.steps.iter().map(|s| s.step).collect();
+    assert_eq!(
+        steps,
+        vec![
+            "clean whitespace and control characters",
+            "trim whitespace",
+            "lower case",
+            "NFD",
+            "remove characters other than letters, numbers, symbols and whitespace",
+            "NFKC",
+            "whitespace removal",
+        ]
+    );
+    // Every step starts from the result of the previous one.
+    assert_eq!(trace.steps[0].before, text.chars().count());
+    for pair in trace.steps.windows(2) {
+        assert_eq!(pair[0].after, pair[1].before);
+    }
+    // The final output reproduces the code when windowed and hashed.
+    let normalized = &trace.steps.last().unwrap().preview;
+    let features: Vec<u32> = sliding_window(normalized, 13)
+        .iter()
+        .map(|w| {
+            let spaced: Vec<String> = w.chars().map(String::from).collect();
+            xxhash32(spaced.join(" ").as_bytes())
+        })
+        .collect();
+    assert_eq!(
+        content_id_text_from_features(&features, false).unwrap(),
+        result.code
+    );
+
+    let (short, short_trace) = content_id_text_with_trace(&text, false, 20);
+    assert_eq!(short, result);
+    assert!(short_trace
+        .steps
+        .iter()
+        .all(|s| s.preview.chars().count() <= 20));
+
+    let title = "  Die \u{FB01}ne Unendliche Geschichte \u{2014} ".repeat(8);
+    let (meta, trace) = meta_id_with_trace(&title, "Michael Ende", usize::MAX);
+    assert_eq!(meta, meta_id_result(&title, "Michael Ende"));
+    for input in &["title", "extra"] {
+        let steps: Vec<&str> = trace.steps_of(input).map(|s| s.step).collect();
+        assert_eq!(steps.first(), Some(&"repair typography"));
+        assert_eq!(steps[7], "whitespace collapse");
+        assert_eq!(steps.last(), Some(&"trim to 128 bytes"));
+    }
+    let trim = trace.steps_of("title").last().unwrap();
+    assert!(trim.before > 128 && trim.after <= 128);
+    assert!(trim.to_string().starts_with("title: trim to 128 bytes: "));
+    assert_eq!(trim.preview, meta.title);
+    let concat = format!("{} {}", meta.title, meta.extra);
+    let digests: Vec<u64> = sliding_window(&concat, 4)
+        .iter()
+        .map(|n| xxhash64(n.as_bytes()))
+        .collect();
+    let mut digest = vec![0];
+    digest.extend(similarity_hash(digests));
+    assert_eq!(base58::encode(&digest), meta.code);
+}
+
+/// The Data-ID of `data` from every entry point, or the error message.
+fn data_codes(data: &[u8]) -> Vec<(&'static str, Result<String, String>)> {
+    let path = std::env::temp_dir().join(format!("iscc-data-{}", std::process::id()));
+    std::fs::write(&path, data).unwrap();
+    let path = path.to_str().unwrap();
+    let mut hasher = DataHasher::new();
+    hasher.update(data);
+    let codes = vec![
+        ("data_id", data_id(path).map_err(|e| e.to_string())),
+        (
+            "data_id_result",
+            data_id_result(path)
+                .map(|r| r.code)
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "data_id_full",
+            data_id_full(path)
+                .map(|full| full.to_code(64).unwrap().to_string())
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "data_id_with_report",
+            data_id_with_report(data)
+                .map(|(code, _)| code)
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "data_id_with_metrics",
+            data_id_with_metrics(data)
+                .map(|(r, _)| r.code)
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "data_id_with_options",
+            data_id_with_options(data, &IsccOptions::default())
+                .map(|r| r.code)
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "data_sketch",
+            data_sketch(data)
+                .map(|s| s.to_data_code())
+                .map_err(|e| e.to_string()),
+        ),
+        (
+            "DataHasher",
+            hasher.finish().map(|r| r.code).map_err(|e| e.to_string()),
+        ),
+    ];
+    std::fs::remove_file(path).unwrap();
+    codes
+}
+
+/// The Instance-ID and tophash of `data` from every entry point.
+fn instance_codes(data: &[u8]) -> Vec<(&'static str, (String, String))> {
+    let path = std::env::temp_dir().join(format!("iscc-instance-{}", std::process::id()));
+    std::fs::write(&path, data).unwrap();
+    let path = path.to_str().unwrap();
+    let mut hasher = InstanceHasher::new();
+    hasher.update(data);
+    let full = instance_id_full(path).unwrap();
+    let codes = vec![
+        ("instance_id", instance_id(path).unwrap()),
+        (
+            "instance_id_result",
+            instance_id_result(path).unwrap().into(),
+        ),
+        (
+            "instance_id_full",
+            (
+                full.to_code(64).unwrap().to_string(),
+                hex::encode(full.digest),
+            ),
+        ),
+        (
+            "instance_id_from_reader",
+            instance_id_from_reader(data, InstanceAlgo::Default).unwrap(),
+        ),
+        (
+            "instance_id_with_metrics",
+            instance_id_with_metrics(data).unwrap().0.into(),
+        ),
+        ("InstanceHasher", hasher.finish().into()),
+    ];
+    std::fs::remove_file(path).unwrap();
+    codes
+}
+
+#[test]
+fn test_empty_input() {
+    // Like the reference implementation there is no Data-ID of empty data.
+    for (name, code) in data_codes(b"") {
+        assert_eq!(code, Err("Input is empty".to_string()), "{}", name);
+    }
+    // The Instance-ID of empty data is the tophash of a single empty leaf.
+    for (name, code) in instance_codes(b"") {
+        assert_eq!(
+            code,
+            (
+                "CR4ATDsziWVwB".to_string(),
+                "1406e05881e299367766d313e26c05564ec91bf721d31726bd6e46e60689539a".to_string()
+            ),
+            "{}",
+            name
+        );
+    }
+    assert_eq!(
+        hex::encode(iscc::iid::top_hash(&[])),
+        "1406e05881e299367766d313e26c05564ec91bf721d31726bd6e46e60689539a"
+    );
+
+    // All entry points agree on non-empty data.
+    let data = std::fs::read("tests/test_data/lenna.jpg").unwrap();
+    let expected = data_id("tests/test_data/lenna.jpg").unwrap();
+    for (name, code) in data_codes(&data) {
+        assert_eq!(code.as_ref(), Ok(&expected), "{}", name);
+    }
+    let expected = instance_id("tests/test_data/lenna.jpg").unwrap();
+    for (name, code) in instance_codes(&data) {
+        assert_eq!(code, expected, "{}", name);
+    }
+}
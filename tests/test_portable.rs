@@ -0,0 +1,185 @@
+//! Portable known-answer tests. Every code, encoding and binary format of
+//! `tests/test_data/portable.json` must be reproduced bit for bit on every
+//! platform, independent of its byte order and pointer width. CI runs this
+//! suite unchanged on x86_64 and ARM64.
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json as json;
+
+use iscc::base58;
+use iscc::bloom::text_feature_filter;
+#[cfg(feature = "image")]
+use iscc::cid_image::content_id_image_full;
+use iscc::cid_text::{content_id_text_full, extract_text_features};
+use iscc::code::{pack_codes, Code, FullDigest};
+#[cfg(feature = "image")]
+use iscc::content_id_image;
+use iscc::did::data_id_full;
+use iscc::hashes::{minhash_to_bytes_with, SketchEncoding};
+use iscc::iid::instance_id_full;
+use iscc::mid::meta_id_full;
+use iscc::options::IsccOptions;
+use iscc::{
+    content_id_mixed, content_id_text, data_id, instance_id, meta_id, minimum_hash, xxhash64,
+    DataHasher, InstanceHasher,
+};
+
+fn get_path(filename: &str) -> String {
+    let path: PathBuf = ["tests", "test_data", filename].iter().collect();
+    path.to_string_lossy().into_owned()
+}
+
+fn load() -> json::Value {
+    let data = fs::read_to_string(get_path("portable.json")).unwrap();
+    json::from_str(&data).unwrap()
+}
+
+fn cases<'a>(suite: &'a json::Value, function: &str) -> &'a Vec<json::Value> {
+    let cases = suite[function].as_array().unwrap();
+    assert!(!cases.is_empty(), "no cases for {}", function);
+    cases
+}
+
+/// Checks the code in all encodings of the case outputs, and the 256-bit
+/// digest if the case has one.
+fn check(name: &str, outputs: &json::Value, code: &str, full: Option<FullDigest>) {
+    let parsed: Code = code.parse().unwrap();
+    let bytes = parsed.to_bytes();
+    let mut actual = vec![
+        ("code", code.to_string()),
+        ("hex", hex::encode(&bytes)),
+        ("checked", base58::encode_checked(&bytes)),
+    ];
+    if outputs.get("full").is_some() {
+        let full = full.unwrap();
+        actual.push(("full", hex::encode(full.digest)));
+        actual.push(("code_256", full.to_code(256).unwrap().to_string()));
+    }
+    for (encoding, value) in actual {
+        assert_eq!(
+            value,
+            outputs[encoding].as_str().unwrap(),
+            "{} ({})",
+            name,
+            encoding
+        );
+    }
+}
+
+#[test]
+fn test_portable_meta_id() {
+    let suite = load();
+    for case in cases(&suite, "meta_id") {
+        let title = case["inputs"][0].as_str().unwrap();
+        let extra = case["inputs"][1].as_str().unwrap();
+        let (code, _, _) = meta_id(title, extra);
+        let full = meta_id_full(title, extra);
+        check(title, &case["outputs"], &code, Some(full));
+    }
+}
+
+#[test]
+fn test_portable_content_id_text() {
+    let suite = load();
+    for case in cases(&suite, "content_id_text") {
+        let text = case["inputs"][0].as_str().unwrap();
+        let partial = case["inputs"][1].as_bool().unwrap();
+        let code = content_id_text(text, partial);
+        let full = content_id_text_full(text, partial);
+        check(text, &case["outputs"], &code, Some(full));
+    }
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_portable_content_id_image() {
+    let suite = load();
+    for case in cases(&suite, "content_id_image") {
+        let path = get_path(case["inputs"][0].as_str().unwrap());
+        let partial = case["inputs"][1].as_bool().unwrap();
+        let code = content_id_image(&path, partial).unwrap();
+        let full = content_id_image_full(&path, partial).unwrap();
+        check(&path, &case["outputs"], &code, Some(full));
+    }
+}
+
+#[test]
+fn test_portable_content_id_mixed() {
+    let suite = load();
+    for case in cases(&suite, "content_id_mixed") {
+        let codes: Vec<&str> = case["inputs"][0]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap())
+            .collect();
+        let partial = case["inputs"][1].as_bool().unwrap();
+        let code = content_id_mixed(&codes, partial);
+        check(&codes.join(","), &case["outputs"], &code, None);
+    }
+}
+
+#[test]
+fn test_portable_data_id() {
+    let suite = load();
+    for case in cases(&suite, "data_id") {
+        let path = get_path(case["inputs"][0].as_str().unwrap());
+        let code = data_id(&path).unwrap();
+        let full = data_id_full(&path).unwrap();
+        check(&path, &case["outputs"], &code, Some(full));
+    }
+}
+
+#[test]
+fn test_portable_instance_id() {
+    let suite = load();
+    for case in cases(&suite, "instance_id") {
+        let path = get_path(case["inputs"][0].as_str().unwrap());
+        let (code, tophash) = instance_id(&path).unwrap();
+        let full = instance_id_full(&path).unwrap();
+        check(&path, &case["outputs"], &code, Some(full));
+        assert_eq!(tophash, case["outputs"]["tophash"].as_str().unwrap());
+    }
+}
+
+#[test]
+fn test_portable_serialization() {
+    let suite = load();
+    for case in cases(&suite, "serialization") {
+        let format = case["format"].as_str().unwrap();
+        let inputs = &case["inputs"];
+        let file = || fs::read(get_path(inputs[0].as_str().unwrap())).unwrap();
+        let text = || String::from_utf8(file()).unwrap();
+        let prefix = || file()[..inputs[1].as_u64().unwrap() as usize].to_vec();
+        let sketch = || minimum_hash(extract_text_features(&text(), &IsccOptions::default()));
+        let actual = match format {
+            "sketch_fixed" => hex::encode(minhash_to_bytes_with(&sketch(), SketchEncoding::Fixed)),
+            "sketch_varint" => {
+                hex::encode(minhash_to_bytes_with(&sketch(), SketchEncoding::Varint))
+            }
+            "bloom_filter" => hex::encode(text_feature_filter(&text(), 13, 1024, 4).to_bytes()),
+            "data_checkpoint_xxhash64" => {
+                let mut hasher = DataHasher::new();
+                hasher.update(&prefix());
+                format!("{:016x}", xxhash64(&hasher.checkpoint()))
+            }
+            "instance_checkpoint" => {
+                let mut hasher = InstanceHasher::new();
+                hasher.update(&prefix());
+                hex::encode(hasher.checkpoint().unwrap())
+            }
+            "bundle" => {
+                let codes: Vec<Code> = inputs[0]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|v| v.as_str().unwrap().parse().unwrap())
+                    .collect();
+                hex::encode(pack_codes(&codes))
+            }
+            _ => panic!("unknown format {}", format),
+        };
+        assert_eq!(actual, case["outputs"].as_str().unwrap(), "{}", format);
+    }
+}
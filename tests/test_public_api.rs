@@ -0,0 +1,363 @@
+//! Snapshot of the public items of the crate. Update `PUBLIC_API` when the
+//! public surface changes on purpose.
+//!
+//! The snapshot lists the `pub` items declared at the top level of every
+//! public module, including items behind features, as `module::item`.
+//! Methods and items of private modules are not listed.
+use std::fs;
+
+const PUBLIC_API: &[&str] = &[
+    "Code = crate::code::Code",
+    "CodeKind = crate::code::CodeKind",
+    "CodeResult = crate::result::CodeResult",
+    "DataHasher = crate::did::DataHasher",
+    "FullDigest = crate::code::FullDigest",
+    "InstanceHasher = crate::iid::InstanceHasher",
+    "InstanceResult = crate::iid::InstanceResult",
+    "IsccError = crate::error::IsccError",
+    "IsccOptions = crate::options::IsccOptions",
+    "MetaResult = crate::mid::MetaResult",
+    "base58",
+    "base58::decode",
+    "base58::decode_checked",
+    "base58::encode",
+    "base58::encode_checked",
+    "base58::try_decode",
+    "bench",
+    "bench::Throughput",
+    "bench::bench_data_code",
+    "bench::bench_text_code",
+    "bench::data_code",
+    "bench::data_input",
+    "bench::text_code",
+    "bench::text_input",
+    "bloom",
+    "bloom::BloomFilter",
+    "bloom::text_feature_filter",
+    "cache",
+    "cache::FileIscc",
+    "cache::IsccCache",
+    "cache::MemoryCache",
+    "cache::iscc_from_file_cached",
+    "cid_image",
+    "cid_image::FrameSampling",
+    "cid_image::ImageAlgo",
+    "cid_image::ImagePreview",
+    "cid_image::content_id_image",
+    "cid_image::content_id_image_animated",
+    "cid_image::content_id_image_full",
+    "cid_image::content_id_image_pixels",
+    "cid_image::content_id_image_result",
+    "cid_image::content_id_image_with_algo",
+    "cid_image::content_id_image_with_metrics",
+    "cid_image::dct",
+    "cid_image::image_hash",
+    "cid_image::image_hash_dct",
+    "cid_image::image_hash_full",
+    "cid_image::image_hash_wavelet",
+    "cid_image::image_normalize",
+    "cid_image::image_preview",
+    "cid_image::image_preview_from_bytes",
+    "cid_mixed",
+    "cid_mixed::content_id_mixed",
+    "cid_mixed::content_id_mixed_full",
+    "cid_mixed::content_id_mixed_result",
+    "cid_text",
+    "cid_text::CacheStats",
+    "cid_text::IncrementalTextCode",
+    "cid_text::TextResult",
+    "cid_text::TEXT_SEGMENT_SIZE",
+    "cid_text::content_id_text",
+    "cid_text::content_id_text_from_features",
+    "cid_text::content_id_text_full",
+    "cid_text::content_id_text_html",
+    "cid_text::content_id_text_parallel",
+    "cid_text::content_id_text_result",
+    "cid_text::content_id_text_with_metrics",
+    "cid_text::content_id_text_with_options",
+    "cid_text::content_id_text_with_scripts",
+    "cid_text::content_id_text_with_trace",
+    "cid_text::extract_text_features",
+    "cid_text::soft_hash_text_features",
+    "cid_text::text_containment",
+    "code",
+    "code::Code",
+    "code::CodeKind",
+    "code::CodeReader",
+    "code::FullDigest",
+    "code::Iscc",
+    "code::MAX_COMPONENTS",
+    "code::canonicalize",
+    "code::pack_codes",
+    "code::unpack_codes",
+    "compare",
+    "compare::cluster",
+    "compare::ct_eq",
+    "compare::distance",
+    "compare::hamming_distance_u64",
+    "compare::is_comparable",
+    "compare::match_pairs",
+    "compare::nearest",
+    "compare::nearest_parallel",
+    "compare::shard_key",
+    "compare::shard_keys_within",
+    "compare::within",
+    "constants",
+    "constants::CHUNKING_GEAR",
+    "constants::MINHASH_PERMUTATIONS",
+    "content_id_image = crate::cid_image::content_id_image",
+    "content_id_mixed = crate::cid_mixed::content_id_mixed",
+    "content_id_text = crate::cid_text::content_id_text",
+    "data_id = crate::did::data_id",
+    "did",
+    "did::Chunk",
+    "did::ChunkDiff",
+    "did::ChunkInfo",
+    "did::Chunker",
+    "did::DataHasher",
+    "did::DataReport",
+    "did::DataSimilarity",
+    "did::DataSketch",
+    "did::GearChunker",
+    "did::RabinChunker",
+    "did::chunk_length",
+    "did::data_chunks",
+    "did::data_id",
+    "did::data_id_full",
+    "did::data_id_result",
+    "did::data_id_with_metrics",
+    "did::data_id_with_options",
+    "did::data_id_with_report",
+    "did::data_similarity",
+    "did::data_sketch",
+    "did::diff_reports",
+    "did::extract_data_features",
+    "error",
+    "error::IsccError",
+    "extract",
+    "extract::TextCodeResult",
+    "extract::content_id_text_from_document",
+    "hamming_distance_u64 = crate::compare::hamming_distance_u64",
+    "file",
+    "file::BestEffortResult",
+    "file::iscc_best_effort",
+    "hashes",
+    "hashes::BitOrder",
+    "hashes::CONTAINMENT_SAMPLE_SIZE",
+    "hashes::FeatureDedup",
+    "hashes::MAX_SIMHASH_DIGESTS",
+    "hashes::MinHasher",
+    "hashes::SimHasher",
+    "hashes::SketchEncoding",
+    "hashes::WidthUnit",
+    "hashes::feature_containment",
+    "hashes::minhash_from_bytes",
+    "hashes::minhash_similarity",
+    "hashes::minhash_to_bytes",
+    "hashes::minhash_to_bytes_with",
+    "hashes::minimum_hash",
+    "hashes::similarity_hash",
+    "hashes::similarity_hash_bits",
+    "hashes::similarity_hash_confidence",
+    "hashes::similarity_hash_u128",
+    "hashes::similarity_hash_with_order",
+    "hashes::sliding_window",
+    "hashes::sliding_window_slices",
+    "hashes::sliding_window_units",
+    "hashes::text_features",
+    "hashes::weighted_distance",
+    "hashes::xxhash128",
+    "hashes::xxhash32",
+    "hashes::xxhash64",
+    "hashes::xxhash64_with_seed",
+    "html",
+    "html::html_to_text",
+    "iid",
+    "iid::InstanceAlgo",
+    "iid::InstanceExpectation",
+    "iid::InstanceHasher",
+    "iid::InstanceResult",
+    "iid::TREE_LEAF_SIZE",
+    "iid::VerifyOutcome",
+    "iid::instance_id",
+    "iid::instance_id_from_reader",
+    "iid::instance_id_full",
+    "iid::instance_id_result",
+    "iid::instance_id_tree",
+    "iid::instance_id_tree_from_reader",
+    "iid::instance_id_with_algo",
+    "iid::instance_id_with_metrics",
+    "iid::instance_verify",
+    "iid::parse_tophash",
+    "iid::sha256d",
+    "iid::top_hash",
+    "index",
+    "index::MemorySketchStore",
+    "index::MinHashIndex",
+    "index::STORE_BAND_ROWS",
+    "index::SketchStore",
+    "index::StoreEntry",
+    "index::insert_sketch",
+    "index::query_similar",
+    "instance_id = crate::iid::instance_id",
+    "meta_id = crate::mid::meta_id",
+    "metrics",
+    "metrics::Metrics",
+    "metrics::Stage",
+    "mid",
+    "mid::MetaResult",
+    "mid::meta_id",
+    "mid::meta_id_from_map",
+    "mid::meta_id_full",
+    "mid::meta_id_result",
+    "mid::meta_id_with_metrics",
+    "mid::meta_id_with_options",
+    "mid::meta_id_with_trace",
+    "mid::text_trim",
+    "minimum_hash = crate::hashes::minimum_hash",
+    "multihash",
+    "multihash::MULTIHASH_BLAKE3",
+    "multihash::MULTIHASH_MERKLE_SHA2_256D",
+    "multihash::base58btc_encode",
+    "multihash::multihash",
+    "multihash::parse_multihash",
+    "normalization",
+    "normalization::meta_normalize",
+    "normalization::text_clean",
+    "normalization::text_normalize",
+    "normalization::text_normalize_robust",
+    "normalization::text_trim",
+    "options",
+    "options::ChunkingOptions",
+    "options::IsccOptions",
+    "prelude",
+    "prelude::Code = crate::code::Code",
+    "prelude::CodeKind = crate::code::CodeKind",
+    "prelude::CodeResult = crate::result::CodeResult",
+    "prelude::DataHasher = crate::did::DataHasher",
+    "prelude::FullDigest = crate::code::FullDigest",
+    "prelude::InstanceHasher = crate::iid::InstanceHasher",
+    "prelude::InstanceResult = crate::iid::InstanceResult",
+    "prelude::IsccError = crate::error::IsccError",
+    "prelude::IsccOptions = crate::options::IsccOptions",
+    "prelude::MetaResult = crate::mid::MetaResult",
+    "prelude::content_id_image = crate::cid_image::content_id_image",
+    "prelude::content_id_mixed = crate::cid_mixed::content_id_mixed",
+    "prelude::content_id_text = crate::cid_text::content_id_text",
+    "prelude::data_id = crate::did::data_id",
+    "prelude::hamming_distance_u64 = crate::compare::hamming_distance_u64",
+    "prelude::instance_id = crate::iid::instance_id",
+    "prelude::meta_id = crate::mid::meta_id",
+    "prelude::minimum_hash = crate::hashes::minimum_hash",
+    "prelude::similarity_hash = crate::hashes::similarity_hash",
+    "prelude::sliding_window = crate::hashes::sliding_window",
+    "prelude::text_normalize = crate::normalization::text_normalize",
+    "prelude::text_trim = crate::normalization::text_trim",
+    "prelude::xxhash32 = crate::hashes::xxhash32",
+    "prelude::xxhash64 = crate::hashes::xxhash64",
+    "result",
+    "result::CodeResult",
+    "script",
+    "script::Script",
+    "script::script_of",
+    "script::script_ratios",
+    "similarity_hash = crate::hashes::similarity_hash",
+    "sliding_window = crate::hashes::sliding_window",
+    "testing",
+    "testing::Case",
+    "testing::DiffReport",
+    "testing::Fixture",
+    "testing::FixtureOracle",
+    "testing::Input",
+    "testing::Mismatch",
+    "testing::ReferenceOracle",
+    "testing::differential_check",
+    "testing::gen_bytes",
+    "testing::gen_features",
+    "testing::gen_text",
+    "testing::mutate_text",
+    "text_normalize = crate::normalization::text_normalize",
+    "text_trim = crate::normalization::text_trim",
+    "trace",
+    "trace::Trace",
+    "trace::TraceStep",
+    "trace::Unit",
+    "tree",
+    "tree::TreeEntry",
+    "tree::TreeOptions",
+    "tree::TreeSum",
+    "tree::iscc_sum_tree",
+    "tree::iscc_sum_tree_with",
+    "version",
+    "version::ALGORITHM_VERSION",
+    "version::AlgorithmVersion",
+    "xxhash32 = crate::hashes::xxhash32",
+    "xxhash64 = crate::hashes::xxhash64",
+];
+
+/// Returns the `pub` items of a module source as `module::item`.
+fn public_items(module: &str, source: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut lines = source.lines();
+    while let Some(line) = lines.next() {
+        let rest = match line.strip_prefix("pub ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+        if let Some(path) = rest.strip_prefix("use ") {
+            // Collect the whole statement, it may span several lines
+            let mut statement = path.to_string();
+            while !statement.ends_with(';') {
+                statement.push_str(lines.next().expect("unterminated use").trim());
+            }
+            let statement = statement.trim_end_matches(';');
+            let (prefix, names) = match statement.find('{') {
+                Some(i) => (&statement[..i], statement[i + 1..].trim_end_matches('}')),
+                None => match statement.rfind("::") {
+                    Some(i) => (&statement[..i + 2], &statement[i + 2..]),
+                    None => ("", statement),
+                },
+            };
+            for name in names.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+                items.push(format!("{}{} = {}{}", module, name, prefix, name));
+            }
+            continue;
+        }
+        let mut words = rest.split_whitespace();
+        let kind = words.next().unwrap_or_default();
+        if let "fn" | "struct" | "enum" | "trait" | "const" | "static" | "type" | "mod" = kind {
+            let name: String = words
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            items.push(format!("{}{}", module, name));
+        }
+    }
+    items
+}
+
+#[test]
+fn test_public_api() {
+    let lib = fs::read_to_string("src/lib.rs").unwrap();
+    let mut api = public_items("", &lib);
+    let modules: Vec<String> = api
+        .iter()
+        .filter(|item| !item.contains(" = "))
+        .cloned()
+        .collect();
+    for module in modules {
+        let source = fs::read_to_string(format!("src/{}.rs", module)).unwrap();
+        api.extend(public_items(&format!("{}::", module), &source));
+    }
+    api.sort();
+
+    let mut expected: Vec<String> = PUBLIC_API.iter().map(|s| s.to_string()).collect();
+    expected.sort();
+    assert!(
+        api == expected,
+        "The public API changed, update PUBLIC_API:\n{:#?}",
+        api
+    );
+}
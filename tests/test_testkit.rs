@@ -0,0 +1,13 @@
+#![cfg(feature = "testkit")]
+use std::path::Path;
+
+use iscc::testing::{differential_check, FixtureOracle};
+
+#[test]
+fn test_differential_check_reference_fixtures() {
+    let oracle = FixtureOracle::load(Path::new("tests/test_data/reference.jsonl")).unwrap();
+    let report = differential_check(&oracle, oracle.cases()).unwrap();
+    assert!(report.is_ok(), "{}", report);
+    assert_eq!(report.checked, oracle.fixtures.len());
+    assert!(report.skipped.is_empty());
+}